@@ -0,0 +1,144 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    interner::Symbol,
+    interpreter::methods::get_method,
+    node::Expression,
+};
+
+/// Functions `rmp run` registers for every script, with their signatures.
+/// `rmp check` and the language server count them as defined.
+pub(crate) const SCRIPT_FUNCTIONS: &[(&str, &str)] =
+    &[("arg", "arg(index)"), ("arg_count", "arg_count()")];
+
+/// A problem found in a program without running it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Line the problem was found on, when the tree records one.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// Checks a parsed program for mistakes that would only surface at runtime:
+/// calls to functions that are defined nowhere, calls with the wrong number
+/// of arguments and `return` outside a function.
+///
+/// Scoping is dynamic, so a function declared anywhere in the program counts
+/// as defined. `host_functions` names functions the host registers.
+pub fn analyze(program: &Expression, host_functions: &[&str]) -> Vec<Diagnostic> {
+    let mut analyzer = Analyzer {
+        arities: HashMap::new(),
+        host_functions: host_functions.iter().map(|name| Symbol::intern(name)).collect(),
+        function_depth: 0,
+        diagnostics: Vec::new(),
+    };
+    analyzer.collect_declarations(program);
+    analyzer.check(program);
+    analyzer.diagnostics
+}
+
+struct Analyzer {
+    /// Parameter count of each declared function, `None` when declarations
+    /// of the same name disagree.
+    arities: HashMap<Symbol, Option<usize>>,
+    host_functions: Vec<Symbol>,
+    function_depth: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analyzer {
+    fn collect_declarations(&mut self, expression: &Expression) {
+        if let Expression::FunctionDeclaration(function) = expression {
+            let arity = function.arguments.len();
+            self.arities
+                .entry(function.identifier.name)
+                .and_modify(|known| {
+                    if *known != Some(arity) {
+                        *known = None;
+                    }
+                })
+                .or_insert(Some(arity));
+        }
+        for_each_child(expression, |child| self.collect_declarations(child));
+    }
+
+    fn check(&mut self, expression: &Expression) {
+        match expression {
+            Expression::FunctionCall(call) => {
+                let name = call.identifier.name;
+                match self.arities.get(&name) {
+                    Some(Some(arity)) if *arity != call.arguments.len() => {
+                        self.report(
+                            Some(call.location),
+                            format!(
+                                "Function '{}' expects {} arguments, called with {}",
+                                name,
+                                arity,
+                                call.arguments.len()
+                            ),
+                        );
+                    }
+                    Some(_) => {}
+                    None if get_method(name).is_some() || self.host_functions.contains(&name) => {}
+                    None => self.report(
+                        Some(call.location),
+                        format!("Call to undefined function '{}'", name),
+                    ),
+                }
+            }
+            Expression::Return(_) if self.function_depth == 0 => {
+                self.report(None, "'return' outside a function".to_string());
+            }
+            Expression::FunctionDeclaration(_) => {
+                self.function_depth += 1;
+                for_each_child(expression, |child| self.check(child));
+                self.function_depth -= 1;
+                return;
+            }
+            _ => {}
+        }
+        for_each_child(expression, |child| self.check(child));
+    }
+
+    fn report(&mut self, line: Option<usize>, message: String) {
+        self.diagnostics.push(Diagnostic { line, message });
+    }
+}
+
+/// Calls `visit` on each direct child of `expression`, in source order.
+pub(crate) fn for_each_child(expression: &Expression, mut visit: impl FnMut(&Expression)) {
+    let children: &[Expression] = match expression {
+        Expression::Literal(_) | Expression::Identifier(_) | Expression::Trivia(_) => &[],
+        Expression::BinaryOperation(left, _, right) => {
+            visit(left);
+            visit(right);
+            &[]
+        }
+        Expression::UnaryOperation(_, inner)
+        | Expression::Statement(inner, _)
+        | Expression::Declaration(_, inner)
+        | Expression::Return(inner) => {
+            visit(inner);
+            &[]
+        }
+        Expression::Program(program) => &program.body,
+        Expression::FunctionCall(call) => &call.arguments,
+        Expression::Block(block) => block,
+        Expression::FunctionDeclaration(function) => &function.block,
+        Expression::IfConditional(condition, if_block, else_block) => {
+            visit(condition);
+            if_block.iter().for_each(&mut visit);
+            else_block.as_deref().unwrap_or_default()
+        }
+    };
+    children.iter().for_each(visit);
+}
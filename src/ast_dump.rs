@@ -0,0 +1,232 @@
+use std::fmt::Write;
+
+use serde_json::{json, Value};
+
+use crate::node::{Block, Expression, Literal, Trivia};
+
+/// A node as it is shown in the text and DOT dumps: a label and the nodes
+/// below it. `Then` and `Else` branches become nodes of their own.
+struct DumpNode {
+    label: String,
+    children: Vec<DumpNode>,
+}
+
+impl DumpNode {
+    fn leaf(label: String) -> Self {
+        DumpNode {
+            label,
+            children: Vec::new(),
+        }
+    }
+
+    fn block(label: String, block: &Block) -> Self {
+        DumpNode {
+            label,
+            children: block.iter().map(DumpNode::from).collect(),
+        }
+    }
+}
+
+impl From<&Expression> for DumpNode {
+    fn from(expression: &Expression) -> Self {
+        let (label, children) = match expression {
+            Expression::Literal(literal) => {
+                return DumpNode::leaf(format!("Literal {}", literal_text(literal)))
+            }
+            Expression::BinaryOperation(left, operator, right) => (
+                format!("BinaryOperation {}", operator),
+                vec![left.as_ref().into(), right.as_ref().into()],
+            ),
+            Expression::UnaryOperation(operator, operand) => (
+                format!("UnaryOperation {}", operator),
+                vec![operand.as_ref().into()],
+            ),
+            Expression::Program(program) => {
+                return DumpNode::block("Program".to_string(), &program.body)
+            }
+            Expression::Statement(inner, _) => ("Statement".to_string(), vec![inner.as_ref().into()]),
+            Expression::FunctionCall(call) => (
+                format!(
+                    "FunctionCall {} (line {})",
+                    call.identifier.name, call.location
+                ),
+                call.arguments.iter().map(DumpNode::from).collect(),
+            ),
+            Expression::Identifier(identifier) => {
+                return DumpNode::leaf(format!("Identifier {}", identifier.name))
+            }
+            Expression::Declaration(identifier, value) => (
+                format!("Declaration {}", identifier.name),
+                vec![value.as_ref().into()],
+            ),
+            Expression::Block(block) => return DumpNode::block("Block".to_string(), block),
+            Expression::FunctionDeclaration(function) => {
+                let parameters: Vec<String> = function
+                    .arguments
+                    .iter()
+                    .map(|argument| argument.name.to_string())
+                    .collect();
+                return DumpNode::block(
+                    format!(
+                        "FunctionDeclaration {}({})",
+                        function.identifier.name,
+                        parameters.join(", ")
+                    ),
+                    &function.block,
+                );
+            }
+            Expression::Return(value) => ("Return".to_string(), vec![value.as_ref().into()]),
+            Expression::IfConditional(condition, if_block, else_block) => {
+                let mut children = vec![
+                    condition.as_ref().into(),
+                    DumpNode::block("Then".to_string(), if_block),
+                ];
+                if let Some(else_block) = else_block {
+                    children.push(DumpNode::block("Else".to_string(), else_block));
+                }
+                ("IfConditional".to_string(), children)
+            }
+            Expression::Trivia(Trivia::Comment { text, trailing }) => {
+                let kind = if *trailing { "TrailingComment" } else { "Comment" };
+                return DumpNode::leaf(format!("{} {}", kind, text));
+            }
+            Expression::Trivia(Trivia::BlankLine) => return DumpNode::leaf("BlankLine".to_string()),
+        };
+        DumpNode { label, children }
+    }
+}
+
+fn literal_text(literal: &Literal) -> String {
+    match literal {
+        Literal::Boolean(b) => b.to_string(),
+        Literal::Integer(i) => i.to_string(),
+        Literal::Float(f) => format!("{:?}", f),
+        Literal::String(s) => format!("{:?}", s.as_ref()),
+    }
+}
+
+/// Renders a tree as indented text, one node per line, children indented by
+/// two spaces below their parent.
+pub fn to_text(expression: &Expression) -> String {
+    fn write_node(out: &mut String, node: &DumpNode, depth: usize) {
+        let _ = writeln!(out, "{:indent$}{}", "", node.label, indent = depth * 2);
+        for child in &node.children {
+            write_node(out, child, depth + 1);
+        }
+    }
+
+    let mut out = String::new();
+    write_node(&mut out, &expression.into(), 0);
+    out
+}
+
+/// Renders a tree as a Graphviz `digraph`, with nodes numbered in pre-order.
+pub fn to_dot(expression: &Expression) -> String {
+    fn write_node(out: &mut String, node: &DumpNode, next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+        let _ = writeln!(out, "  n{} [label={}];", id, Value::from(node.label.as_str()));
+        for child in &node.children {
+            let child_id = write_node(out, child, next_id);
+            let _ = writeln!(out, "  n{} -> n{};", id, child_id);
+        }
+        id
+    }
+
+    let mut out = String::from("digraph ast {\n  node [shape=box];\n");
+    write_node(&mut out, &expression.into(), &mut 0);
+    out.push_str("}\n");
+    out
+}
+
+/// Renders a tree as JSON. Every node is an object with a `"type"` field
+/// naming its `Expression` variant; the other fields depend on the variant.
+pub fn to_json(expression: &Expression) -> String {
+    format!("{}\n", json_value(expression))
+}
+
+fn json_value(expression: &Expression) -> Value {
+    match expression {
+        Expression::Literal(literal) => {
+            let value = match literal {
+                Literal::Boolean(b) => json!(b),
+                Literal::Integer(i) => json!(i),
+                // Non-finite floats have no JSON form and become `null`.
+                Literal::Float(f) => json!(f),
+                Literal::String(s) => json!(s.as_ref()),
+            };
+            json!({ "type": "Literal", "value": value })
+        }
+        Expression::BinaryOperation(left, operator, right) => json!({
+            "type": "BinaryOperation",
+            "operator": operator.to_string(),
+            "left": json_value(left),
+            "right": json_value(right),
+        }),
+        Expression::UnaryOperation(operator, operand) => json!({
+            "type": "UnaryOperation",
+            "operator": operator.to_string(),
+            "operand": json_value(operand),
+        }),
+        Expression::Program(program) => json!({
+            "type": "Program",
+            "body": json_block(&program.body),
+        }),
+        Expression::Statement(inner, _) => json!({
+            "type": "Statement",
+            "expression": json_value(inner),
+        }),
+        Expression::FunctionCall(call) => json!({
+            "type": "FunctionCall",
+            "name": call.identifier.name.to_string(),
+            "line": call.location,
+            "arguments": json_block(&call.arguments),
+        }),
+        Expression::Identifier(identifier) => json!({
+            "type": "Identifier",
+            "name": identifier.name.to_string(),
+        }),
+        Expression::Declaration(identifier, value) => json!({
+            "type": "Declaration",
+            "name": identifier.name.to_string(),
+            "value": json_value(value),
+        }),
+        Expression::Block(block) => json!({
+            "type": "Block",
+            "body": json_block(block),
+        }),
+        Expression::FunctionDeclaration(function) => {
+            let parameters: Vec<String> = function
+                .arguments
+                .iter()
+                .map(|argument| argument.name.to_string())
+                .collect();
+            json!({
+                "type": "FunctionDeclaration",
+                "name": function.identifier.name.to_string(),
+                "parameters": parameters,
+                "body": json_block(&function.block),
+            })
+        }
+        Expression::Return(value) => json!({
+            "type": "Return",
+            "value": json_value(value),
+        }),
+        Expression::IfConditional(condition, if_block, else_block) => json!({
+            "type": "IfConditional",
+            "condition": json_value(condition),
+            "then": json_block(if_block),
+            "else": else_block.as_ref().map(|else_block| json_block(else_block)),
+        }),
+        Expression::Trivia(Trivia::Comment { text, trailing }) => json!({
+            "type": "Comment",
+            "text": text.as_ref(),
+            "trailing": trailing,
+        }),
+        Expression::Trivia(Trivia::BlankLine) => json!({ "type": "BlankLine" }),
+    }
+}
+
+fn json_block(block: &[Expression]) -> Value {
+    block.iter().map(json_value).collect()
+}
@@ -0,0 +1,11 @@
+use std::io::{self, BufReader};
+
+fn main() {
+    match parser::dap::serve(BufReader::new(io::stdin()), io::stdout()) {
+        Ok(code) => std::process::exit(code),
+        Err(err) => {
+            eprintln!("rmp-dap: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
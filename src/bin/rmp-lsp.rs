@@ -0,0 +1,13 @@
+use std::io;
+
+fn main() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    match parser::lsp::serve(stdin.lock(), stdout.lock()) {
+        Ok(code) => std::process::exit(code),
+        Err(err) => {
+            eprintln!("rmp-lsp: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
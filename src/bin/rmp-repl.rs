@@ -0,0 +1,62 @@
+use std::{env, path::PathBuf};
+
+use parser::repl::{Repl, Reply};
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+fn main() {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            eprintln!("Unable to start the line editor: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(".rmp_history"));
+    if let Some(path) = &history {
+        // A missing history file just means this is the first session.
+        let _ = editor.load_history(path);
+    }
+
+    println!("rmp REPL, :help for commands, Ctrl-D to quit.");
+
+    let mut repl = Repl::new();
+    let mut buffer = String::new();
+
+    loop {
+        let prompt = if buffer.is_empty() { ">> " } else { ".. " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if buffer.is_empty() && line.trim().is_empty() {
+                    continue;
+                }
+                buffer.push_str(&line);
+                buffer.push('\n');
+                if !Repl::is_complete(&buffer) {
+                    continue;
+                }
+
+                let input = std::mem::take(&mut buffer);
+                let _ = editor.add_history_entry(input.trim_end());
+                match repl.handle(&input) {
+                    Reply::Empty => {}
+                    Reply::Output(text) => println!("{}", text),
+                    Reply::Error(text) => eprintln!("{}", text),
+                    Reply::Quit => break,
+                }
+            }
+            // Ctrl-C drops the input typed so far.
+            Err(ReadlineError::Interrupted) => buffer.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Unable to read input: {}", err);
+                break;
+            }
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(err) = editor.save_history(path) {
+            eprintln!("Unable to save history to {}: {}", path.display(), err);
+        }
+    }
+}
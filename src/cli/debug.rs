@@ -0,0 +1,213 @@
+use std::io::{self, Write};
+
+use super::{register_script_args, report, usage_error, EXIT_FAILURE, EXIT_SUCCESS};
+use crate::{
+    error::Error,
+    interpreter::{
+        debugger::{DebugCommand, DebugFrontend, Debugger, Pause, PauseReason},
+        runtime_errors::RuntimeErrorKind,
+    },
+    Engine,
+};
+
+/// Lines shown by `list` on each side of the paused line.
+const LISTED_LINES: usize = 3;
+
+const HELP: &str = "\
+Commands:
+  continue, c         run to the next breakpoint
+  step, s             run to the next statement, entering calls
+  next, n             run to the next statement of this function
+  out, o              run until this function returns
+  break, b [<line>]   set a breakpoint, or list them
+  delete, d <line>    remove a breakpoint
+  backtrace, bt       show the call stack
+  vars, v [<frame>]   show the variables of a frame, 0 being the innermost
+  print, p <name>     show the value of a variable
+  list, l             show the source around the paused line
+  quit, q             stop the program";
+
+/// `rmp debug`: runs a program under the debugger, reading commands from
+/// stdin. It pauses on the first statement, or at the first breakpoint when
+/// some are given with `--break`.
+pub fn debug(args: &[String]) -> i32 {
+    let mut breakpoints = Vec::new();
+    let mut program_file = None;
+    let mut script_args = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if program_file.is_some() {
+            script_args.push(arg.clone());
+            continue;
+        }
+        match arg.as_str() {
+            "--break" => match args.next().and_then(|line| line.parse::<usize>().ok()) {
+                Some(line) => breakpoints.push(line),
+                None => return usage_error("--break expects a line number."),
+            },
+            "-" => return usage_error("The debugger reads its commands from stdin."),
+            _ => program_file = Some(arg.clone()),
+        }
+    }
+    let Some(program_file) = program_file else {
+        return usage_error("Missing program file.");
+    };
+    let source = match std::fs::read_to_string(&program_file) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Unable to read {}: {}", program_file, err);
+            return EXIT_FAILURE;
+        }
+    };
+
+    let mut debugger = Debugger::new(TerminalDebugger {
+        lines: source.lines().map(str::to_string).collect(),
+    });
+    if breakpoints.is_empty() {
+        debugger = debugger.stop_on_entry();
+    }
+    for line in breakpoints {
+        debugger.set_breakpoint(line);
+    }
+
+    let mut engine = Engine::new();
+    register_script_args(&mut engine, script_args);
+    engine.interpreter_mut().attach_debugger(debugger);
+
+    match engine.eval(&source) {
+        Ok(_) => {
+            println!("Program finished");
+            EXIT_SUCCESS
+        }
+        Err(Error::Runtime(err)) if err.kind == RuntimeErrorKind::Interrupted => EXIT_SUCCESS,
+        Err(err) => report(&err),
+    }
+}
+
+struct TerminalDebugger {
+    lines: Vec<String>,
+}
+
+impl TerminalDebugger {
+    fn source_line(&self, line: usize) -> String {
+        let text = line
+            .checked_sub(1)
+            .and_then(|index| self.lines.get(index))
+            .map_or("", String::as_str);
+        format!("{:>4} | {}", line, text)
+    }
+
+    /// Runs one command. Returns how to resume, or `None` to stay paused.
+    fn command(&self, pause: &mut Pause<'_>, input: &str) -> Option<DebugCommand> {
+        let mut words = input.split_whitespace();
+        let command = words.next()?;
+        let argument = words.next();
+        let line_argument = || argument.and_then(|line| line.parse::<usize>().ok());
+
+        match command {
+            "continue" | "c" => return Some(DebugCommand::Continue),
+            "step" | "s" => return Some(DebugCommand::StepInto),
+            "next" | "n" => return Some(DebugCommand::StepOver),
+            "out" | "o" => return Some(DebugCommand::StepOut),
+            "quit" | "q" => return Some(DebugCommand::Stop),
+            "break" | "b" => match (argument, line_argument()) {
+                (None, _) => {
+                    let lines: Vec<String> =
+                        pause.breakpoints().map(|line| line.to_string()).collect();
+                    if lines.is_empty() {
+                        println!("No breakpoints");
+                    } else {
+                        println!("Breakpoints at lines {}", lines.join(", "));
+                    }
+                }
+                (Some(_), Some(line)) => {
+                    pause.set_breakpoint(line);
+                    println!("Breakpoint set at line {}", line);
+                }
+                (Some(_), None) => println!("break expects a line number"),
+            },
+            "delete" | "d" => match line_argument() {
+                Some(line) => {
+                    pause.clear_breakpoint(line);
+                    println!("Breakpoint removed from line {}", line);
+                }
+                None => println!("delete expects a line number"),
+            },
+            "backtrace" | "bt" => {
+                for (index, frame) in pause.frames().iter().enumerate() {
+                    let function = frame
+                        .function
+                        .map_or("<program>".to_string(), |name| name.to_string());
+                    let line = frame.line.map_or("?".to_string(), |line| line.to_string());
+                    println!("#{} {} (line {})", index, function, line);
+                }
+            }
+            "vars" | "v" => {
+                let frames = pause.frames();
+                let index = argument.and_then(|index| index.parse().ok()).unwrap_or(0);
+                let Some(frame) = frames.get(index) else {
+                    println!("No such frame, see backtrace");
+                    return None;
+                };
+                let mut empty = true;
+                for scope in pause.scopes(frame) {
+                    if scope.variables.is_empty() {
+                        continue;
+                    }
+                    empty = false;
+                    println!("{}:", scope.name);
+                    for (name, value) in &scope.variables {
+                        println!("  {} = {}", name, value.repr());
+                    }
+                }
+                if empty {
+                    println!("No variables");
+                }
+            }
+            "print" | "p" => match argument {
+                Some(name) => match pause.lookup(name) {
+                    Some(value) => println!("{} = {}", name, value.repr()),
+                    None => println!("Undefined variable {}", name),
+                },
+                None => println!("print expects a variable name"),
+            },
+            "list" | "l" => {
+                let first = pause.line().saturating_sub(LISTED_LINES).max(1);
+                let last = (pause.line() + LISTED_LINES).min(self.lines.len());
+                for line in first..=last {
+                    let marker = if line == pause.line() { ">" } else { " " };
+                    println!("{}{}", marker, self.source_line(line));
+                }
+            }
+            "help" | "h" => println!("{}", HELP),
+            _ => println!("Unknown command {}, see help", command),
+        }
+        None
+    }
+}
+
+impl DebugFrontend for TerminalDebugger {
+    fn paused(&mut self, pause: &mut Pause<'_>) -> DebugCommand {
+        let reason = match pause.reason() {
+            PauseReason::Entry => "entry",
+            PauseReason::Breakpoint => "breakpoint",
+            PauseReason::Step => "step",
+        };
+        println!("Paused at line {} ({})", pause.line(), reason);
+        println!("{}", self.source_line(pause.line()));
+
+        loop {
+            print!("(rmp) ");
+            let _ = io::stdout().flush();
+            let mut input = String::new();
+            match io::stdin().read_line(&mut input) {
+                Ok(0) | Err(_) => return DebugCommand::Stop,
+                Ok(_) => {}
+            }
+            if let Some(command) = self.command(pause, &input) {
+                return command;
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::Path;
+
+use super::{report, single_source, usage_error, EXIT_SUCCESS};
+use crate::{
+    doc::{builtin_docs, html, markdown, program_docs},
+    Engine,
+};
+
+/// `rmp doc`: prints a reference page for the functions of a program, or
+/// with `--builtins` for the builtins, as Markdown or HTML.
+pub fn doc(args: &[String]) -> i32 {
+    let mut format = "markdown";
+    let mut builtins = false;
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some(name @ ("markdown" | "html")) => format = name,
+                _ => return usage_error("--format expects markdown or html."),
+            },
+            "--builtins" => builtins = true,
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    let (title, docs) = match (builtins, files.as_slice()) {
+        (true, []) => ("Built-in functions".to_string(), builtin_docs()),
+        (true, _) => return usage_error("--builtins takes no program file."),
+        (false, files) => {
+            let source = match single_source(files) {
+                Ok(source) => source,
+                Err(code) => return code,
+            };
+            let program = match Engine::compile(&source) {
+                Ok(program) => program,
+                Err(err) => return report(&err),
+            };
+            let title = Path::new(&files[0])
+                .file_stem()
+                .filter(|_| files[0] != "-")
+                .map_or("Functions".to_string(), |stem| stem.to_string_lossy().into_owned());
+            (title, program_docs(&program))
+        }
+    };
+
+    match format {
+        "html" => print!("{}", html(&title, &docs)),
+        _ => print!("{}", markdown(&title, &docs)),
+    }
+    EXIT_SUCCESS
+}
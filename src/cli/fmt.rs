@@ -0,0 +1,62 @@
+use std::fs;
+
+use super::{read_source, report, usage_error, EXIT_CHECK_FAILED, EXIT_FAILURE, EXIT_SUCCESS};
+use crate::formatter::format_source;
+
+/// `rmp fmt`: formats files in place, or prints stdin formatted. With
+/// `--check` nothing is written; files that are not formatted are listed
+/// and the command fails.
+pub fn fmt(args: &[String]) -> i32 {
+    let (check, paths) = match args {
+        [flag, paths @ ..] if flag == "--check" => (true, paths),
+        paths => (false, paths),
+    };
+    if paths.is_empty() {
+        return usage_error("Missing program file.");
+    }
+
+    let mut code = EXIT_SUCCESS;
+    for path in paths {
+        let result = format_file(path, check);
+        // Errors that stop a file from being formatted outrank unformatted files.
+        if result != EXIT_SUCCESS && (code == EXIT_SUCCESS || code == EXIT_CHECK_FAILED) {
+            code = result;
+        }
+    }
+    code
+}
+
+fn format_file(path: &str, check: bool) -> i32 {
+    let source = match read_source(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Unable to read {}: {}", path, err);
+            return EXIT_FAILURE;
+        }
+    };
+    let formatted = match format_source(&source) {
+        Ok(formatted) => formatted,
+        Err(err) => {
+            eprint!("{}: ", path);
+            return report(&err);
+        }
+    };
+
+    if check {
+        if formatted == source {
+            return EXIT_SUCCESS;
+        }
+        eprintln!("{} is not formatted", path);
+        return EXIT_CHECK_FAILED;
+    }
+
+    if path == "-" {
+        print!("{}", formatted);
+    } else if formatted != source {
+        if let Err(err) = fs::write(path, formatted) {
+            eprintln!("Unable to write {}: {}", path, err);
+            return EXIT_FAILURE;
+        }
+    }
+    EXIT_SUCCESS
+}
@@ -0,0 +1,108 @@
+use std::io::{self, Write};
+
+use super::{
+    report, single_source, usage_error, EXIT_CHECK_FAILED, EXIT_FAILURE, EXIT_SUCCESS,
+};
+use crate::{
+    analysis::{self, SCRIPT_FUNCTIONS},
+    ast_dump,
+    error::Error,
+    lexer::{Token, TokenParser},
+    Engine,
+};
+
+/// `rmp check`: lexes, parses and analyzes a program without running it.
+pub fn check(args: &[String]) -> i32 {
+    let source = match single_source(args) {
+        Ok(source) => source,
+        Err(code) => return code,
+    };
+    let ast = match Engine::compile(&source) {
+        Ok(ast) => ast,
+        Err(err) => return report(&err),
+    };
+
+    let host_functions: Vec<&str> = SCRIPT_FUNCTIONS.iter().map(|(name, _)| *name).collect();
+    let diagnostics = analysis::analyze(&ast, &host_functions);
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    if diagnostics.is_empty() {
+        EXIT_SUCCESS
+    } else {
+        EXIT_CHECK_FAILED
+    }
+}
+
+/// `rmp tokens`: prints the lexer's tokens, one per line.
+pub fn tokens(args: &[String]) -> i32 {
+    let source = match single_source(args) {
+        Ok(source) => source,
+        Err(code) => return code,
+    };
+    let tokens = match TokenParser::new(source).parse() {
+        Ok(tokens) => tokens,
+        Err(err) => return report(&Error::Lexer(err)),
+    };
+
+    output_status(write_tokens(&mut io::stdout().lock(), tokens))
+}
+
+fn write_tokens(out: &mut impl Write, tokens: Vec<Token>) -> io::Result<()> {
+    for token in tokens {
+        let text = match (&token.operator_type, token.value) {
+            (Some(operator), _) => format!(" {}", operator),
+            (None, Some(value)) => format!(" {:?}", value.to_string()),
+            (None, None) => String::new(),
+        };
+        writeln!(
+            out,
+            "{}:{}..{} {}{}",
+            token.line, token.start, token.end, token.token_type, text
+        )?;
+    }
+    out.flush()
+}
+
+/// `rmp ast`: prints the parsed tree as indented text, JSON or DOT.
+pub fn ast(args: &[String]) -> i32 {
+    let mut format = "text";
+    let mut rest = args;
+    if let [flag, value, tail @ ..] = args {
+        if flag == "--format" {
+            format = value;
+            rest = tail;
+        }
+    }
+    let render = match format {
+        "text" => ast_dump::to_text,
+        "json" => ast_dump::to_json,
+        "dot" => ast_dump::to_dot,
+        other => return usage_error(&format!("Unknown AST format {}.", other)),
+    };
+
+    let source = match single_source(rest) {
+        Ok(source) => source,
+        Err(code) => return code,
+    };
+    match Engine::compile(&source) {
+        Ok(ast) => {
+            let mut out = io::stdout().lock();
+            output_status(write!(out, "{}", render(&ast)).and_then(|_| out.flush()))
+        }
+        Err(err) => report(&err),
+    }
+}
+
+/// The exit code of a command that wrote `result` to stdout. A reader that
+/// stops early and closes the pipe, like `head`, is not a failure.
+fn output_status(result: io::Result<()>) -> i32 {
+    match result {
+        Ok(()) => EXIT_SUCCESS,
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => EXIT_SUCCESS,
+        Err(err) => {
+            eprintln!("Unable to write the output: {}", err);
+            EXIT_FAILURE
+        }
+    }
+}
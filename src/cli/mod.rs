@@ -0,0 +1,130 @@
+//! The `rmp` command-line tool. `src/main.rs` only forwards its arguments
+//! here and exits with the returned code.
+
+mod debug;
+mod doc;
+mod fmt;
+mod inspect;
+mod run;
+mod test;
+
+use std::io::{self, Read};
+
+use crate::{error::Error, Engine};
+
+pub const EXIT_SUCCESS: i32 = 0;
+/// Bad usage, or a file that could not be read or written.
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_LEXER_ERROR: i32 = 2;
+pub const EXIT_PARSER_ERROR: i32 = 3;
+pub const EXIT_RUNTIME_ERROR: i32 = 4;
+/// `rmp check` found problems in a program that lexes and parses,
+/// `rmp fmt --check` found a file that is not formatted, or a test of
+/// `rmp test` did not pass.
+pub const EXIT_CHECK_FAILED: i32 = 5;
+
+const USAGE: &str = "\
+Usage:
+  rmp run [--profile] [--folded <file>] [--trace] [--trace-function <name>]...
+          [--coverage] [--lcov <file>] [--watch] <file> [args...]
+  rmp debug [--break <line>]... <file> [args...]
+  rmp check <file>
+  rmp tokens <file>
+  rmp ast [--format text|json|dot] <file>
+  rmp fmt [--check] <file>...
+  rmp doc [--format markdown|html] <file> | --builtins
+  rmp test [--format human|tap|junit] [--coverage] [--lcov <file>]
+           [<file or directory>...]
+  rmp -e <code>
+
+<file> may be `-` to read the program from stdin, except with `debug` and
+`--watch`.
+`rmp <file>` is short for `rmp run <file>`.
+
+Exit codes: 1 usage or I/O error, 2 lexer error, 3 parser error, 4 runtime
+error, 5 problems found by `check` or `fmt --check`, or tests that did not
+pass.";
+
+/// Runs the command described by `args`, not including the program name,
+/// and returns the process exit code.
+pub fn main(args: Vec<String>) -> i32 {
+    let Some(command) = args.first() else {
+        eprintln!("{}", USAGE);
+        return EXIT_FAILURE;
+    };
+    let rest = &args[1..];
+
+    match command.as_str() {
+        "run" => run::run(rest),
+        "-e" => run::eval(rest),
+        "debug" => debug::debug(rest),
+        "check" => inspect::check(rest),
+        "tokens" => inspect::tokens(rest),
+        "ast" => inspect::ast(rest),
+        "fmt" => fmt::fmt(rest),
+        "doc" => doc::doc(rest),
+        "test" => test::test(rest),
+        "-h" | "--help" | "help" => {
+            println!("{}", USAGE);
+            EXIT_SUCCESS
+        }
+        _ => run::run(&args),
+    }
+}
+
+/// Reads the program named on the command line, `-` being stdin.
+fn read_source(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    } else {
+        std::fs::read_to_string(path)
+    }
+}
+
+/// Prints `err` and returns the exit code for the stage that failed.
+fn report(err: &Error) -> i32 {
+    eprintln!("{}", err.to_string().trim_end());
+    exit_code(err)
+}
+
+/// The exit code for the stage that failed with `err`.
+pub(crate) fn exit_code(err: &Error) -> i32 {
+    match err {
+        Error::Io(_) => EXIT_FAILURE,
+        Error::Lexer(_) => EXIT_LEXER_ERROR,
+        Error::Parser(_) => EXIT_PARSER_ERROR,
+        Error::Runtime(_) => EXIT_RUNTIME_ERROR,
+    }
+}
+
+fn usage_error(message: &str) -> i32 {
+    eprintln!("{}\n\n{}", message, USAGE);
+    EXIT_FAILURE
+}
+
+/// The single `<file>` argument of commands that take nothing else, read.
+fn single_source(args: &[String]) -> Result<String, i32> {
+    match args {
+        [path] => read_source(path).map_err(|err| {
+            eprintln!("Unable to read {}: {}", path, err);
+            EXIT_FAILURE
+        }),
+        [] => Err(usage_error("Missing program file.")),
+        _ => Err(usage_error("Expected a single program file.")),
+    }
+}
+
+/// Registers the `analysis::SCRIPT_FUNCTIONS`, which give scripts the
+/// arguments after their file on the command line.
+pub(crate) fn register_script_args(engine: &mut Engine, script_args: Vec<String>) {
+    let count = script_args.len() as i64;
+    let interpreter = engine.interpreter_mut();
+    interpreter.register_typed_function("arg", move |index: i64| -> Option<String> {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| script_args.get(index).cloned())
+    });
+    interpreter.register_typed_function("arg_count", move || count);
+}
@@ -0,0 +1,296 @@
+use std::{
+    fs,
+    io::{self, IsTerminal, Write},
+    thread,
+    time::Duration,
+};
+
+use super::{
+    read_source, register_script_args, report, usage_error, EXIT_FAILURE, EXIT_SUCCESS,
+};
+use crate::{
+    analysis::{self, SCRIPT_FUNCTIONS},
+    error::Error,
+    interpreter::{runtime_errors::RuntimeErrorKind, tracer::Tracer, value::Value},
+    repl, Engine,
+};
+
+/// Clears the terminal and moves the cursor to its top left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// How often `--watch` checks the program file for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The command line of `rmp run`.
+struct RunOptions {
+    profile: bool,
+    folded_output: Option<String>,
+    coverage: bool,
+    lcov_output: Option<String>,
+    trace: bool,
+    traced_functions: Vec<String>,
+    watch: bool,
+    program_file: String,
+    script_args: Vec<String>,
+}
+
+/// `rmp run`: runs a program. Arguments after the program file are passed to
+/// the script, which reads them with `arg(index)` and `arg_count()`.
+pub fn run(args: &[String]) -> i32 {
+    let options = match parse_options(args) {
+        Ok(options) => options,
+        Err(code) => return code,
+    };
+    if options.watch {
+        return watch(&options);
+    }
+
+    let source = match read_source(&options.program_file) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Unable to read {}: {}", options.program_file, err);
+            return EXIT_FAILURE;
+        }
+    };
+    run_source(&options, &source)
+}
+
+fn parse_options(args: &[String]) -> Result<RunOptions, i32> {
+    let mut profile = false;
+    let mut folded_output = None;
+    let mut coverage = false;
+    let mut lcov_output = None;
+    let mut trace = false;
+    let mut traced_functions = Vec::new();
+    let mut watch = false;
+    let mut program_file = None;
+    let mut script_args = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if program_file.is_some() {
+            script_args.push(arg.clone());
+            continue;
+        }
+        match arg.as_str() {
+            "--profile" => profile = true,
+            "--folded" => match args.next() {
+                Some(path) => {
+                    profile = true;
+                    folded_output = Some(path.clone());
+                }
+                None => return Err(usage_error("--folded expects an output file.")),
+            },
+            "--coverage" => coverage = true,
+            "--lcov" => match args.next() {
+                Some(path) => lcov_output = Some(path.clone()),
+                None => return Err(usage_error("--lcov expects an output file.")),
+            },
+            "--trace" => trace = true,
+            "--trace-function" => match args.next() {
+                Some(function) => {
+                    trace = true;
+                    traced_functions.push(function.clone());
+                }
+                None => return Err(usage_error("--trace-function expects a function name.")),
+            },
+            "--watch" => watch = true,
+            _ => program_file = Some(arg.clone()),
+        }
+    }
+    let Some(program_file) = program_file else {
+        return Err(usage_error("Missing program file."));
+    };
+    if watch && program_file == "-" {
+        return Err(usage_error("--watch needs a program file to watch."));
+    }
+
+    Ok(RunOptions {
+        profile,
+        folded_output,
+        coverage,
+        lcov_output,
+        trace,
+        traced_functions,
+        watch,
+        program_file,
+        script_args,
+    })
+}
+
+/// Runs `source` in a new interpreter with the reports `options` ask for,
+/// and returns the exit code.
+fn run_source(options: &RunOptions, source: &str) -> i32 {
+    let mut engine = new_engine(options, source);
+    let result = engine.eval(source);
+    finish_run(options, source, &engine, result)
+}
+
+/// An interpreter set up with the reports `options` ask for.
+fn new_engine(options: &RunOptions, source: &str) -> Engine {
+    let mut engine = Engine::new();
+    register_script_args(&mut engine, options.script_args.clone());
+    if options.profile {
+        engine.interpreter_mut().enable_profiler();
+    }
+    if options.coverage || options.lcov_output.is_some() {
+        engine.interpreter_mut().enable_coverage();
+    }
+    if options.trace {
+        let tracer = options
+            .traced_functions
+            .iter()
+            .fold(Tracer::new(source, Box::new(io::stderr())), |tracer, function| {
+                tracer.with_function(function)
+            });
+        engine.interpreter_mut().add_observer(tracer);
+    }
+    engine
+}
+
+/// Prints and writes the reports of a run of `source` by `engine`, and
+/// returns the exit code for `result`.
+fn finish_run(
+    options: &RunOptions,
+    source: &str,
+    engine: &Engine,
+    result: Result<Value, Error>,
+) -> i32 {
+    if let Some(profiler) = engine.interpreter().profiler() {
+        eprintln!("\n{}", profiler);
+        if let Some(path) = &options.folded_output {
+            if let Err(err) = fs::write(path, profiler.folded_stacks()) {
+                eprintln!("Unable to write folded stacks to {}: {}", path, err);
+            }
+        }
+    }
+
+    if let (Some(covered), Ok(program)) =
+        (engine.interpreter().coverage(), Engine::compile(source))
+    {
+        if options.coverage {
+            eprint!("\n{}", covered.annotate(source, &program));
+        }
+        if let Some(path) = &options.lcov_output {
+            if let Err(err) = fs::write(path, covered.lcov(&options.program_file, &program)) {
+                eprintln!("Unable to write coverage to {}: {}", path, err);
+            }
+        }
+    }
+
+    match result {
+        Ok(_) => EXIT_SUCCESS,
+        Err(err) => report(&err),
+    }
+}
+
+/// `rmp run --watch`: runs the program again, on a cleared screen, each time
+/// its file changes, until interrupted. A run still going when the file
+/// changes is stopped. Problems `rmp check` would report are shown before
+/// each run, and errors do not stop the watch.
+fn watch(options: &RunOptions) -> i32 {
+    let path = &options.program_file;
+    let mut source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Unable to read {}: {}", path, err);
+            return EXIT_FAILURE;
+        }
+    };
+
+    loop {
+        source = run_watched(options, &source);
+    }
+}
+
+/// One run of `rmp run --watch`. The file is watched while the program runs,
+/// on another thread that interrupts it if the file changes. Returns the
+/// changed source once the run is over.
+fn run_watched(options: &RunOptions, source: &str) -> String {
+    // Escape codes would only clutter output going to a file or a pipe.
+    if io::stdout().is_terminal() {
+        print!("{}", CLEAR_SCREEN);
+        let _ = io::stdout().flush();
+    }
+    if let Ok(program) = Engine::compile(source) {
+        let host_functions: Vec<&str> = SCRIPT_FUNCTIONS.iter().map(|(name, _)| *name).collect();
+        for diagnostic in analysis::analyze(&program, &host_functions) {
+            eprintln!("{}", diagnostic);
+        }
+    }
+
+    let mut engine = new_engine(options, source);
+    let interrupt = engine.interpreter().interrupt_handle();
+    thread::scope(|scope| {
+        let watcher = scope.spawn(|| {
+            let changed = wait_for_change(&options.program_file, source);
+            // Does nothing to a run that is already over.
+            interrupt.interrupt();
+            changed
+        });
+
+        match engine.eval(source) {
+            // Stopped by the watcher: the new source is run straight away.
+            Err(Error::Runtime(err)) if err.kind == RuntimeErrorKind::Interrupted => {}
+            result => {
+                let code = finish_run(options, source, &engine, result);
+                let _ = io::stdout().flush();
+
+                let outcome = match code {
+                    EXIT_SUCCESS => "finished".to_string(),
+                    code => format!("failed with exit code {}", code),
+                };
+                eprintln!(
+                    "\n[{}] Watching {} for changes, Ctrl-C to stop",
+                    outcome, options.program_file
+                );
+            }
+        }
+        watcher.join().expect("the watcher thread panicked")
+    })
+}
+
+/// Polls `path` until its content settles on something other than
+/// `current`, and returns it.
+fn wait_for_change(path: &str, current: &str) -> String {
+    // A change is only returned once the file stays the same for an
+    // interval, so that a file being written is not run half-written.
+    let mut changed: Option<String> = None;
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        // Editors may replace the file rather than write to it, so it can be
+        // missing or unreadable for a moment. Watching goes on until it can
+        // be read again.
+        match fs::read_to_string(path).ok() {
+            Some(source) if source != current => {
+                if changed.as_ref() == Some(&source) {
+                    return source;
+                }
+                changed = Some(source);
+            }
+            _ => changed = None,
+        }
+    }
+}
+
+/// `rmp -e`: runs the code given on the command line and prints the value
+/// of its last expression.
+pub fn eval(args: &[String]) -> i32 {
+    let code = match args {
+        [code] => code,
+        _ => return usage_error("-e expects the code to run."),
+    };
+    let code = repl::terminated(code);
+
+    let mut engine = Engine::new();
+    match engine.eval(&code) {
+        Ok(Value::Empty) => EXIT_SUCCESS,
+        Ok(value) => {
+            if let Value::String(text) = value.to_string() {
+                println!("{}", text);
+            }
+            EXIT_SUCCESS
+        }
+        Err(err) => report(&err),
+    }
+}
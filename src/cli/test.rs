@@ -0,0 +1,123 @@
+use std::{fs, io, path::Path};
+
+use super::{usage_error, EXIT_CHECK_FAILED, EXIT_FAILURE, EXIT_SUCCESS};
+use crate::{
+    interpreter::coverage::Coverage,
+    testing::{
+        human_report, junit_report, run_file, run_file_with_coverage, tap_report, Outcome,
+    },
+    Engine,
+};
+
+/// `rmp test`: runs the `test_*` functions of the given files, and of the
+/// `.rmp` files found under the given directories, the current one by
+/// default. Fails when a test does not pass.
+///
+/// With `--coverage` the files with tests are printed to stderr annotated
+/// with what their tests ran; `--lcov` writes the same as an lcov report.
+pub fn test(args: &[String]) -> i32 {
+    let mut format = "human";
+    let mut coverage = false;
+    let mut lcov_output = None;
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some(name @ ("human" | "tap" | "junit")) => format = name,
+                _ => return usage_error("--format expects human, tap or junit."),
+            },
+            "--coverage" => coverage = true,
+            "--lcov" => match args.next() {
+                Some(path) => lcov_output = Some(path.clone()),
+                None => return usage_error("--lcov expects an output file."),
+            },
+            _ => paths.push(arg.clone()),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(".".to_string());
+    }
+
+    let mut files = Vec::new();
+    for path in &paths {
+        if let Err(err) = collect_files(Path::new(path), &mut files) {
+            eprintln!("Unable to read {}: {}", path, err);
+            return EXIT_FAILURE;
+        }
+    }
+
+    let mut results = Vec::new();
+    let mut annotated = String::new();
+    let mut lcov = String::new();
+    for file in files {
+        let source = match fs::read_to_string(&file) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("Unable to read {}: {}", file, err);
+                return EXIT_FAILURE;
+            }
+        };
+        if !coverage && lcov_output.is_none() {
+            results.extend(run_file(&file, &source));
+            continue;
+        }
+
+        let mut covered = Coverage::new();
+        let file_results = run_file_with_coverage(&file, &source, &mut covered);
+        if let (false, Ok(program)) = (file_results.is_empty(), Engine::compile(&source)) {
+            annotated.push_str(&format!("\n{}:\n", file));
+            annotated.push_str(&covered.annotate(&source, &program));
+            lcov.push_str(&covered.lcov(&file, &program));
+        }
+        results.extend(file_results);
+    }
+
+    let report = match format {
+        "tap" => tap_report(&results),
+        "junit" => junit_report(&results),
+        _ => human_report(&results),
+    };
+    print!("{}", report);
+
+    if coverage {
+        eprint!("{}", annotated);
+    }
+    if let Some(path) = lcov_output {
+        if let Err(err) = fs::write(&path, lcov) {
+            eprintln!("Unable to write coverage to {}: {}", path, err);
+            return EXIT_FAILURE;
+        }
+    }
+
+    if results.iter().all(|result| result.outcome == Outcome::Passed) {
+        EXIT_SUCCESS
+    } else {
+        EXIT_CHECK_FAILED
+    }
+}
+
+/// Adds `path` to `files` if it is a file, or the `.rmp` files under it,
+/// sorted, if it is a directory.
+fn collect_files(path: &Path, files: &mut Vec<String>) -> io::Result<()> {
+    if !path.is_dir() {
+        // Fails for a path that does not exist.
+        fs::metadata(path)?;
+        files.push(path.display().to_string());
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_files(&entry, files)?;
+        } else if entry.extension().is_some_and(|extension| extension == "rmp") {
+            files.push(entry.display().to_string());
+        }
+    }
+    Ok(())
+}
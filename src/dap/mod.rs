@@ -0,0 +1,492 @@
+//! A Debug Adapter Protocol server for `.rmp` programs, run by the `rmp-dap`
+//! binary over stdio.
+//!
+//! A session debugs the program named by the `program` field of the
+//! `launch` request, with the strings of `args` passed to the script and
+//! `stopOnEntry` pausing on its first statement. The program runs on a single
+//! thread; breakpoints are line breakpoints in the program's file. What the
+//! program prints is sent as `output` events, and `readln` reads nothing.
+
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    cli::{exit_code, register_script_args, EXIT_FAILURE, EXIT_SUCCESS},
+    error::Error,
+    interpreter::{
+        debugger::{DebugCommand, DebugFrontend, Debugger, Pause, PauseReason},
+        runtime_errors::RuntimeErrorKind,
+    },
+    lsp::transport::{read_message, write_message},
+    shared::{MaybeSend, Shared},
+    Engine,
+};
+
+/// The id of the only thread.
+const THREAD_ID: i64 = 1;
+
+/// Serves a debug session read from `input` until the client disconnects or
+/// closes the input. Returns the exit code: 0 when the client disconnected,
+/// 1 otherwise.
+pub fn serve<R, W>(input: R, output: W) -> io::Result<i32>
+where
+    R: BufRead + MaybeSend + 'static,
+    W: Write + MaybeSend + 'static,
+{
+    let client = Shared::new(Mutex::new(Client {
+        input,
+        output,
+        seq: 0,
+        disconnected: false,
+        pending_output: None,
+    }));
+
+    // The program starts once it is launched and the client is done
+    // configuring breakpoints, in whichever order the two happen.
+    let mut launch = None;
+    let mut configured = false;
+    let mut breakpoints = Vec::new();
+    while launch.is_none() || !configured {
+        let mut client = lock(&client);
+        let Some(request) = client.read()? else {
+            return Ok(1);
+        };
+        match command(&request) {
+            "initialize" => {
+                client.respond(
+                    &request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsEvaluateForHovers": true,
+                        "supportsTerminateRequest": true,
+                    }),
+                )?;
+                client.event("initialized", json!({}))?;
+            }
+            "launch" => {
+                let arguments = &request["arguments"];
+                let Some(program) = arguments["program"].as_str() else {
+                    client.fail(&request, "launch expects a program")?;
+                    continue;
+                };
+                launch = Some(Launch {
+                    program: program.to_string(),
+                    args: arguments["args"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|arg| arg.as_str().map(str::to_string))
+                        .collect(),
+                    stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+                });
+                client.respond(&request, Value::Null)?;
+            }
+            "setBreakpoints" => {
+                breakpoints = requested_lines(&request);
+                client.respond(&request, breakpoints_body(&breakpoints))?;
+            }
+            "configurationDone" => {
+                configured = true;
+                client.respond(&request, Value::Null)?;
+            }
+            "disconnect" => {
+                client.respond(&request, Value::Null)?;
+                return Ok(0);
+            }
+            _ => client.answer(&request)?,
+        }
+    }
+
+    if let Some(launch) = launch {
+        run(&client, launch, breakpoints)?;
+    }
+
+    let mut client = lock(&client);
+    while !client.disconnected {
+        let Some(request) = client.read()? else {
+            return Ok(1);
+        };
+        match command(&request) {
+            "disconnect" => {
+                client.respond(&request, Value::Null)?;
+                client.disconnected = true;
+            }
+            _ => client.answer(&request)?,
+        }
+    }
+    Ok(0)
+}
+
+struct Launch {
+    program: String,
+    args: Vec<String>,
+    stop_on_entry: bool,
+}
+
+/// Runs the launched program under the debugger, then reports how it ended
+/// unless the client disconnected meanwhile.
+fn run<R, W>(client: &SharedClient<R, W>, launch: Launch, breakpoints: Vec<usize>) -> io::Result<()>
+where
+    R: BufRead + MaybeSend + 'static,
+    W: Write + MaybeSend + 'static,
+{
+    let code = match std::fs::read_to_string(&launch.program) {
+        Ok(source) => {
+            let name = Path::new(&launch.program)
+                .file_name()
+                .map_or(launch.program.clone(), |name| name.to_string_lossy().into_owned());
+            let mut debugger = Debugger::new(DapFrontend {
+                client: client.clone(),
+                source: json!({ "name": name, "path": launch.program }),
+            });
+            if launch.stop_on_entry {
+                debugger = debugger.stop_on_entry();
+            }
+            for line in breakpoints {
+                debugger.set_breakpoint(line);
+            }
+
+            let mut engine = Engine::new();
+            register_script_args(&mut engine, launch.args);
+            let interpreter = engine.interpreter_mut();
+            interpreter.set_input(Box::new(io::empty()));
+            interpreter.set_output(Box::new(ProgramOutput {
+                client: client.clone(),
+                category: "stdout",
+            }));
+            interpreter.set_error_output(Box::new(ProgramOutput {
+                client: client.clone(),
+                category: "stderr",
+            }));
+            interpreter.attach_debugger(debugger);
+
+            match engine.eval(&source) {
+                Ok(_) => EXIT_SUCCESS,
+                Err(Error::Runtime(err)) if err.kind == RuntimeErrorKind::Interrupted => {
+                    EXIT_SUCCESS
+                }
+                Err(err) => {
+                    let mut client = lock(client);
+                    client.flush_output()?;
+                    client.output("stderr", &err.to_string())?;
+                    exit_code(&err)
+                }
+            }
+        }
+        Err(err) => {
+            let message = format!("Unable to read {}: {}\n", launch.program, err);
+            lock(client).output("stderr", &message)?;
+            EXIT_FAILURE
+        }
+    };
+
+    let mut client = lock(client);
+    client.flush_output()?;
+    if !client.disconnected {
+        client.event("exited", json!({ "exitCode": code }))?;
+        client.event("terminated", json!({}))?;
+    }
+    Ok(())
+}
+
+struct Client<R, W> {
+    input: R,
+    output: W,
+    seq: i64,
+    disconnected: bool,
+    /// Program output not sent yet because its line is unfinished, and its
+    /// category.
+    pending_output: Option<(&'static str, String)>,
+}
+
+type SharedClient<R, W> = Shared<Mutex<Client<R, W>>>;
+
+fn lock<R, W>(client: &SharedClient<R, W>) -> MutexGuard<'_, Client<R, W>> {
+    client.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<R: BufRead, W: Write> Client<R, W> {
+    /// Reads the next request, skipping anything else the client sends.
+    fn read(&mut self) -> io::Result<Option<Value>> {
+        while let Some(body) = read_message(&mut self.input)? {
+            match serde_json::from_str::<Value>(&body) {
+                Ok(message) if message["type"] == "request" => return Ok(Some(message)),
+                _ => continue,
+            }
+        }
+        Ok(None)
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.output, &message)
+    }
+
+    fn respond(&mut self, request: &Value, body: Value) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": true,
+            "command": request["command"],
+            "body": body,
+        }))
+    }
+
+    fn fail(&mut self, request: &Value, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": false,
+            "command": request["command"],
+            "message": message,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn output(&mut self, category: &str, output: &str) -> io::Result<()> {
+        self.event("output", json!({ "category": category, "output": output }))
+    }
+
+    /// Sends program output a line at a time, as `println` writes a line in
+    /// several pieces.
+    fn program_output(&mut self, category: &'static str, output: &str) -> io::Result<()> {
+        if matches!(&self.pending_output, Some((pending, _)) if *pending != category) {
+            self.flush_output()?;
+        }
+        let (_, pending) = self.pending_output.get_or_insert((category, String::new()));
+        pending.push_str(output);
+        if let Some(end) = pending.rfind('\n') {
+            let rest = pending.split_off(end + 1);
+            let lines = std::mem::replace(pending, rest);
+            self.output(category, &lines)?;
+        }
+        Ok(())
+    }
+
+    fn flush_output(&mut self) -> io::Result<()> {
+        match self.pending_output.take() {
+            Some((category, output)) if !output.is_empty() => self.output(category, &output),
+            _ => Ok(()),
+        }
+    }
+
+    /// Answers the requests that mean the same whether the program is
+    /// running or not.
+    fn answer(&mut self, request: &Value) -> io::Result<()> {
+        match command(request) {
+            "threads" => self.respond(
+                request,
+                json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] }),
+            ),
+            "stackTrace" => self.respond(request, json!({ "stackFrames": [], "totalFrames": 0 })),
+            other => self.fail(request, &format!("Unsupported request {}", other)),
+        }
+    }
+}
+
+/// Sends what the program writes to `print` and friends as `output` events.
+struct ProgramOutput<R, W> {
+    client: SharedClient<R, W>,
+    category: &'static str,
+}
+
+impl<R: BufRead, W: Write> Write for ProgramOutput<R, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        lock(&self.client).program_output(self.category, &String::from_utf8_lossy(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct DapFrontend<R, W> {
+    client: SharedClient<R, W>,
+    /// The `Source` the stack frames point to.
+    source: Value,
+}
+
+impl<R: BufRead + MaybeSend, W: Write + MaybeSend> DebugFrontend for DapFrontend<R, W> {
+    fn paused(&mut self, pause: &mut Pause<'_>) -> DebugCommand {
+        self.serve_pause(pause).unwrap_or(DebugCommand::Stop)
+    }
+}
+
+impl<R: BufRead, W: Write> DapFrontend<R, W> {
+    /// Answers requests about the paused program until one resumes it.
+    fn serve_pause(&mut self, pause: &mut Pause<'_>) -> io::Result<DebugCommand> {
+        let mut client = lock(&self.client);
+        let reason = match pause.reason() {
+            PauseReason::Entry => "entry",
+            PauseReason::Breakpoint => "breakpoint",
+            PauseReason::Step => "step",
+        };
+        client.flush_output()?;
+        client.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )?;
+
+        // Frame ids are indexes in `frames` plus one. Variable references are
+        // handed out as scopes are requested: reference `n` is the scope at
+        // `scopes[n - 1]`, a frame index and a scope index in that frame.
+        let frames = pause.frames();
+        let mut scopes: Vec<(usize, usize)> = Vec::new();
+
+        loop {
+            let Some(request) = client.read()? else {
+                return Ok(DebugCommand::Stop);
+            };
+            let arguments = &request["arguments"];
+            let resume = match command(&request) {
+                "continue" => Some(DebugCommand::Continue),
+                "next" => Some(DebugCommand::StepOver),
+                "stepIn" => Some(DebugCommand::StepInto),
+                "stepOut" => Some(DebugCommand::StepOut),
+                "terminate" => Some(DebugCommand::Stop),
+                "disconnect" => {
+                    client.disconnected = true;
+                    Some(DebugCommand::Stop)
+                }
+                _ => None,
+            };
+            if let Some(resume) = resume {
+                let body = match resume {
+                    DebugCommand::Continue => json!({ "allThreadsContinued": true }),
+                    _ => Value::Null,
+                };
+                client.respond(&request, body)?;
+                return Ok(resume);
+            }
+
+            match command(&request) {
+                "stackTrace" => {
+                    let stack_frames: Vec<Value> = frames
+                        .iter()
+                        .enumerate()
+                        .map(|(index, frame)| {
+                            let name = frame
+                                .function
+                                .map_or("<program>".to_string(), |name| name.to_string());
+                            json!({
+                                "id": index + 1,
+                                "name": name,
+                                "line": frame.line.unwrap_or(0),
+                                "column": 1,
+                                "source": self.source,
+                            })
+                        })
+                        .collect();
+                    let body = json!({ "stackFrames": stack_frames, "totalFrames": frames.len() });
+                    client.respond(&request, body)?;
+                }
+                "scopes" => {
+                    let frame_index = arguments["frameId"]
+                        .as_u64()
+                        .and_then(|id| (id as usize).checked_sub(1))
+                        .filter(|&index| index < frames.len());
+                    let Some(frame_index) = frame_index else {
+                        client.fail(&request, "Unknown frame")?;
+                        continue;
+                    };
+                    let frame_scopes: Vec<Value> = pause
+                        .scopes(&frames[frame_index])
+                        .iter()
+                        .enumerate()
+                        .map(|(scope_index, scope)| {
+                            scopes.push((frame_index, scope_index));
+                            json!({
+                                "name": scope.name,
+                                "variablesReference": scopes.len(),
+                                "expensive": false,
+                            })
+                        })
+                        .collect();
+                    client.respond(&request, json!({ "scopes": frame_scopes }))?;
+                }
+                "variables" => {
+                    let scope = arguments["variablesReference"]
+                        .as_u64()
+                        .and_then(|reference| (reference as usize).checked_sub(1))
+                        .and_then(|index| scopes.get(index));
+                    let Some(&(frame_index, scope_index)) = scope else {
+                        client.fail(&request, "Unknown variables reference")?;
+                        continue;
+                    };
+                    let variables: Vec<Value> = pause.scopes(&frames[frame_index])[scope_index]
+                        .variables
+                        .iter()
+                        .map(|(name, value)| {
+                            json!({
+                                "name": name.to_string(),
+                                "value": value.repr(),
+                                "type": value.type_name(),
+                                "variablesReference": 0,
+                            })
+                        })
+                        .collect();
+                    client.respond(&request, json!({ "variables": variables }))?;
+                }
+                "evaluate" => {
+                    let expression = arguments["expression"].as_str().unwrap_or_default().trim();
+                    match pause.lookup(expression) {
+                        Some(value) => client.respond(
+                            &request,
+                            json!({
+                                "result": value.repr(),
+                                "type": value.type_name(),
+                                "variablesReference": 0,
+                            }),
+                        )?,
+                        None => client.fail(
+                            &request,
+                            &format!("Only variables can be evaluated: {}", expression),
+                        )?,
+                    }
+                }
+                "setBreakpoints" => {
+                    let lines = requested_lines(&request);
+                    pause.clear_breakpoints();
+                    for &line in &lines {
+                        pause.set_breakpoint(line);
+                    }
+                    client.respond(&request, breakpoints_body(&lines))?;
+                }
+                _ => client.answer(&request)?,
+            }
+        }
+    }
+}
+
+fn command(request: &Value) -> &str {
+    request["command"].as_str().unwrap_or_default()
+}
+
+/// The lines of a `setBreakpoints` request.
+fn requested_lines(request: &Value) -> Vec<usize> {
+    request["arguments"]["breakpoints"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|breakpoint| breakpoint["line"].as_u64())
+        .map(|line| line as usize)
+        .collect()
+}
+
+fn breakpoints_body(lines: &[usize]) -> Value {
+    let breakpoints: Vec<Value> = lines
+        .iter()
+        .map(|line| json!({ "verified": true, "line": line }))
+        .collect();
+    json!({ "breakpoints": breakpoints })
+}
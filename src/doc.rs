@@ -0,0 +1,195 @@
+//! Reference pages for the functions of a program or for the builtins, for
+//! `rmp doc`.
+//!
+//! Documentation is Markdown: the `///` comments of a function, or the
+//! description a builtin is registered with. Fenced code blocks in it are
+//! examples, listed after the rest of the text.
+
+use std::fmt::Write;
+
+use crate::{interpreter::methods::methods, node::Expression};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDoc {
+    pub name: String,
+    /// How the function is called, e.g. `half(n)`.
+    pub signature: String,
+    /// The documentation without its examples, empty when there is none.
+    pub description: String,
+    /// The code of each example.
+    pub examples: Vec<String>,
+}
+
+impl FunctionDoc {
+    fn new(name: &str, signature: String, doc: &str) -> Self {
+        let (description, examples) = split_examples(doc);
+        FunctionDoc {
+            name: name.to_string(),
+            signature,
+            description,
+            examples,
+        }
+    }
+}
+
+/// The functions declared at the top level of `program`, in source order.
+pub fn program_docs(program: &Expression) -> Vec<FunctionDoc> {
+    let Expression::Program(program) = program else {
+        return Vec::new();
+    };
+    program
+        .body
+        .iter()
+        .filter_map(|statement| match statement {
+            Expression::Statement(statement, _) => match statement.as_ref() {
+                Expression::FunctionDeclaration(function) => Some(function),
+                _ => None,
+            },
+            Expression::FunctionDeclaration(function) => Some(function),
+            _ => None,
+        })
+        .map(|function| {
+            let name = function.identifier.name.to_string();
+            let arguments: Vec<String> = function
+                .arguments
+                .iter()
+                .map(|argument| argument.name.to_string())
+                .collect();
+            let signature = format!("{}({})", name, arguments.join(", "));
+            FunctionDoc::new(&name, signature, function.doc.as_deref().unwrap_or(""))
+        })
+        .collect()
+}
+
+/// Every builtin registered with `register_method!` and its variants,
+/// sorted by name.
+pub fn builtin_docs() -> Vec<FunctionDoc> {
+    let mut docs: Vec<FunctionDoc> = methods()
+        .map(|method| FunctionDoc::new(method.name, method.signature.to_string(), method.doc))
+        .collect();
+    docs.sort_by(|a, b| a.name.cmp(&b.name));
+    docs
+}
+
+/// Separates the fenced code blocks of `doc` from the rest of its text.
+fn split_examples(doc: &str) -> (String, Vec<String>) {
+    let mut description = Vec::new();
+    let mut examples = Vec::new();
+    let mut example: Option<Vec<&str>> = None;
+
+    for line in doc.lines() {
+        let fence = line.trim_start().starts_with("```");
+        match (&mut example, fence) {
+            (None, true) => example = Some(Vec::new()),
+            (None, false) => description.push(line),
+            (Some(lines), true) => {
+                examples.push(lines.join("\n"));
+                example = None;
+            }
+            (Some(lines), false) => lines.push(line),
+        }
+    }
+    // An example left open runs to the end of the documentation.
+    if let Some(lines) = example {
+        examples.push(lines.join("\n"));
+    }
+
+    (description.join("\n").trim().to_string(), examples)
+}
+
+/// A Markdown page titled `title`, with a section per function.
+pub fn markdown(title: &str, docs: &[FunctionDoc]) -> String {
+    let mut page = format!("# {}\n", title);
+    for doc in docs {
+        let _ = write!(page, "\n## {}\n\n```rmp\n{}\n```\n", doc.name, doc.signature);
+        if !doc.description.is_empty() {
+            let _ = write!(page, "\n{}\n", doc.description);
+        }
+        if !doc.examples.is_empty() {
+            page.push_str("\n### Examples\n");
+            for example in &doc.examples {
+                let _ = write!(page, "\n```rmp\n{}\n```\n", example);
+            }
+        }
+    }
+    page
+}
+
+/// A standalone HTML page titled `title`, with a table of contents and a
+/// section per function. Descriptions are shown as paragraphs, with
+/// `` `code` `` spans kept.
+pub fn html(title: &str, docs: &[FunctionDoc]) -> String {
+    let title = escape_html(title);
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+
+    if !docs.is_empty() {
+        page.push_str("<ul>\n");
+        for doc in docs {
+            let name = escape_html(&doc.name);
+            let _ = writeln!(page, "<li><a href=\"#{name}\"><code>{name}</code></a></li>");
+        }
+        page.push_str("</ul>\n");
+    }
+
+    for doc in docs {
+        let _ = writeln!(
+            page,
+            "<h2 id=\"{}\">{}</h2>\n<pre><code>{}</code></pre>",
+            escape_html(&doc.name),
+            escape_html(&doc.name),
+            escape_html(&doc.signature)
+        );
+        for paragraph in doc.description.split("\n\n") {
+            let paragraph = paragraph.trim();
+            if !paragraph.is_empty() {
+                let _ = writeln!(page, "<p>{}</p>", inline_code(&escape_html(paragraph)));
+            }
+        }
+        if !doc.examples.is_empty() {
+            page.push_str("<h3>Examples</h3>\n");
+            for example in &doc.examples {
+                let _ = writeln!(page, "<pre><code>{}</code></pre>", escape_html(example));
+            }
+        }
+    }
+
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Turns the `` `code` `` spans of `text` into `<code>` elements. An
+/// unmatched backtick is kept as is.
+fn inline_code(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    let mut parts = text.split('`');
+    html.push_str(parts.next().unwrap_or(""));
+    while let Some(code) = parts.next() {
+        match parts.next() {
+            Some(rest) => {
+                let _ = write!(html, "<code>{}</code>{}", code, rest);
+            }
+            None => {
+                html.push('`');
+                html.push_str(code);
+            }
+        }
+    }
+    html
+}
@@ -0,0 +1,67 @@
+use std::{fs, path::Path};
+
+use crate::{
+    error::Error,
+    interpreter::{config::InterpreterConfig, value::Value, Interpreter},
+    lexer::TokenParser,
+    node::Expression,
+    parser::Parser,
+    shared::Shared,
+};
+
+/// Lexes, parses and runs programs in one call.
+///
+/// ```
+/// use parser::{interpreter::value::Value, Engine};
+///
+/// let mut engine = Engine::new();
+/// assert_eq!(engine.eval("let x = 20; x * 2 + 2;").unwrap(), Value::Integer(42));
+/// ```
+pub struct Engine {
+    interpreter: Interpreter,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::with_config(InterpreterConfig::default())
+    }
+
+    pub fn with_config(config: InterpreterConfig) -> Self {
+        Engine {
+            interpreter: Interpreter::with_config(config),
+        }
+    }
+
+    /// Lexes and parses `source` without running it.
+    pub fn compile(source: &str) -> Result<Box<Expression>, Error> {
+        let tokens = TokenParser::new(source.to_string()).parse()?;
+        Ok(Parser::new(tokens).parse()?)
+    }
+
+    /// Runs `source` and returns the value of its last statement if that
+    /// statement is an expression, `Value::Empty` otherwise.
+    pub fn eval(&mut self, source: &str) -> Result<Value, Error> {
+        let ast = Self::compile(source)?;
+        let value = self.interpreter.eval(ast.as_ref())?;
+        Ok(Shared::unwrap_or_clone(value))
+    }
+
+    pub fn eval_file(&mut self, path: impl AsRef<Path>) -> Result<Value, Error> {
+        let source = fs::read_to_string(path)?;
+        self.eval(&source)
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,4 +1,66 @@
+use std::{fmt, io};
+
+use crate::{
+    interpreter::runtime_errors::RuntimeError, lexer_errors::LexerInvalidTokenError,
+    parser_errors::ParserError,
+};
+
 pub fn error(error_message: &str) -> ! {
     eprintln!("[Error] {}", error_message);
     std::process::exit(1);
 }
+
+/// Any failure while loading, lexing, parsing or running a program.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Lexer(LexerInvalidTokenError),
+    Parser(ParserError),
+    Runtime(RuntimeError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "IO error: {}", err),
+            Error::Lexer(err) => write!(f, "{}", err),
+            Error::Parser(err) => write!(f, "{}", err),
+            Error::Runtime(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Lexer(err) => Some(err),
+            Error::Parser(err) => Some(err),
+            Error::Runtime(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<LexerInvalidTokenError> for Error {
+    fn from(err: LexerInvalidTokenError) -> Self {
+        Error::Lexer(err)
+    }
+}
+
+impl From<ParserError> for Error {
+    fn from(err: ParserError) -> Self {
+        Error::Parser(err)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(err: RuntimeError) -> Self {
+        Error::Runtime(err)
+    }
+}
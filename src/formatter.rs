@@ -0,0 +1,227 @@
+use crate::{
+    error::Error,
+    lexer::{OperatorType, TokenParser},
+    node::{Block, Expression, Literal, Trivia},
+    parser::Parser,
+};
+
+const INDENT: &str = "    ";
+
+/// Formats a program canonically: four-space indentation, one statement per
+/// line, single spaces around binary operators and after commas, opening
+/// braces on the line of their statement, and parentheses only where
+/// precedence needs them.
+///
+/// Comments are kept, and runs of blank lines between statements are kept as
+/// a single one. Formatting already formatted source leaves it unchanged.
+pub fn format_source(source: &str) -> Result<String, Error> {
+    let tokens = TokenParser::new(source.to_string())
+        .with_comments()
+        .parse()?;
+    let program = Parser::with_trivia(tokens).parse()?;
+
+    let mut formatter = Formatter {
+        out: String::with_capacity(source.len()),
+        depth: 0,
+    };
+    if let Expression::Program(program) = program.as_ref() {
+        formatter.write_statements(&program.body);
+    }
+    Ok(formatter.out)
+}
+
+struct Formatter {
+    out: String,
+    depth: usize,
+}
+
+impl Formatter {
+    fn write_statements(&mut self, block: &Block) {
+        let mut wrote_line = false;
+        let mut blank_line = false;
+
+        for expression in block {
+            match expression {
+                // Blank lines at the start and end of a block are dropped.
+                Expression::Trivia(Trivia::BlankLine) => blank_line = wrote_line,
+                Expression::Trivia(Trivia::Comment {
+                    text,
+                    trailing: true,
+                }) if wrote_line => {
+                    self.out.pop();
+                    self.out.push(' ');
+                    self.out.push_str(text.trim_end());
+                    self.out.push('\n');
+                }
+                statement => {
+                    if blank_line {
+                        self.out.push('\n');
+                        blank_line = false;
+                    }
+                    self.write_indent();
+                    match statement {
+                        Expression::Trivia(Trivia::Comment { text, .. }) => {
+                            self.out.push_str(text.trim_end())
+                        }
+                        statement => self.write_statement(statement),
+                    }
+                    self.out.push('\n');
+                    wrote_line = true;
+                }
+            }
+        }
+    }
+
+    fn write_statement(&mut self, statement: &Expression) {
+        match statement {
+            Expression::Statement(inner, _) => self.write_statement(inner),
+            Expression::Declaration(identifier, value) => {
+                self.out.push_str(&format!(
+                    "let {} = {};",
+                    identifier.name,
+                    expression_text(value)
+                ));
+            }
+            Expression::Return(value) => {
+                self.out
+                    .push_str(&format!("return {};", expression_text(value)));
+            }
+            Expression::FunctionDeclaration(function) => {
+                let parameters: Vec<String> = function
+                    .arguments
+                    .iter()
+                    .map(|argument| argument.name.to_string())
+                    .collect();
+                self.out.push_str(&format!(
+                    "func {}({}) ",
+                    function.identifier.name,
+                    parameters.join(", ")
+                ));
+                self.write_block(&function.block);
+            }
+            Expression::IfConditional(condition, if_block, else_block) => {
+                self.out
+                    .push_str(&format!("if ({}) ", expression_text(condition)));
+                self.write_block(if_block);
+                if let Some(else_block) = else_block {
+                    self.out.push_str(" else ");
+                    match else_block.as_slice() {
+                        [else_if @ Expression::Statement(inner, _)]
+                            if matches!(inner.as_ref(), Expression::IfConditional(..)) =>
+                        {
+                            self.write_statement(else_if)
+                        }
+                        _ => self.write_block(else_block),
+                    }
+                }
+            }
+            Expression::Block(block) => self.write_block(block),
+            expression => {
+                self.out.push_str(&expression_text(expression));
+                self.out.push(';');
+            }
+        }
+    }
+
+    fn write_block(&mut self, block: &Block) {
+        if block.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push_str("{\n");
+        self.depth += 1;
+        self.write_statements(block);
+        self.depth -= 1;
+        self.write_indent();
+        self.out.push('}');
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+}
+
+pub(crate) fn expression_text(expression: &Expression) -> String {
+    match expression {
+        Expression::Literal(literal) => literal_text(literal),
+        Expression::Identifier(identifier) => identifier.name.to_string(),
+        Expression::FunctionCall(call) => {
+            let arguments: Vec<String> = call.arguments.iter().map(expression_text).collect();
+            format!("{}({})", call.identifier.name, arguments.join(", "))
+        }
+        // The operand of a unary operator is parsed as a single term.
+        Expression::UnaryOperation(operator, operand) => match operand.as_ref() {
+            Expression::BinaryOperation(..) => {
+                format!("{}({})", operator, expression_text(operand))
+            }
+            _ => format!("{}{}", operator, expression_text(operand)),
+        },
+        Expression::BinaryOperation(left, operator, right) => {
+            let (precedence, right_associative) = operator.precedence();
+            // Mirrors `Parser::parse_binary_expression`: the right operand is
+            // parsed at `precedence + 1`, or `precedence` when right-associative.
+            let left_needs_parens = |inner: i32| {
+                inner < precedence || (inner == precedence && right_associative)
+            };
+            let right_minimum = if right_associative {
+                precedence
+            } else {
+                precedence + 1
+            };
+            format!(
+                "{} {} {}",
+                operand_text(operator, left, left_needs_parens),
+                operator,
+                operand_text(operator, right, |inner| inner < right_minimum)
+            )
+        }
+        Expression::Statement(inner, _) => expression_text(inner),
+        other => unreachable!("{:?} is not an expression", other),
+    }
+}
+
+fn operand_text(
+    parent: &OperatorType,
+    operand: &Expression,
+    needs_parens: impl Fn(i32) -> bool,
+) -> String {
+    let text = expression_text(operand);
+    match operand {
+        Expression::BinaryOperation(_, operator, _)
+            if needs_parens(operator.precedence().0) || reads_ambiguously(parent, operator) =>
+        {
+            format!("({})", text)
+        }
+        _ => text,
+    }
+}
+
+/// Comparisons and boolean operators bind tighter than arithmetic, so
+/// `"a" + 1 == 1` means `"a" + (1 == 1)`. The parentheses are kept to avoid
+/// misreading it.
+fn reads_ambiguously(parent: &OperatorType, operand: &OperatorType) -> bool {
+    let is_logical = |operator: &OperatorType| {
+        matches!(operator, OperatorType::Comp(_) | OperatorType::Boolean(_))
+    };
+    !is_logical(parent) && is_logical(operand)
+}
+
+fn literal_text(literal: &Literal) -> String {
+    match literal {
+        Literal::Boolean(b) => b.to_string(),
+        Literal::Integer(i) => i.to_string(),
+        // `Display` never uses an exponent, which the lexer could not read
+        // back, but drops the fraction of integral values.
+        Literal::Float(f) => {
+            let text = f.to_string();
+            if text.contains('.') {
+                text
+            } else {
+                format!("{}.0", text)
+            }
+        }
+        Literal::String(s) => format!("\"{}\"", s),
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use once_cell::sync::Lazy;
+
+/// An interned name: an identifier, a keyword or an operator.
+///
+/// Symbols are plain integers, so copying, hashing and comparing them is cheap.
+/// The interner is shared by the lexer, the parser and the runtime scopes, so
+/// a symbol produced by `TokenParser` can be used directly as a scope key.
+///
+/// Interned names are never freed, so only names go in: literals and comments
+/// stay as text. Code that looks a name up on behalf of a host uses
+/// `Symbol::lookup`, which does not add it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+#[derive(Debug, Default)]
+pub struct Interner {
+    symbols: HashMap<Arc<str>, Symbol>,
+    names: Vec<Arc<str>>,
+}
+
+static INTERNER: Lazy<RwLock<Interner>> = Lazy::new(|| RwLock::new(Interner::default()));
+
+impl Interner {
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+
+        let symbol = Symbol(self.names.len() as u32);
+        let name: Arc<str> = Arc::from(name);
+        self.names.push(name.clone());
+        self.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> Arc<str> {
+        self.names[symbol.0 as usize].clone()
+    }
+}
+
+impl Symbol {
+    /// Returns the symbol for `name`, interning it on first use.
+    pub fn intern(name: &str) -> Symbol {
+        if let Some(symbol) = Self::lookup(name) {
+            return symbol;
+        }
+        INTERNER.write().expect("interner poisoned").intern(name)
+    }
+
+    /// Returns the symbol for `name` if it was interned already. Nothing the
+    /// interpreter knows can be named by a string that was never interned.
+    pub fn lookup(name: &str) -> Option<Symbol> {
+        INTERNER.read().expect("interner poisoned").get(name)
+    }
+
+    /// Resolves the symbol back to the text it was interned from.
+    pub fn resolve(self) -> Arc<str> {
+        INTERNER.read().expect("interner poisoned").resolve(self)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.resolve())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symbol;
+
+    #[test]
+    fn interning_is_idempotent() {
+        let first = Symbol::intern("interned_name");
+        let second = Symbol::intern("interned_name");
+        let other = Symbol::intern("other_name");
+
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert_eq!(&*first.resolve(), "interned_name");
+        assert_eq!(other.to_string(), "other_name");
+    }
+
+    #[test]
+    fn lookup_does_not_intern() {
+        assert_eq!(Symbol::lookup("never_interned_name"), None);
+        assert_eq!(Symbol::lookup("never_interned_name"), None);
+
+        let symbol = Symbol::intern("looked_up_name");
+        assert_eq!(Symbol::lookup("looked_up_name"), Some(symbol));
+    }
+}
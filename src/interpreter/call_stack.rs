@@ -1,8 +1,8 @@
-use crate::interpreter::runtime_errors::StackAttachable;
+use crate::{interner::Symbol, interpreter::runtime_errors::StackAttachable};
 
 #[derive(Debug, Clone)]
 pub struct StackFrame {
-    pub function: String,
+    pub function: Symbol,
     pub location: Option<usize>, // optional line:col info
 }
 
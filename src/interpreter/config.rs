@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+/// Maximum number of nested user function calls allowed by default.
+///
+/// This limit stops runaway recursion with a `CallDepthExceeded` error. It
+/// does not protect the native stack, which the interpreter guards by itself
+/// (see `STACK_RED_ZONE`), so it is set for the memory a script may use:
+/// each nested call takes about 20 KiB of stack in debug builds and a few
+/// KiB in release builds, so about 200 MiB and a few tens of MiB at this
+/// depth.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+/// Script calls recurse on the native stack. When less than this is left as
+/// a call starts, the call runs on a new stack segment of
+/// `STACK_SEGMENT_SIZE` bytes allocated on the heap, whatever the size of
+/// the thread's own stack.
+///
+/// It has to cover what the interpreter uses between two calls: the frames
+/// of one script call, of the builtins it calls and of the expressions
+/// nested in its arguments. A single statement nesting expressions deeper
+/// than that can still overflow the stack, which aborts the process. So
+/// does failing to allocate a segment.
+pub(crate) const STACK_RED_ZONE: usize = 256 * 1024;
+
+/// Size of the stack segments allocated once the red zone is reached.
+pub(crate) const STACK_SEGMENT_SIZE: usize = 2 * 1024 * 1024;
+
+/// Limits applied while running a program. Every limit other than the call
+/// depth is off by default; set them when evaluating untrusted scripts.
+#[derive(Debug, Clone)]
+pub struct InterpreterConfig {
+    /// Nested user function calls allowed before a runtime error is raised.
+    /// Tail calls (`return f(x);`) reuse the caller's slot and do not count.
+    pub max_call_depth: usize,
+    /// Statements and expressions evaluated per `run`.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time allowed per `run`.
+    pub time_limit: Option<Duration>,
+    /// Longest string, in bytes, a program may produce.
+    pub max_string_length: Option<usize>,
+    /// Scopes alive at the same time, one per active block or call.
+    pub max_scopes: Option<usize>,
+    /// Variables defined across all live scopes.
+    pub max_variables: Option<usize>,
+}
+
+impl Default for InterpreterConfig {
+    fn default() -> Self {
+        InterpreterConfig {
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_steps: None,
+            time_limit: None,
+            max_string_length: None,
+            max_scopes: None,
+            max_variables: None,
+        }
+    }
+}
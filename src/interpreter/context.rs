@@ -0,0 +1,82 @@
+use crate::{
+    interner::Symbol,
+    interpreter::{
+        call_stack::CallStack,
+        io::{Input, Output},
+        methods::{NativeFnArgs, NativeFnReturn},
+        runtime_errors::RuntimeError,
+        value::Value,
+        Interpreter,
+    },
+    shared::Shared,
+};
+
+/// What a call from a native function resolves to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Callee {
+    /// A function defined by the script, taking `arity` arguments.
+    Script { arity: usize },
+    /// A builtin, host function or object method, which checks its own
+    /// arguments.
+    Native,
+}
+
+/// Gives native functions access to the interpreter that called them.
+pub struct NativeContext<'a> {
+    interpreter: &'a mut Interpreter,
+}
+
+impl<'a> NativeContext<'a> {
+    pub(crate) fn new(interpreter: &'a mut Interpreter) -> Self {
+        NativeContext { interpreter }
+    }
+
+    /// Calls a function visible from the calling script, resolved the same way
+    /// as a call written in the script. When the call fails, the interpreter
+    /// is left as it was before it, so the error may be handled.
+    pub fn call(&mut self, name: &str, args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
+        let location = self
+            .call_stack()
+            .frames
+            .last()
+            .and_then(|frame| frame.location)
+            .unwrap_or(0);
+        let Some(symbol) = Symbol::lookup(name) else {
+            let err = RuntimeError::new(format!("Method not found: {}", name));
+            return Err(self.interpreter.execution_context().attach_stack(err));
+        };
+        self.interpreter.invoke_from_native(symbol, args, location)
+    }
+
+    /// What `call` would run for `name` and `args`, without running it.
+    /// `None` when no function of that name is visible.
+    pub fn resolve(&self, name: &str, args: &NativeFnArgs) -> Option<Callee> {
+        self.interpreter.resolve(Symbol::lookup(name)?, args)
+    }
+
+    /// Looks up a variable in the scope the native function was called from.
+    pub fn lookup_variable(&self, name: &str) -> Option<Shared<Value>> {
+        self.interpreter
+            .execution_context()
+            .lookup_variable_in_scope(Symbol::lookup(name)?)
+    }
+
+    pub fn call_stack(&self) -> &CallStack {
+        self.interpreter.execution_context().call_stack()
+    }
+
+    /// The reader `readln` takes its lines from.
+    pub fn input(&mut self) -> &mut dyn Input {
+        self.interpreter.input()
+    }
+
+    /// The writer `print`-style builtins send their output to.
+    pub fn output(&mut self) -> &mut dyn Output {
+        self.interpreter.output()
+    }
+
+    /// The writer `eprint`-style builtins send their output to.
+    pub fn error_output(&mut self) -> &mut dyn Output {
+        self.interpreter.error_output()
+    }
+}
@@ -0,0 +1,310 @@
+use std::any::{self, Any};
+
+use crate::{
+    interpreter::{
+        methods::NativeFnReturn, object::HostObject, runtime_errors::RuntimeError, value::Value,
+    },
+    shared::{MaybeSync, Shared},
+};
+
+/// Converts a script value into a Rust argument of a typed native function.
+///
+/// The lifetime lets borrowed types such as `&str` point into the argument
+/// list for the duration of the call.
+pub trait FromValue<'a>: Sized {
+    /// Whether the argument may be left out by the caller. Only trailing
+    /// arguments can be optional.
+    const OPTIONAL: bool = false;
+
+    fn from_value(value: &'a Value) -> Result<Self, ConversionError>;
+
+    /// The value used when an optional argument is left out.
+    fn from_missing() -> Option<Self> {
+        None
+    }
+}
+
+/// Converts a Rust value returned by a typed native function into a script value.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+/// The value a `FromValue` implementation expected, along with the type of
+/// the value it got instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionError {
+    pub expected: &'static str,
+    pub found: String,
+}
+
+impl ConversionError {
+    pub fn new(expected: &'static str, found: &Value) -> Self {
+        let found = match found {
+            Value::Object(object) => object.type_name(),
+            other => other.type_name(),
+        };
+        ConversionError {
+            expected,
+            found: found.to_string(),
+        }
+    }
+}
+
+impl FromValue<'_> for i64 {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Integer(i) => Ok(*i),
+            other => Err(ConversionError::new("integer", other)),
+        }
+    }
+}
+
+impl FromValue<'_> for f64 {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Integer(i) => Ok(*i as f64),
+            Value::Float(f) => Ok(*f),
+            other => Err(ConversionError::new("number", other)),
+        }
+    }
+}
+
+impl FromValue<'_> for bool {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Boolean(b) => Ok(*b),
+            other => Err(ConversionError::new("boolean", other)),
+        }
+    }
+}
+
+impl FromValue<'_> for String {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::String(s) => Ok(s.to_string()),
+            other => Err(ConversionError::new("string", other)),
+        }
+    }
+}
+
+impl<'a> FromValue<'a> for &'a str {
+    fn from_value(value: &'a Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::String(s) => Ok(s),
+            other => Err(ConversionError::new("string", other)),
+        }
+    }
+}
+
+impl FromValue<'_> for Value {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue<'_> for HostObject {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Object(object) => Ok(object.clone()),
+            other => Err(ConversionError::new("object", other)),
+        }
+    }
+}
+
+/// The value held by a host object, which must be a `T`.
+impl<T: Any + MaybeSync> FromValue<'_> for Shared<T> {
+    fn from_value(value: &Value) -> Result<Self, ConversionError> {
+        let expected = any::type_name::<T>()
+            .rsplit("::")
+            .next()
+            .unwrap_or("object");
+        match value {
+            Value::Object(object) => object
+                .downcast::<T>()
+                .ok_or_else(|| ConversionError::new(expected, value)),
+            other => Err(ConversionError::new(expected, other)),
+        }
+    }
+}
+
+/// `Value::Empty` and a missing trailing argument both convert to `None`.
+impl<'a, T: FromValue<'a>> FromValue<'a> for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn from_value(value: &'a Value) -> Result<Self, ConversionError> {
+        match value {
+            Value::Empty => Ok(None),
+            other => T::from_value(other).map(Some),
+        }
+    }
+
+    fn from_missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Integer(self)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(Shared::from(self))
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(Shared::from(self))
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for HostObject {
+    fn into_value(self) -> Value {
+        Value::Object(self)
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Empty
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Empty, IntoValue::into_value)
+    }
+}
+
+/// The return type of a typed native function: a value, or a
+/// `Result` whose error is reported as a runtime error.
+pub trait IntoNativeReturn {
+    fn into_native_return(self) -> Result<NativeFnReturn, RuntimeError>;
+}
+
+impl<T: IntoValue> IntoNativeReturn for T {
+    fn into_native_return(self) -> Result<NativeFnReturn, RuntimeError> {
+        Ok(self.into_value().into_rc())
+    }
+}
+
+impl<T: IntoValue> IntoNativeReturn for Result<T, RuntimeError> {
+    fn into_native_return(self) -> Result<NativeFnReturn, RuntimeError> {
+        self.map(|value| value.into_value().into_rc())
+    }
+}
+
+/// A Rust function whose parameters implement `FromValue` and whose return
+/// type implements `IntoNativeReturn`, callable with the untyped argument list
+/// of a native function.
+///
+/// `Args` is the tuple of parameter types; it only exists to tell the
+/// implementations for each arity apart and is always inferred.
+pub trait TypedFunction<'a, Args> {
+    fn call_typed(
+        &self,
+        name: &str,
+        args: &'a [Shared<Value>],
+    ) -> Result<NativeFnReturn, RuntimeError>;
+}
+
+fn check_arity(name: &str, optional: &[bool], found: usize) -> Result<(), RuntimeError> {
+    let max = optional.len();
+    let min = max
+        - optional
+            .iter()
+            .rev()
+            .take_while(|optional| **optional)
+            .count();
+
+    if found < min || found > max {
+        let expected = if min == max {
+            min.to_string()
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return Err(RuntimeError::new(format!(
+            "Function '{}' expected {} arguments, got {}",
+            name, expected, found
+        )));
+    }
+    Ok(())
+}
+
+fn argument<'a, T: FromValue<'a>>(
+    name: &str,
+    args: &'a [Shared<Value>],
+    position: usize,
+) -> Result<T, RuntimeError> {
+    match args.get(position) {
+        Some(value) => T::from_value(value).map_err(|err| {
+            RuntimeError::new(format!(
+                "Function '{}' expected {} for argument {}, got {}",
+                name,
+                err.expected,
+                position + 1,
+                err.found
+            ))
+        }),
+        // `check_arity` only lets optional arguments be left out.
+        None => Ok(T::from_missing().expect("missing argument should be optional")),
+    }
+}
+
+macro_rules! impl_typed_function {
+    ($($arg:ident),*) => {
+        impl<'a, F, R, $($arg,)*> TypedFunction<'a, ($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R,
+            R: IntoNativeReturn,
+            $($arg: FromValue<'a>,)*
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables, unused_assignments)]
+            fn call_typed(
+                &self,
+                name: &str,
+                args: &'a [Shared<Value>],
+            ) -> Result<NativeFnReturn, RuntimeError> {
+                check_arity(name, &[$($arg::OPTIONAL),*], args.len())?;
+
+                let mut position = 0;
+                $(
+                    let $arg = argument::<$arg>(name, args, position)?;
+                    position += 1;
+                )*
+                (self)($($arg),*).into_native_return()
+            }
+        }
+    };
+}
+
+impl_typed_function!();
+impl_typed_function!(A1);
+impl_typed_function!(A1, A2);
+impl_typed_function!(A1, A2, A3);
+impl_typed_function!(A1, A2, A3, A4);
+impl_typed_function!(A1, A2, A3, A4, A5);
+impl_typed_function!(A1, A2, A3, A4, A5, A6);
+impl_typed_function!(A1, A2, A3, A4, A5, A6, A7);
+impl_typed_function!(A1, A2, A3, A4, A5, A6, A7, A8);
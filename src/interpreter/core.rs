@@ -1,8 +1,29 @@
-use std::rc::Rc;
+use std::{
+    collections::HashMap,
+    io::{self, BufReader},
+    sync::Arc,
+    time::Instant,
+};
 
-use super::methods::get_method;
+use super::methods::{get_method, HostFn, NativeFnArgs, NativeFnReturn};
+use crate::{
+    interner::Symbol,
+    shared::{MaybeSync, Shared},
+};
 use super::value::Value;
-use crate::interpreter::{execution_context::ExecutionContext, runtime_errors::RuntimeError};
+use crate::interpreter::{
+    config::{InterpreterConfig, STACK_RED_ZONE, STACK_SEGMENT_SIZE},
+    context::{Callee, NativeContext},
+    convert::TypedFunction,
+    coverage::Coverage,
+    debugger::Debugger,
+    execution_context::{ExecutionContext, TailCall},
+    interrupt::InterruptHandle,
+    io::{Input, Output},
+    observer::Observer,
+    profiler::{FunctionKind, Profiler},
+    runtime_errors::{RuntimeError, RuntimeErrorKind},
+};
 use crate::lexer::{
     AdditiveOperatorSubtype, BooleanOperatorSubtype, CompOperatorSubtype, MultiplicativeOperatorSubtype, OperatorType, UnaryOperatorSubtype
 };
@@ -16,46 +37,295 @@ pub enum ControlFlow {
 
 pub struct Interpreter {
     execution_context: ExecutionContext,
+    config: InterpreterConfig,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    debugger: Option<Debugger>,
+    observers: Vec<Box<dyn Observer>>,
+    steps: u64,
+    started: Instant,
+    interrupt: InterruptHandle,
+    last_value: Option<Shared<Value>>,
+    host_functions: HashMap<Symbol, Shared<HostFn>>,
+    input: Box<dyn Input>,
+    output: Box<dyn Output>,
+    error_output: Box<dyn Output>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        Self::with_config(InterpreterConfig::default())
+    }
+
+    pub fn with_config(config: InterpreterConfig) -> Self {
+        Self::with_io(
+            config,
+            Box::new(BufReader::new(io::stdin())),
+            Box::new(io::stdout()),
+            Box::new(io::stderr()),
+        )
+    }
+
+    /// Creates an interpreter whose I/O builtins read from `input` and write to
+    /// `output` and `error_output` instead of the process stdin, stdout and stderr.
+    pub fn with_io(
+        config: InterpreterConfig,
+        input: Box<dyn Input>,
+        output: Box<dyn Output>,
+        error_output: Box<dyn Output>,
+    ) -> Self {
         Interpreter {
             execution_context: ExecutionContext::new(),
+            config,
+            profiler: None,
+            coverage: None,
+            debugger: None,
+            observers: Vec::new(),
+            steps: 0,
+            started: Instant::now(),
+            interrupt: InterruptHandle::new(),
+            last_value: None,
+            host_functions: HashMap::new(),
+            input,
+            output,
+            error_output,
         }
     }
 
+    /// Replaces the reader `readln` takes its lines from.
+    pub fn set_input(&mut self, input: Box<dyn Input>) {
+        self.input = input;
+    }
+
+    /// Replaces the writer `print` and `println` send their output to.
+    pub fn set_output(&mut self, output: Box<dyn Output>) {
+        self.output = output;
+    }
+
+    /// Replaces the writer `eprint` and `eprintln` send their output to.
+    pub fn set_error_output(&mut self, error_output: Box<dyn Output>) {
+        self.error_output = error_output;
+    }
+
+    /// Starts recording call counts and timings for every function call.
+    pub fn enable_profiler(&mut self) {
+        self.profiler = Some(Profiler::new());
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Starts counting the statements that run and the way each `if` goes.
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Lets `debugger` pause the programs this interpreter runs.
+    pub fn attach_debugger(&mut self, debugger: Debugger) {
+        self.debugger = Some(debugger);
+    }
+
+    pub fn debugger_mut(&mut self) -> Option<&mut Debugger> {
+        self.debugger.as_mut()
+    }
+
+    pub fn detach_debugger(&mut self) -> Option<Debugger> {
+        self.debugger.take()
+    }
+
+    /// Reports the statements, calls and definitions of the programs this
+    /// interpreter runs to `observer`, after those of the observers added
+    /// before it.
+    pub fn add_observer(&mut self, observer: impl Observer + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub fn clear_observers(&mut self) {
+        self.observers.clear();
+    }
+
+    /// Makes `function` callable from scripts run by this interpreter as `name`.
+    ///
+    /// Host functions are looked up after functions defined by the script and
+    /// before the builtins registered with `register_method!`, so they can
+    /// shadow a builtin for this interpreter only.
+    pub fn register_function<F>(&mut self, name: &str, function: F)
+    where
+        F: Fn(&mut NativeContext, NativeFnArgs) -> Result<NativeFnReturn, RuntimeError>
+            + MaybeSync
+            + 'static,
+    {
+        self.host_functions
+            .insert(Symbol::intern(name), Shared::new(function));
+    }
+
+    /// Registers an ordinary Rust function or closure as a host function.
+    ///
+    /// Arguments are converted with `FromValue` and the result with
+    /// `IntoValue`, so the arity and argument types are checked before
+    /// `function` runs:
+    ///
+    /// ```
+    /// # use parser::Engine;
+    /// let mut engine = Engine::new();
+    /// engine
+    ///     .interpreter_mut()
+    ///     .register_typed_function("hypot", |a: f64, b: f64| a.hypot(b));
+    /// assert!(engine.eval("hypot(3, \"4\");").is_err());
+    /// ```
+    pub fn register_typed_function<F, Args>(&mut self, name: &str, function: F)
+    where
+        F: for<'a> TypedFunction<'a, Args> + MaybeSync + 'static,
+    {
+        let label = name.to_string();
+        self.register_function(name, move |_, args| function.call_typed(&label, &args));
+    }
+
+    /// Defines `name` for every program this interpreter runs. Scripts may
+    /// shadow it with their own `let`.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.execution_context
+            .define_global(Symbol::intern(name), value.into_rc(), false);
+    }
+
+    /// Defines `name` for every program this interpreter runs. Scripts that
+    /// try to redefine it with `let` fail with a runtime error.
+    pub fn set_readonly_global(&mut self, name: &str, value: Value) {
+        self.execution_context
+            .define_global(Symbol::intern(name), value.into_rc(), true);
+    }
+
+    /// Looks up a variable defined at the top level of a program run in this
+    /// session, or set with `set_global`.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.execution_context
+            .lookup_global_variable(Symbol::lookup(name)?)
+            .map(Shared::unwrap_or_clone)
+    }
+
+    /// Iterates over the variables `get_global` can see.
+    pub fn globals(&self) -> impl Iterator<Item = (Arc<str>, Value)> + '_ {
+        self.execution_context
+            .globals()
+            .map(|(name, value)| (name.resolve(), value.as_ref().clone()))
+    }
+
+    /// Iterates over the functions programs have defined at the top level.
+    pub fn global_functions(&self) -> impl Iterator<Item = (Arc<str>, &FunctionDeclaration)> {
+        self.execution_context
+            .global_functions()
+            .map(|(name, function)| (name.resolve(), function))
+    }
+
+    /// Forgets every function and variable programs have defined at the top
+    /// level, so the next run starts from a clean session. Globals set by the
+    /// host are kept.
+    pub fn reset(&mut self) {
+        self.execution_context.reset_session();
+        self.last_value = None;
+    }
+
+    /// Returns a handle that stops the running program from another thread.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Runs a program. When it fails, calls and blocks still in progress are
+    /// unwound so the interpreter can run again.
+    ///
+    /// Functions and variables defined at the top level of the program are
+    /// kept in the session scope, so later runs and `call` can use them until
+    /// `reset` is called. Definitions made before a failure are kept too.
     pub fn run(&mut self, node: Option<&Expression>) -> Result<(), RuntimeError> {
-        self.evaluate(node)?;
-        Ok(())
+        self.steps = 0;
+        self.started = Instant::now();
+        self.forget_last_statement();
+        let result = self.evaluate(node);
+        self.unwind_on_error(result).map(|_| ())
+    }
+
+    /// Calls a function defined at the top level of a program run in this
+    /// session, for example to use scripts as plugins:
+    ///
+    /// ```
+    /// # use parser::{interpreter::value::Value, Engine};
+    /// let mut engine = Engine::new();
+    /// engine.eval("func score(n) { return n * 10; }").unwrap();
+    /// let score = engine.interpreter_mut().call("score", &[Value::Integer(3)]);
+    /// assert_eq!(score.unwrap(), Value::Integer(30));
+    /// ```
+    ///
+    /// The call runs under the same limits as `run`, counted from the start of
+    /// the call.
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
+        let (name, function) = Symbol::lookup(name)
+            .and_then(|symbol| {
+                let function = self.execution_context.lookup_global_function(symbol)?;
+                Some((symbol, function))
+            })
+            .ok_or_else(|| RuntimeError::new(format!("Function not found: {}", name)))?;
+        let arguments: Vec<_> = args.iter().cloned().map(Value::into_rc).collect();
+        self.check_arity(name, &function, &arguments)?;
+
+        self.steps = 0;
+        self.started = Instant::now();
+        let result = self.call_function(TailCall {
+            name,
+            function,
+            arguments,
+            location: None,
+            inherited: Default::default(),
+        });
+        self.unwind_on_error(result).map(Shared::unwrap_or_clone)
+    }
+
+    /// Runs a program and returns the value of its last statement if that
+    /// statement is an expression, `Value::Empty` otherwise.
+    pub fn eval(&mut self, node: &Expression) -> Result<Shared<Value>, RuntimeError> {
+        self.last_value = None;
+        self.run(Some(node))?;
+        Ok(self
+            .last_value
+            .take()
+            .unwrap_or_else(|| Value::Empty.into_rc()))
     }
 
     pub fn evaluate(&mut self, node: Option<&Expression>) -> Result<ControlFlow, RuntimeError> {
         if let Some(node_content) = node {
             match node_content {
-                Expression::Program(program) => {
-                    Ok(self.evaluate_program(program)?)
-                },
-                Expression::BinaryOperation(_, _, _) => {
+                Expression::Statement(_, line) => self.before_statement(*line)?,
+                _ => self.tick()?,
+            }
+            match node_content {
+                Expression::Program(program) => self.evaluate_program(program),
+                Expression::BinaryOperation(_, _, _)
+                | Expression::UnaryOperation(_, _)
+                | Expression::Identifier(_)
+                | Expression::Literal(_) => {
                     self.evaluate_expression(node_content)?;
                     Ok(ControlFlow::Continue)
                 }
-                Expression::Statement(_)
+                Expression::Statement(..)
                 | Expression::Declaration(_, _)
-                | Expression::FunctionCall(_) => Ok(self.evaluate_statement(node_content)?),
+                | Expression::FunctionCall(_) => self.evaluate_statement(node_content),
                 Expression::IfConditional(expression, if_block, else_block) => {
-                    self.evaluate_conditional(expression, if_block, else_block)?;
-                    Ok(ControlFlow::Continue)
+                    self.evaluate_conditional(expression, if_block, else_block, None)
                 }
                 Expression::Return(_) => {
                     self.evaluate_return(node_content)?;
-                    
-                    return Ok(ControlFlow::Break);
+
+                    Ok(ControlFlow::Break)
                 },
                 Expression::FunctionDeclaration(function_declaration) => {
                     self.evaluate_function_definition(function_declaration)?;
                     Ok(ControlFlow::Continue)
                 }
+                Expression::Trivia(_) => Ok(ControlFlow::Continue),
                 _ => panic!("Unexpected AST node"),
             }
         }
@@ -65,14 +335,74 @@ impl Interpreter {
         }
     }
 
+    /// Checks for an interruption and the limits, then hands the statement
+    /// starting on `line` to coverage, the profiler, the observers and the
+    /// debugger.
+    ///
+    /// Kept out of `evaluate`, which has a frame on the native stack for
+    /// every nested statement and call, so that frame stays small.
+    #[inline(never)]
+    fn before_statement(&mut self, line: usize) -> Result<(), RuntimeError> {
+        if self.interrupt.take() {
+            return Err(self.error_with_kind(
+                RuntimeErrorKind::Interrupted,
+                "Execution interrupted".to_string(),
+            ));
+        }
+        self.tick()?;
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.statement(line, self.execution_context.call_stack().frames.len());
+        }
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.statement(line);
+        }
+        self.notify(|observer, depth| observer.statement(line, depth));
+        if let Some(debugger) = self.debugger.as_mut() {
+            if !debugger.before_statement(line, &self.execution_context) {
+                return Err(self.error_with_kind(
+                    RuntimeErrorKind::Interrupted,
+                    "Stopped by the debugger".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the program's statements directly in the session scope, so what
+    /// they define outlives the program.
     fn evaluate_program(&mut self, program: &Program) -> Result<ControlFlow, RuntimeError> {
-        let statements = &program.body;
-        self.evaluate_block(statements)
+        self.execution_context.enter_session_scope();
+        for statement in &program.body {
+            if let ControlFlow::Break = self.evaluate(Some(statement))? {
+                return Ok(ControlFlow::Break);
+            }
+        }
+        Ok(ControlFlow::Continue)
     }
 
     fn evaluate_return(&mut self, expression: &Expression) -> Result<(), RuntimeError> {
         if self.execution_context.is_in_function() {
             if let Expression::Return(inner_expression) = expression {
+                // `return f(x);` to a user function is handed back to the call loop
+                // in `call_function` so tail recursion does not grow the native stack.
+                if let Expression::FunctionCall(call) = inner_expression.as_ref() {
+                    let name = call.identifier.name;
+                    if let Some(function) = self.execution_context.lookup_function_in_scope(name) {
+                        let arguments = self.evaluate_arguments(&call.arguments)?;
+                        self.check_arity(name, &function, &arguments)?;
+                        let inherited = self
+                            .execution_context
+                            .definitions_for_tail_call(&function);
+                        self.execution_context.set_tail_call(TailCall {
+                            name,
+                            function,
+                            arguments,
+                            location: Some(call.location),
+                            inherited,
+                        });
+                        return Ok(());
+                    }
+                }
                 let value = self.evaluate_expression(inner_expression)?;
                 self.execution_context.set_return_value(value);
             } else {
@@ -86,6 +416,7 @@ impl Interpreter {
 
     fn evaluate_block(&mut self, block: &Block) -> Result<ControlFlow, RuntimeError> {
         let (parent_scope, _) = self.execution_context.enter_new_scope();
+        self.check_scope_limit()?;
         let mut break_invoked = false;
         for statement in block {
             let statement = self.evaluate(Some(statement))?;
@@ -103,10 +434,27 @@ impl Interpreter {
 
     fn evaluate_statement(&mut self, expression: &Expression) -> Result<ControlFlow, RuntimeError> {
         match expression {
-            Expression::Statement(expr) => {
-                let eval = self.evaluate(Some(expr.as_ref()))?;
-                Ok(eval)
-            }
+            // Every statement overwrites `last_value`, so once a top-level
+            // statement finishes it holds that statement's value.
+            Expression::Statement(expr, line) => match expr.as_ref() {
+                Expression::BinaryOperation(_, _, _)
+                | Expression::UnaryOperation(_, _)
+                | Expression::Identifier(_)
+                | Expression::Literal(_)
+                | Expression::FunctionCall(_) => {
+                    self.last_value = Some(self.evaluate_expression(expr)?);
+                    Ok(ControlFlow::Continue)
+                }
+                statement => {
+                    let eval = match statement {
+                        Expression::IfConditional(condition, if_block, else_block) => self
+                            .evaluate_conditional(condition, if_block, else_block, Some(*line)),
+                        _ => self.evaluate(Some(statement)),
+                    }?;
+                    self.last_value = None;
+                    Ok(eval)
+                }
+            },
             Expression::Declaration(identifier, expr) => {
                 self.evaluate_assignment(identifier, expr)?;
                 Ok(ControlFlow::Continue)
@@ -115,23 +463,50 @@ impl Interpreter {
                 self.evaluate_function_call(method_call)?;
                 Ok(ControlFlow::Continue)
             }
-            _ => return Err(self.error_with_stack("Unexpected AST node")),
+            _ => Err(self.error_with_stack("Unexpected AST node")),
         }
     }
 
+    /// `line` is where the `if` starts, when it is a statement of its own.
     fn evaluate_conditional(
         &mut self,
         expression: &Expression,
         if_block: &Block,
         else_block: &Option<Block>,
-    ) -> Result<(), RuntimeError> {
-        let expression_result = self.evaluate_expression(expression)?;
-        if expression_result.to_bool() {
-            self.evaluate_block(if_block)?;
+        line: Option<usize>,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let taken = self.evaluate_expression(expression)?.to_bool();
+        if let Some(line) = line {
+            self.cover_branch(line, taken);
+        }
+        if taken {
+            self.evaluate_block(if_block)
         } else if let Some(else_block) = else_block {
-            self.evaluate_block(else_block)?;
+            self.evaluate_block(else_block)
+        } else {
+            Ok(ControlFlow::Continue)
+        }
+    }
+
+    /// Makes the next statement at the current call depth count as a new
+    /// line for coverage and the debugger, even on the line that ran last,
+    /// as when a run starts or a call binds its parameters.
+    #[inline(never)]
+    fn forget_last_statement(&mut self) {
+        let depth = self.execution_context.call_stack().frames.len();
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.forget_line(depth);
+        }
+        if let Some(debugger) = self.debugger.as_mut() {
+            debugger.forget_last_statement();
+        }
+    }
+
+    #[inline(never)]
+    fn cover_branch(&mut self, line: usize, taken: bool) {
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.branch(line, taken);
         }
-        Ok(())
     }
 
     fn evaluate_assignment(
@@ -139,10 +514,17 @@ impl Interpreter {
         identifier: &Identifier,
         expression: &Expression,
     ) -> Result<(), RuntimeError> {
+        if self.execution_context.is_readonly(identifier.name) {
+            return Err(self.error_with_stack(&format!(
+                "Cannot redefine read-only global {}",
+                identifier.name
+            )));
+        }
         let value = self.evaluate_expression(expression)?;
+        self.notify(|observer, depth| observer.define_variable(identifier.name, &value, depth));
         self.execution_context
-            .define_variable_in_scope(&identifier.name, value)?;
-        Ok(())
+            .define_variable_in_scope(identifier.name, value)?;
+        self.check_variable_limit()
     }
 
     fn evaluate_function_definition(
@@ -150,68 +532,248 @@ impl Interpreter {
         node: &FunctionDeclaration,
     ) -> Result<(), RuntimeError> {
         self.execution_context
-            .define_function_in_scope(&node.identifier.name, node.clone())?;
+            .define_function_in_scope(node.identifier.name, Shared::new(node.clone()))?;
         Ok(())
     }
 
-    fn evaluate_function_call(&mut self, node: &FunctionCall) -> Result<Rc<Value>, RuntimeError> {
-        let method_name = &node.identifier.name;
-        if let Some(function) = self.execution_context.lookup_function_in_scope(method_name) {
-            let FunctionDeclaration {
-                arguments: param_names,
-                block,
-                ..
-            } = function;
-            let evaluated_args = self.evaluate_arguments(&node.arguments)?;
-
-            if param_names.len() != evaluated_args.len() {
-                return Err(self.error_with_stack(&format!(
-                    "Function '{}' expected {} arguments, got {}",
-                    method_name,
-                    param_names.len(),
-                    evaluated_args.len()
-                )));
+    fn evaluate_function_call(&mut self, node: &FunctionCall) -> Result<Shared<Value>, RuntimeError> {
+        let args = self.evaluate_arguments(&node.arguments)?;
+        self.invoke(node.identifier.name, args, node.location)
+    }
+
+    /// Calls `name` with already evaluated arguments, trying script functions
+    /// first, then methods of a host object passed as the first argument, then
+    /// host functions, then the registered builtins.
+    pub(crate) fn invoke(
+        &mut self,
+        name: Symbol,
+        args: Vec<Shared<Value>>,
+        location: usize,
+    ) -> Result<Shared<Value>, RuntimeError> {
+        if let Some(function) = self.execution_context.lookup_function_in_scope(name) {
+            self.check_arity(name, &function, &args)?;
+
+            return self.call_function(TailCall {
+                name,
+                function,
+                arguments: args,
+                location: Some(location),
+                inherited: Default::default(),
+            });
+        }
+
+        self.notify(|observer, depth| {
+            observer.enter_function(name, FunctionKind::Builtin, &args, depth)
+        });
+        self.execution_context.push_frame(name, Some(location));
+        self.profile_enter(FunctionKind::Builtin);
+
+        let object_method = match args.first().map(|arg| arg.as_ref()) {
+            Some(Value::Object(object)) => object.object_type().method(name),
+            _ => None,
+        };
+
+        let result = match object_method.or_else(|| self.host_functions.get(&name).cloned()) {
+            Some(function) => function(&mut NativeContext::new(self), args),
+            None => match get_method(name) {
+                Some(method) => method.call(&mut NativeContext::new(self), args),
+                None => Err(RuntimeError::new(format!("Method not found: {}", name))),
+            },
+        };
+
+        // Errors raised by script functions called back from the native
+        // already carry their stack.
+        let result = result.map_err(|err| {
+            if err.stack.is_empty() {
+                self.execution_context.attach_stack(err)
+            } else {
+                err
             }
+        });
 
-            let (parent_scope, _) = self.execution_context.enter_new_scope();
+        self.execution_context.pop_frame();
+        self.profile_exit();
+        if let Ok(value) = &result {
+            self.notify(|observer, depth| observer.exit_function(name, Some(value), depth));
+        }
 
-            // Function arguments are not passed at reference. cloning values.
-            for (param, value) in param_names.into_iter().zip(evaluated_args.into_iter()) {
-                self.execution_context
-                    .define_variable_in_scope(&param.name, value.as_ref().clone().into_rc())?;
+        let result = result?;
+        self.check_string_length(&result)?;
+        Ok(result)
+    }
+
+    /// What `invoke` would run for `name` and `args`, looked up in the same
+    /// order.
+    pub(crate) fn resolve(&self, name: Symbol, args: &[Shared<Value>]) -> Option<Callee> {
+        let scope = self.execution_context.current_scope();
+        if let Some(function) = self.execution_context.scope_arena().lookup_function(scope, name) {
+            return Some(Callee::Script {
+                arity: function.arguments.len(),
+            });
+        }
+
+        let object_method = match args.first().map(|arg| arg.as_ref()) {
+            Some(Value::Object(object)) => object.object_type().method(name).is_some(),
+            _ => false,
+        };
+        (object_method || self.host_functions.contains_key(&name) || get_method(name).is_some())
+            .then_some(Callee::Native)
+    }
+
+    /// `invoke` for a native function calling back into the script. A failed
+    /// call leaves the interpreter as it was before it, so that the native
+    /// function may carry on after the error.
+    pub(crate) fn invoke_from_native(
+        &mut self,
+        name: Symbol,
+        args: Vec<Shared<Value>>,
+        location: usize,
+    ) -> Result<Shared<Value>, RuntimeError> {
+        let checkpoint = self.execution_context.checkpoint();
+        let result = self.invoke(name, args, location);
+        if result.is_err() {
+            self.execution_context.rewind(checkpoint);
+            if let Some(profiler) = self.profiler.as_mut() {
+                profiler.unwind_to(checkpoint.frames());
+            }
+        }
+        result
+    }
+
+    fn unwind_on_error<T>(&mut self, result: Result<T, RuntimeError>) -> Result<T, RuntimeError> {
+        if result.is_err() {
+            self.execution_context.unwind();
+            if let Some(profiler) = self.profiler.as_mut() {
+                profiler.unwind();
             }
+        }
+        result
+    }
 
-            self.execution_context
-                .push_frame(method_name.clone(), Some(node.location));
-            self.execution_context.enter_function();
+    pub(crate) fn execution_context(&self) -> &ExecutionContext {
+        &self.execution_context
+    }
 
-            self.evaluate_block(&block)?;
+    pub(crate) fn input(&mut self) -> &mut dyn Input {
+        self.input.as_mut()
+    }
 
-            let return_value = self
-                .execution_context
-                .exit_function_with_return()
-                .unwrap_or(Value::Empty);
+    pub(crate) fn output(&mut self) -> &mut dyn Output {
+        self.output.as_mut()
+    }
 
-            self.execution_context.pop_frame();
-            self.execution_context.restore_scope(parent_scope);
+    pub(crate) fn error_output(&mut self) -> &mut dyn Output {
+        self.error_output.as_mut()
+    }
 
-            Ok(return_value.into_rc())
-        } else {
-            self.execution_context
-                .push_frame(method_name.clone(), Some(node.location));
+    fn check_arity(
+        &mut self,
+        name: Symbol,
+        function: &FunctionDeclaration,
+        args: &[Shared<Value>],
+    ) -> Result<(), RuntimeError> {
+        if function.arguments.len() != args.len() {
+            return Err(self.error_with_stack(&format!(
+                "Function '{}' expected {} arguments, got {}",
+                name,
+                function.arguments.len(),
+                args.len()
+            )));
+        }
+        Ok(())
+    }
 
-            let args = self.evaluate_arguments(&node.arguments)?;
-            let result = get_method(method_name.clone(), args);
+    /// Runs a user function, on a new stack segment when the native stack is
+    /// close to running out.
+    fn call_function(&mut self, call: TailCall) -> Result<Shared<Value>, RuntimeError> {
+        stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, || {
+            self.run_function(call)
+        })
+    }
 
-            let result = result.map_err(|err| self.execution_context.attach_stack(err));
+    fn run_function(&mut self, call: TailCall) -> Result<Shared<Value>, RuntimeError> {
+        if self.execution_context.function_depth() >= self.config.max_call_depth {
+            return Err(self.error_with_kind(
+                RuntimeErrorKind::CallDepthExceeded,
+                format!("Maximum call depth of {} exceeded", self.config.max_call_depth),
+            ));
+        }
 
-            self.execution_context.pop_frame();
-            
-            result
+        self.notify(|observer, depth| {
+            observer.enter_function(call.name, FunctionKind::User, &call.arguments, depth)
+        });
+        self.execution_context
+            .push_frame(call.name, call.location);
+        self.profile_enter(FunctionKind::User);
+        self.execution_context.enter_function();
+
+        let mut name = call.name;
+        let mut function = call.function;
+        let mut arguments = call.arguments;
+        let mut inherited = call.inherited;
+        let caller_scope = self.execution_context.current_scope();
+
+        loop {
+            // What the caller of a tail call defined stays visible to the
+            // callee, as it would be in a nested call.
+            if !inherited.is_empty() {
+                self.execution_context.enter_new_scope();
+                self.check_scope_limit()?;
+                self.execution_context.define_all(inherited);
+            }
+            self.execution_context.enter_parameter_scope();
+            self.check_scope_limit()?;
+            self.forget_last_statement();
+
+            // Function arguments are not passed at reference. cloning values.
+            for (param, value) in function.arguments.iter().zip(arguments) {
+                self.execution_context
+                    .define_variable_in_scope(param.name, value.as_ref().clone().into_rc())?;
+            }
+            self.check_variable_limit()?;
+
+            self.evaluate_block(&function.block)?;
+            self.execution_context.restore_scope(caller_scope);
+
+            // A tail call replaces the current frame instead of nesting a new one.
+            match self.execution_context.take_tail_call() {
+                Some(tail_call) => {
+                    self.execution_context.pop_frame();
+                    self.profile_exit();
+                    self.notify(|observer, depth| {
+                        observer.exit_function(name, None, depth);
+                        observer.enter_function(
+                            tail_call.name,
+                            FunctionKind::User,
+                            &tail_call.arguments,
+                            depth,
+                        );
+                    });
+                    self.execution_context
+                        .push_frame(tail_call.name, tail_call.location);
+                    self.profile_enter(FunctionKind::User);
+                    name = tail_call.name;
+                    function = tail_call.function;
+                    arguments = tail_call.arguments;
+                    inherited = tail_call.inherited;
+                }
+                None => break,
+            }
         }
+
+        let return_value = self
+            .execution_context
+            .exit_function_with_return()
+            .unwrap_or(Value::Empty);
+
+        self.execution_context.pop_frame();
+        self.profile_exit();
+        self.notify(|observer, depth| observer.exit_function(name, Some(&return_value), depth));
+
+        Ok(return_value.into_rc())
     }
 
-    fn evaluate_arguments(&mut self, args: &[Expression]) -> Result<Vec<Rc<Value>>, RuntimeError> {
+    fn evaluate_arguments(&mut self, args: &[Expression]) -> Result<Vec<Shared<Value>>, RuntimeError> {
         let mut results = Vec::with_capacity(args.len());
         for expr in args {
             results.push(self.evaluate_expression(expr)?);
@@ -219,11 +781,12 @@ impl Interpreter {
         Ok(results)
     }
 
-    fn evaluate_expression(&mut self, node: &Expression) -> Result<Rc<Value>, RuntimeError> {
+    fn evaluate_expression(&mut self, node: &Expression) -> Result<Shared<Value>, RuntimeError> {
+        self.tick()?;
         match node {
             Expression::Identifier(identifier) => {
-                let identifier = identifier.name.clone();
-                let result = self.execution_context.lookup_variable_in_scope(&identifier);
+                let identifier = identifier.name;
+                let result = self.execution_context.lookup_variable_in_scope(identifier);
 
                 // Cloning variable. Considering a way to pass the reference so that cloning is
                 // not necessary. Variables should not be cloned.
@@ -235,7 +798,7 @@ impl Interpreter {
                 Literal::Boolean(b) => Value::Boolean(*b).into_rc(),
                 Literal::Integer(i) => Value::Integer(*i).into_rc(),
                 Literal::Float(f) => Value::Float(*f).into_rc(),
-                Literal::String(s) => Value::String(s.clone()).into_rc(), // Cheap Rc clone
+                Literal::String(s) => Value::String(s.clone()).into_rc(), // Cheap Shared clone
             }),
             Expression::FunctionCall(method_call) => self.evaluate_function_call(method_call),
             Expression::UnaryOperation(operator, expr) => {
@@ -285,7 +848,9 @@ impl Interpreter {
                         left_val.sub_value(right_val.as_ref())?
                     }
                     OperatorType::Additive(AdditiveOperatorSubtype::Add) => {
-                        left_val.add_value(right_val.as_ref())?
+                        let sum = left_val.add_value(right_val.as_ref())?;
+                        self.check_string_length(&sum)?;
+                        sum
                     }
                     OperatorType::Comp(comp_type) => match comp_type {
                         CompOperatorSubtype::Eq => left_val.eq_value(&right_val),
@@ -306,6 +871,99 @@ impl Interpreter {
         }
     }
 
+    /// Counts one evaluation step and enforces the step and time limits.
+    fn tick(&mut self) -> Result<(), RuntimeError> {
+        self.steps += 1;
+
+        if let Some(max_steps) = self.config.max_steps {
+            if self.steps > max_steps {
+                return Err(self.error_with_kind(
+                    RuntimeErrorKind::StepLimitExceeded,
+                    format!("Step limit of {} exceeded", max_steps),
+                ));
+            }
+        }
+
+        if let Some(time_limit) = self.config.time_limit {
+            if self.started.elapsed() > time_limit {
+                return Err(self.error_with_kind(
+                    RuntimeErrorKind::DeadlineExceeded,
+                    format!("Time limit of {:?} exceeded", time_limit),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_string_length(&mut self, value: &Value) -> Result<(), RuntimeError> {
+        if let (Some(max_length), Value::String(s)) = (self.config.max_string_length, value) {
+            if s.len() > max_length {
+                return Err(self.error_with_kind(
+                    RuntimeErrorKind::StringTooLong,
+                    format!(
+                        "String of {} bytes exceeds the limit of {}",
+                        s.len(),
+                        max_length
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_scope_limit(&mut self) -> Result<(), RuntimeError> {
+        if let Some(max_scopes) = self.config.max_scopes {
+            if self.execution_context.scope_count() > max_scopes {
+                return Err(self.error_with_kind(
+                    RuntimeErrorKind::ScopeLimitExceeded,
+                    format!("Limit of {} live scopes exceeded", max_scopes),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_variable_limit(&mut self) -> Result<(), RuntimeError> {
+        if let Some(max_variables) = self.config.max_variables {
+            if self.execution_context.variable_count() > max_variables {
+                return Err(self.error_with_kind(
+                    RuntimeErrorKind::VariableLimitExceeded,
+                    format!("Limit of {} live variables exceeded", max_variables),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn error_with_kind(&self, kind: RuntimeErrorKind, msg: String) -> RuntimeError {
+        self.execution_context
+            .attach_stack(RuntimeError::with_kind(kind, msg))
+    }
+
+    fn profile_enter(&mut self, kind: FunctionKind) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.enter(self.execution_context.call_stack(), kind);
+        }
+    }
+
+    fn profile_exit(&mut self) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.exit();
+        }
+    }
+
+    /// Hands an event to every observer, with the current call depth.
+    fn notify(&mut self, mut event: impl FnMut(&mut dyn Observer, usize)) {
+        if self.observers.is_empty() {
+            return;
+        }
+        let depth = self.execution_context.call_stack().frames.len();
+        for observer in &mut self.observers {
+            event(observer.as_mut(), depth);
+        }
+    }
+
     fn error_with_stack(&mut self, msg: &str) -> RuntimeError {
         self.execution_context.attach_stack(RuntimeError::new(msg))
     }
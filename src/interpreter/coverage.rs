@@ -0,0 +1,214 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use crate::{analysis::for_each_child, node::Expression};
+
+/// Counts how often each line ran and which way each `if` went while the
+/// interpreter runs.
+///
+/// The interpreter calls `statement` before each statement and `branch`
+/// once the condition of an `if` is known. Statements that run one after
+/// another on the same line, in the same call, count as one run of the
+/// line, like a line with a single statement would. Lines are only recorded once they
+/// run, so reports take the program to list those that never did.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    /// For each line, how many times it ran.
+    lines: BTreeMap<usize, u64>,
+    /// For each call depth, the line of the last statement run there.
+    current_lines: Vec<Option<usize>>,
+    /// For each line with an `if`, how many times its condition held and
+    /// how many times it did not.
+    branches: BTreeMap<usize, (u64, u64)>,
+}
+
+/// The lines of a program coverage accounts for.
+struct Executable {
+    /// Lines where a statement starts.
+    lines: BTreeSet<usize>,
+    /// Lines where an `if` starts.
+    branches: BTreeSet<usize>,
+}
+
+impl Executable {
+    fn of(program: &Expression) -> Self {
+        let mut executable = Executable {
+            lines: BTreeSet::new(),
+            branches: BTreeSet::new(),
+        };
+        executable.collect(program);
+        executable
+    }
+
+    fn collect(&mut self, expression: &Expression) {
+        if let Expression::Statement(statement, line) = expression {
+            self.lines.insert(*line);
+            if matches!(statement.as_ref(), Expression::IfConditional(..)) {
+                self.branches.insert(*line);
+            }
+        }
+        for_each_child(expression, |child| self.collect(child));
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A statement starting on `line` runs at call `depth`.
+    pub fn statement(&mut self, line: usize, depth: usize) {
+        self.current_lines.resize(depth + 1, None);
+        if self.current_lines[depth].replace(line) != Some(line) {
+            *self.lines.entry(line).or_default() += 1;
+        }
+    }
+
+    /// Counts the next statement at `depth` even if it is on the line that
+    /// ran last, for a new call or run.
+    pub fn forget_line(&mut self, depth: usize) {
+        self.current_lines.truncate(depth);
+    }
+
+    /// The `if` on `line` runs its first block when `taken`, its `else`
+    /// block or nothing otherwise.
+    pub fn branch(&mut self, line: usize, taken: bool) {
+        let (then, otherwise) = self.branches.entry(line).or_default();
+        if taken {
+            *then += 1;
+        } else {
+            *otherwise += 1;
+        }
+    }
+
+    /// Adds the counts of `other`, recorded by another run of the same
+    /// program.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (line, hits) in &other.lines {
+            *self.lines.entry(*line).or_default() += hits;
+        }
+        for (line, (then, otherwise)) in &other.branches {
+            let counts = self.branches.entry(*line).or_default();
+            counts.0 += then;
+            counts.1 += otherwise;
+        }
+    }
+
+    /// How many times `line` ran.
+    pub fn line_hits(&self, line: usize) -> u64 {
+        self.lines.get(&line).copied().unwrap_or(0)
+    }
+
+    /// How many times the `if` on `line` ran its first block and how many
+    /// times it did not.
+    pub fn branch_hits(&self, line: usize) -> (u64, u64) {
+        self.branches.get(&line).copied().unwrap_or((0, 0))
+    }
+
+    /// The report read by `genhtml` and most CI services, for `program`
+    /// read from `file`. Each `if` counts as two branches, the block it runs
+    /// when its condition holds and the one it runs, if any, when it does not.
+    pub fn lcov(&self, file: &str, program: &Expression) -> String {
+        let executable = Executable::of(program);
+        let mut report = format!("TN:\nSF:{}\n", file);
+
+        let mut branches_hit = 0;
+        for &line in &executable.branches {
+            let (then, otherwise) = self.branch_hits(line);
+            if self.line_hits(line) == 0 {
+                let _ = writeln!(report, "BRDA:{},0,0,-\nBRDA:{},0,1,-", line, line);
+                continue;
+            }
+            branches_hit += usize::from(then > 0) + usize::from(otherwise > 0);
+            let _ = writeln!(
+                report,
+                "BRDA:{},0,0,{}\nBRDA:{},0,1,{}",
+                line, then, line, otherwise
+            );
+        }
+        let _ = writeln!(report, "BRF:{}", executable.branches.len() * 2);
+        let _ = writeln!(report, "BRH:{}", branches_hit);
+
+        for &line in &executable.lines {
+            let _ = writeln!(report, "DA:{},{}", line, self.line_hits(line));
+        }
+        let lines_hit = executable
+            .lines
+            .iter()
+            .filter(|&&line| self.line_hits(line) > 0)
+            .count();
+        let _ = writeln!(report, "LF:{}", executable.lines.len());
+        let _ = writeln!(report, "LH:{}", lines_hit);
+        report.push_str("end_of_record\n");
+        report
+    }
+
+    /// `source` with each line prefixed by how many times it ran, `-` for
+    /// lines without statements and `#####` for those that never ran, and
+    /// the counts of each `if` at the end of its line, then a summary:
+    ///
+    /// ```text
+    ///       1     1 | func sign(n) {
+    ///       2     2 |     if (n < 0) {  [then 0, else 2]
+    ///   #####     3 |         return -1;
+    ///       -     4 |     }
+    ///       2     5 |     return 1;
+    ///       -     6 | }
+    ///
+    /// Lines: 3 of 4 (75.0%)
+    /// Branches: 1 of 2 (50.0%)
+    /// ```
+    pub fn annotate(&self, source: &str, program: &Expression) -> String {
+        let executable = Executable::of(program);
+        let mut report = String::new();
+
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let hits = match self.line_hits(line) {
+                _ if !executable.lines.contains(&line) => "-".to_string(),
+                0 => "#####".to_string(),
+                hits => hits.to_string(),
+            };
+            let _ = write!(report, "{:>7} {:>5} | {}", hits, line, text);
+            if executable.branches.contains(&line) {
+                let (then, otherwise) = self.branch_hits(line);
+                let _ = write!(report, "  [then {}, else {}]", then, otherwise);
+            }
+            report.push('\n');
+        }
+
+        let lines_hit = executable
+            .lines
+            .iter()
+            .filter(|&&line| self.line_hits(line) > 0)
+            .count();
+        let branches_hit: usize = executable
+            .branches
+            .iter()
+            .map(|&line| {
+                let (then, otherwise) = self.branch_hits(line);
+                usize::from(then > 0) + usize::from(otherwise > 0)
+            })
+            .sum();
+        let _ = writeln!(
+            report,
+            "\nLines: {}",
+            ratio(lines_hit, executable.lines.len())
+        );
+        let _ = writeln!(
+            report,
+            "Branches: {}",
+            ratio(branches_hit, executable.branches.len() * 2)
+        );
+        report
+    }
+}
+
+fn ratio(hit: usize, total: usize) -> String {
+    if total == 0 {
+        return "0 of 0".to_string();
+    }
+    format!("{} of {} ({:.1}%)", hit, total, hit as f64 * 100.0 / total as f64)
+}
@@ -0,0 +1,269 @@
+use std::{collections::BTreeSet, ops::RangeInclusive};
+
+use crate::{
+    interner::Symbol,
+    interpreter::{execution_context::ExecutionContext, scope::ScopeId, value::Value},
+    shared::MaybeSend,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    /// The first statement of a debugger created with `stop_on_entry`.
+    Entry,
+    Breakpoint,
+    /// A step requested at the previous pause finished.
+    Step,
+}
+
+/// How to resume after a pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugCommand {
+    /// Runs until the next breakpoint.
+    Continue,
+    /// Pauses at the next statement, entering function calls.
+    StepInto,
+    /// Pauses at the next statement of the current function or its callers.
+    StepOver,
+    /// Pauses once the current function has returned.
+    StepOut,
+    /// Ends the run with a `RuntimeErrorKind::Interrupted` error.
+    Stop,
+}
+
+/// The user interface of a `Debugger`, asked what to do whenever the
+/// program pauses.
+pub trait DebugFrontend: MaybeSend {
+    fn paused(&mut self, pause: &mut Pause<'_>) -> DebugCommand;
+}
+
+#[derive(Debug, Clone, Copy)]
+enum StepMode {
+    Run,
+    Into,
+    /// Stop at a statement no deeper than this many frames.
+    Over(usize),
+    /// Stop at a statement shallower than this many frames.
+    Out(usize),
+}
+
+/// Pauses a running program at line breakpoints and after steps, and hands
+/// control to its `DebugFrontend` while paused.
+///
+/// The interpreter consults it before each statement. Pauses happen at the
+/// first statement of a line, so a line holding several statements is
+/// stepped over as one.
+pub struct Debugger {
+    frontend: Box<dyn DebugFrontend>,
+    breakpoints: BTreeSet<usize>,
+    mode: StepMode,
+    stop_on_entry: bool,
+    /// The line and call depth of the previous statement.
+    last_statement: Option<(usize, usize)>,
+}
+
+impl Debugger {
+    pub fn new(frontend: impl DebugFrontend + 'static) -> Self {
+        Debugger {
+            frontend: Box::new(frontend),
+            breakpoints: BTreeSet::new(),
+            mode: StepMode::Run,
+            stop_on_entry: false,
+            last_statement: None,
+        }
+    }
+
+    /// Pauses before the first statement, before any breakpoint is hit.
+    pub fn stop_on_entry(mut self) -> Self {
+        self.stop_on_entry = true;
+        self
+    }
+
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.breakpoints.insert(line);
+    }
+
+    pub fn clear_breakpoint(&mut self, line: usize) {
+        self.breakpoints.remove(&line);
+    }
+
+    /// Lines with a breakpoint, in ascending order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Called when a run starts or a call binds its parameters, so that a
+    /// tail call running the same line again at the same depth pauses there
+    /// again.
+    pub(crate) fn forget_last_statement(&mut self) {
+        self.last_statement = None;
+    }
+
+    /// Called before the statement on `line` runs. Returns `false` when the
+    /// frontend asked to stop the program.
+    pub(crate) fn before_statement(&mut self, line: usize, context: &ExecutionContext) -> bool {
+        let depth = context.call_stack().frames.len();
+        if self.last_statement.replace((line, depth)) == Some((line, depth)) {
+            return true;
+        }
+
+        let reason = if std::mem::take(&mut self.stop_on_entry) {
+            PauseReason::Entry
+        } else if match self.mode {
+            StepMode::Run => false,
+            StepMode::Into => true,
+            StepMode::Over(max_depth) => depth <= max_depth,
+            StepMode::Out(max_depth) => depth < max_depth,
+        } {
+            PauseReason::Step
+        } else if self.breakpoints.contains(&line) {
+            PauseReason::Breakpoint
+        } else {
+            return true;
+        };
+
+        let mut pause = Pause {
+            line,
+            reason,
+            context,
+            breakpoints: &mut self.breakpoints,
+        };
+        self.mode = match self.frontend.paused(&mut pause) {
+            DebugCommand::Continue => StepMode::Run,
+            DebugCommand::StepInto => StepMode::Into,
+            DebugCommand::StepOver => StepMode::Over(depth),
+            DebugCommand::StepOut => StepMode::Out(depth),
+            DebugCommand::Stop => {
+                self.mode = StepMode::Run;
+                self.last_statement = None;
+                return false;
+            }
+        };
+        true
+    }
+}
+
+/// A function call in progress, as seen from a pause.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The function's name, `None` for the top level of the program.
+    pub function: Option<Symbol>,
+    /// The line being run in this frame: the paused statement for the
+    /// innermost frame, the call to the next frame for the others.
+    pub line: Option<usize>,
+    scopes: RangeInclusive<ScopeId>,
+    /// The scope holding the function's parameters.
+    parameters: Option<ScopeId>,
+}
+
+/// The variables defined directly in one scope.
+#[derive(Debug, Clone)]
+pub struct ScopeVariables {
+    /// `Globals` for the host's globals, `Script` for top-level definitions,
+    /// `Arguments` for a function's parameters, `Locals` for the innermost
+    /// scope of a frame and `Block` for the others.
+    pub name: &'static str,
+    /// Sorted by name.
+    pub variables: Vec<(Symbol, Value)>,
+}
+
+/// The state of a paused program, handed to the `DebugFrontend`.
+pub struct Pause<'a> {
+    line: usize,
+    reason: PauseReason,
+    context: &'a ExecutionContext,
+    breakpoints: &'a mut BTreeSet<usize>,
+}
+
+impl Pause<'_> {
+    /// The line of the statement about to run.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn reason(&self) -> PauseReason {
+        self.reason
+    }
+
+    /// The frames of the call stack, innermost first, ending with the top
+    /// level of the program.
+    pub fn frames(&self) -> Vec<Frame> {
+        let call_stack = &self.context.call_stack().frames;
+        let frame_scopes = self.context.frame_scopes();
+        let parameter_scopes = self.context.parameter_scopes();
+
+        let mut frames = Vec::with_capacity(call_stack.len() + 1);
+        let mut last_scope = self.context.current_scope();
+        let mut line = Some(self.line);
+        for ((frame, &caller_scope), &parameters) in
+            call_stack.iter().zip(frame_scopes).zip(parameter_scopes).rev()
+        {
+            frames.push(Frame {
+                function: Some(frame.function),
+                line,
+                scopes: caller_scope + 1..=last_scope,
+                parameters,
+            });
+            last_scope = caller_scope;
+            line = frame.location;
+        }
+        frames.push(Frame {
+            function: None,
+            line,
+            scopes: self.context.root_scope()..=last_scope,
+            parameters: None,
+        });
+        frames
+    }
+
+    /// The scopes of `frame`, innermost first. Frames of builtins have none.
+    pub fn scopes(&self, frame: &Frame) -> Vec<ScopeVariables> {
+        let arena = self.context.scope_arena();
+        frame
+            .scopes
+            .clone()
+            .rev()
+            .map(|scope| {
+                let name = if scope == self.context.root_scope() {
+                    "Globals"
+                } else if scope == self.context.session_scope() {
+                    "Script"
+                } else if frame.parameters == Some(scope) {
+                    "Arguments"
+                } else if scope == *frame.scopes.end() {
+                    "Locals"
+                } else {
+                    "Block"
+                };
+                let mut variables: Vec<(Symbol, Value)> = arena
+                    .variables(scope)
+                    .map(|(name, value)| (name, value.as_ref().clone()))
+                    .collect();
+                variables.sort_by_key(|(name, _)| name.resolve());
+                ScopeVariables { name, variables }
+            })
+            .collect()
+    }
+
+    /// The value `name` has at the paused statement.
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        self.context
+            .lookup_variable_in_scope(Symbol::lookup(name)?)
+            .map(|value| value.as_ref().clone())
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn set_breakpoint(&mut self, line: usize) {
+        self.breakpoints.insert(line);
+    }
+
+    pub fn clear_breakpoint(&mut self, line: usize) {
+        self.breakpoints.remove(&line);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+}
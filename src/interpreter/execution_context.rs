@@ -1,6 +1,7 @@
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
+    interner::Symbol,
     interpreter::{
         call_stack::{CallStack, StackFrame},
         runtime_errors::RuntimeError,
@@ -8,30 +9,189 @@ use crate::{
         value::Value,
     },
     node::FunctionDeclaration,
+    shared::Shared,
 };
 
+/// A `return f(x);` whose callee is a user function. Instead of recursing, the
+/// call is handed back to the caller's call loop, which runs it in place.
+pub struct TailCall {
+    pub name: Symbol,
+    pub function: Shared<FunctionDeclaration>,
+    pub arguments: Vec<Shared<Value>>,
+    pub location: Option<usize>,
+    /// What the caller defined that the callee would see if nested under it.
+    pub inherited: Definitions,
+}
+
+/// Variables and functions carried over from a call to a tail call made by
+/// it, flattened into a single scope.
+#[derive(Default)]
+pub struct Definitions {
+    variables: Vec<(Symbol, Shared<Value>)>,
+    functions: Vec<(Symbol, Shared<FunctionDeclaration>)>,
+}
+
+impl Definitions {
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty() && self.functions.is_empty()
+    }
+}
+
+/// The calls and blocks in progress at some point of a run, to return to
+/// with `ExecutionContext::rewind`.
+#[derive(Debug, Clone, Copy)]
+pub struct Checkpoint {
+    function_depth: usize,
+    return_values: usize,
+    frames: usize,
+    scope: ScopeId,
+}
+
+impl Checkpoint {
+    /// The number of frames on the call stack when it was taken.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+}
+
 pub struct ExecutionContext {
     function_depth: usize,
     return_values: Vec<Option<Value>>,
+    tail_call: Option<TailCall>,
     scope_arena: ScopeArena,
+    /// Holds the globals injected by the host.
+    root_scope: ScopeId,
+    /// Child of the root scope holding the top-level definitions of every
+    /// program run since the last `reset_session`.
+    session_scope: ScopeId,
     current_scope: ScopeId,
     call_stack: CallStack,
+    /// The scope that was current when each frame of `call_stack` was
+    /// pushed: the last scope of its caller.
+    frame_scopes: Vec<ScopeId>,
+    /// For each frame of `call_stack`, the scope its parameters are bound
+    /// in. `None` for builtins.
+    parameter_scopes: Vec<Option<ScopeId>>,
+    /// Globals injected by the host that scripts may not redefine.
+    readonly_globals: HashSet<Symbol>,
 }
 
 impl ExecutionContext {
     pub fn new() -> Self {
         let mut scope_arena = ScopeArena::new();
-        let current_scope = scope_arena.new_scope(None);
+        let root_scope = scope_arena.new_scope(None);
+        let session_scope = scope_arena.new_scope(Some(root_scope));
 
         ExecutionContext {
             function_depth: 0,
             return_values: Vec::new(),
+            tail_call: None,
             scope_arena,
-            current_scope,
+            root_scope,
+            session_scope,
+            current_scope: session_scope,
             call_stack: CallStack::new(),
+            frame_scopes: Vec::new(),
+            parameter_scopes: Vec::new(),
+            readonly_globals: HashSet::new(),
+        }
+    }
+
+    /// Drops every function call and block still in progress, returning to
+    /// the session scope. Used after a run fails part-way through.
+    pub fn unwind(&mut self) {
+        self.function_depth = 0;
+        self.return_values.clear();
+        self.tail_call = None;
+        self.call_stack.frames.clear();
+        self.frame_scopes.clear();
+        self.parameter_scopes.clear();
+        self.restore_scope(self.session_scope);
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            function_depth: self.function_depth,
+            return_values: self.return_values.len(),
+            frames: self.call_stack.frames.len(),
+            scope: self.current_scope,
         }
     }
 
+    /// Drops the function calls and blocks entered since `checkpoint`, like
+    /// `unwind` does for a whole run.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.function_depth = checkpoint.function_depth;
+        self.return_values.truncate(checkpoint.return_values);
+        self.tail_call = None;
+        self.call_stack.frames.truncate(checkpoint.frames);
+        self.frame_scopes.truncate(checkpoint.frames);
+        self.parameter_scopes.truncate(checkpoint.frames);
+        self.restore_scope(checkpoint.scope);
+    }
+
+    /// Enters the session scope for the program about to run.
+    pub fn enter_session_scope(&mut self) {
+        self.restore_scope(self.session_scope);
+    }
+
+    /// Discards everything programs have defined at the top level. Globals
+    /// injected by the host are kept.
+    pub fn reset_session(&mut self) {
+        self.unwind();
+        self.restore_scope(self.root_scope);
+        let (_, session_scope) = self.enter_new_scope();
+        self.session_scope = session_scope;
+    }
+
+    /// Defines a variable in the root scope, which every program run sees.
+    pub fn define_global(&mut self, identifier: Symbol, value: Shared<Value>, readonly: bool) {
+        self.scope_arena
+            .define_variable(self.root_scope, identifier, value);
+        if readonly {
+            self.readonly_globals.insert(identifier);
+        } else {
+            self.readonly_globals.remove(&identifier);
+        }
+    }
+
+    pub fn is_readonly(&self, identifier: Symbol) -> bool {
+        self.readonly_globals.contains(&identifier)
+    }
+
+    pub fn lookup_global_variable(&self, identifier: Symbol) -> Option<Shared<Value>> {
+        self.scope_arena
+            .lookup_variable(self.session_scope, identifier)
+    }
+
+    /// Variables visible at the top level: those defined in the session,
+    /// then the host's globals that they do not shadow.
+    pub fn globals(&self) -> impl Iterator<Item = (Symbol, &Shared<Value>)> {
+        let arena = &self.scope_arena;
+        let session_scope = self.session_scope;
+
+        let shared = arena
+            .variables(self.root_scope)
+            .filter(move |(name, _)| !arena.defines_variable(session_scope, *name));
+        arena.variables(session_scope).chain(shared)
+    }
+
+    /// Functions defined at the top level of the session.
+    pub fn global_functions(&self) -> impl Iterator<Item = (Symbol, &FunctionDeclaration)> {
+        self.scope_arena
+            .functions(self.session_scope)
+            .map(|(name, function)| (name, function.as_ref()))
+    }
+
+    pub fn lookup_global_function(
+        &self,
+        method_name: Symbol,
+    ) -> Option<Shared<FunctionDeclaration>> {
+        self.scope_arena
+            .lookup_function(self.session_scope, method_name)
+            .cloned()
+    }
+
     pub fn enter_function(&mut self) {
         self.function_depth += 1;
         self.return_values.push(None);
@@ -52,10 +212,18 @@ impl ExecutionContext {
         (parent_scope, child_scope)
     }
 
+    /// Enters the scope the parameters of the innermost frame are bound in.
+    pub fn enter_parameter_scope(&mut self) {
+        let (_, scope) = self.enter_new_scope();
+        if let Some(parameters) = self.parameter_scopes.last_mut() {
+            *parameters = Some(scope);
+        }
+    }
+
     pub fn define_variable_in_scope(
         &mut self,
-        identifier: &str,
-        value: Rc<Value>,
+        identifier: Symbol,
+        value: Shared<Value>,
     ) -> Result<(), RuntimeError> {
         self.scope_arena
             .define_variable(self.current_scope, identifier, value);
@@ -64,34 +232,132 @@ impl ExecutionContext {
 
     pub fn define_function_in_scope(
         &mut self,
-        identifier: &str,
-        node: FunctionDeclaration,
+        identifier: Symbol,
+        node: Shared<FunctionDeclaration>,
     ) -> Result<(), RuntimeError> {
         self.scope_arena
             .define_function(self.current_scope, identifier, node);
         Ok(())
     }
 
-    pub fn lookup_function_in_scope(&mut self, method_name: &str) -> Option<FunctionDeclaration> {
+    pub fn lookup_function_in_scope(
+        &mut self,
+        method_name: Symbol,
+    ) -> Option<Shared<FunctionDeclaration>> {
         self.scope_arena
             .lookup_function(self.current_scope, method_name)
             .cloned()
     }
 
-    pub fn lookup_variable_in_scope(&mut self, identifier: &str) -> Option<Rc<Value>> {
+    pub fn lookup_variable_in_scope(&self, identifier: Symbol) -> Option<Shared<Value>> {
         self.scope_arena
             .lookup_variable(self.current_scope, identifier)
     }
 
+    /// Returns to `scope`, discarding the scopes entered since.
     pub fn restore_scope(&mut self, scope: usize) {
         self.current_scope = scope;
+        self.scope_arena.truncate(scope + 1);
+    }
+
+    pub fn current_scope(&self) -> ScopeId {
+        self.current_scope
+    }
+
+    pub fn root_scope(&self) -> ScopeId {
+        self.root_scope
+    }
+
+    pub fn session_scope(&self) -> ScopeId {
+        self.session_scope
+    }
+
+    pub fn scope_arena(&self) -> &ScopeArena {
+        &self.scope_arena
+    }
+
+    pub fn scope_count(&self) -> usize {
+        self.scope_arena.len()
+    }
+
+    pub fn variable_count(&self) -> usize {
+        self.scope_arena.variable_count()
     }
 
     pub fn is_in_function(&self) -> bool {
         self.function_depth > 0
     }
 
-    pub fn set_return_value(&mut self, value:Rc<Value>) {
+    pub fn function_depth(&self) -> usize {
+        self.function_depth
+    }
+
+    /// The definitions of the current call that `function`, called from
+    /// here, would see and not shadow with its parameters. Scoping is
+    /// dynamic, so a tail call to `function` has to keep them visible.
+    pub fn definitions_for_tail_call(&self, function: &FunctionDeclaration) -> Definitions {
+        let Some(&caller_scope) = self.frame_scopes.last() else {
+            return Definitions::default();
+        };
+        let scopes = caller_scope + 1..=self.current_scope;
+        let shadowed = |name: Symbol| {
+            function
+                .arguments
+                .iter()
+                .any(|argument| argument.name == name)
+        };
+        // The common case of a function calling itself, or another function
+        // with the same parameters, has nothing to carry over.
+        if scopes.clone().all(|scope| {
+            self.scope_arena.functions(scope).next().is_none()
+                && self.scope_arena.variables(scope).all(|(name, _)| shadowed(name))
+        }) {
+            return Definitions::default();
+        }
+
+        let mut variables = HashMap::new();
+        let mut functions = HashMap::new();
+        // Outermost scope first, so that inner definitions replace outer ones.
+        for scope in scopes {
+            variables.extend(
+                self.scope_arena
+                    .variables(scope)
+                    .map(|(name, value)| (name, value.clone())),
+            );
+            functions.extend(
+                self.scope_arena
+                    .functions(scope)
+                    .map(|(name, function)| (name, function.clone())),
+            );
+        }
+        variables.retain(|name, _| !shadowed(*name));
+        Definitions {
+            variables: variables.into_iter().collect(),
+            functions: functions.into_iter().collect(),
+        }
+    }
+
+    /// Defines `definitions` in the current scope.
+    pub fn define_all(&mut self, definitions: Definitions) {
+        for (name, value) in definitions.variables {
+            self.scope_arena
+                .define_variable(self.current_scope, name, value);
+        }
+        for (name, function) in definitions.functions {
+            self.scope_arena
+                .define_function(self.current_scope, name, function);
+        }
+    }
+
+    pub fn set_tail_call(&mut self, tail_call: TailCall) {
+        self.tail_call = Some(tail_call);
+    }
+
+    pub fn take_tail_call(&mut self) -> Option<TailCall> {
+        self.tail_call.take()
+    }
+
+    pub fn set_return_value(&mut self, value:Shared<Value>) {
         if let Some(slot) = self.return_values.last_mut() {
             // When returning we clone the value. No reference passing.
             *slot = Some(value.as_ref().clone());
@@ -100,15 +366,34 @@ impl ExecutionContext {
         }
     }
 
-    pub fn push_frame(&mut self, name: String, location: Option<usize>) {
+    pub fn push_frame(&mut self, name: Symbol, location: Option<usize>) {
         self.call_stack.push(StackFrame {
             function: name,
             location,
         });
+        self.frame_scopes.push(self.current_scope);
+        self.parameter_scopes.push(None);
     }
 
     pub fn pop_frame(&mut self) {
         self.call_stack.pop();
+        self.frame_scopes.pop();
+        self.parameter_scopes.pop();
+    }
+
+    /// For each frame of the call stack, the last scope of its caller. The
+    /// scopes after it, up to the next frame's entry, belong to the frame.
+    pub fn frame_scopes(&self) -> &[ScopeId] {
+        &self.frame_scopes
+    }
+
+    /// For each frame of the call stack, the scope holding its parameters.
+    pub fn parameter_scopes(&self) -> &[Option<ScopeId>] {
+        &self.parameter_scopes
+    }
+
+    pub fn call_stack(&self) -> &CallStack {
+        &self.call_stack
     }
 
     pub fn attach_stack(&self, err: RuntimeError) -> RuntimeError {
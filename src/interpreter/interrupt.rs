@@ -0,0 +1,34 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Thread-safe handle used to stop a running `Interpreter` from another thread.
+///
+/// The interpreter checks the handle before each statement; once triggered,
+/// the current `run` returns a `RuntimeErrorKind::Interrupted` error. The
+/// request is consumed when observed, so the next `run` starts normally. A
+/// request made while nothing is running stops the next `run` instead.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    requested: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn interrupt(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Clears a pending request, returning whether there was one.
+    pub fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,15 @@
+use std::io::{BufRead, Write};
+
+use crate::shared::MaybeSend;
+
+/// A reader `readln` can take lines from. Implemented by every `BufRead`,
+/// which must also be `Send` with the `sync` feature.
+pub trait Input: BufRead + MaybeSend {}
+
+impl<T: BufRead + MaybeSend> Input for T {}
+
+/// A writer `print`-style builtins can write to. Implemented by every `Write`,
+/// which must also be `Send` with the `sync` feature.
+pub trait Output: Write + MaybeSend {}
+
+impl<T: Write + MaybeSend> Output for T {}
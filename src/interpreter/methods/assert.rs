@@ -0,0 +1,164 @@
+use crate::{
+    interpreter::{
+        context::{Callee, NativeContext},
+        methods::{NativeFnArgs, NativeFnReturn},
+        runtime_errors::{RuntimeError, RuntimeErrorKind},
+        value::Value,
+    },
+    register_contextual_method, register_method,
+    shared::Shared,
+    takes_arguments,
+};
+
+/// Tolerance of `assert_near` when none is given.
+const DEFAULT_TOLERANCE: f64 = 1e-9;
+
+fn text(value: &Value) -> String {
+    match value.to_string() {
+        Value::String(text) => text.as_ref().to_owned(),
+        _ => unreachable!(),
+    }
+}
+
+/// The error raised when an assertion does not hold, prefixed with the
+/// message the script gave, if any.
+fn failure(message: Option<&Shared<Value>>, details: String) -> RuntimeError {
+    let message = match message {
+        Some(message) => format!("Assertion failed: {}: {}", text(message), details),
+        None => format!("Assertion failed: {}", details),
+    };
+    RuntimeError::with_kind(RuntimeErrorKind::AssertionFailed, message)
+}
+
+fn expect_arguments(args: &NativeFnArgs, min: usize, max: usize) -> Result<(), RuntimeError> {
+    if args.len() < min || args.len() > max {
+        return Err(RuntimeError::new(format!(
+            "Expected {} to {} parameters, found {}",
+            min,
+            max,
+            args.len()
+        )));
+    }
+    Ok(())
+}
+
+pub fn fn_assert(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
+    let (message, condition) = takes_arguments!(args, 2)?;
+
+    if !condition.to_bool() {
+        return Err(RuntimeError::with_kind(
+            RuntimeErrorKind::AssertionFailed,
+            format!("Assertion failed: {}", text(&message)),
+        ));
+    }
+
+    Ok(Value::Empty.into_rc())
+}
+
+pub fn fn_assert_eq(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
+    expect_arguments(&args, 2, 3)?;
+    let (actual, expected) = (&args[0], &args[1]);
+
+    if !actual.eq_value(expected).to_bool() {
+        return Err(failure(
+            args.get(2),
+            format!("expected {}, got {}", expected.repr(), actual.repr()),
+        ));
+    }
+
+    Ok(Value::Empty.into_rc())
+}
+
+pub fn fn_assert_near(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
+    expect_arguments(&args, 2, 4)?;
+    let actual = args[0].to_f64()?;
+    let expected = args[1].to_f64()?;
+    let tolerance = match args.get(2) {
+        Some(tolerance) => tolerance.to_f64()?,
+        None => DEFAULT_TOLERANCE,
+    };
+
+    if (actual - expected).abs() > tolerance || actual.is_nan() || expected.is_nan() {
+        return Err(failure(
+            args.get(3),
+            format!("expected {} within {}, got {}", expected, tolerance, actual),
+        ));
+    }
+
+    Ok(Value::Empty.into_rc())
+}
+
+/// Calls a function that is expected to fail and returns its error message.
+/// Exceeded limits and interruptions are not caught, as they end the run.
+///
+/// A function that does not exist, or a script function given the wrong
+/// number of arguments, fails the assertion rather than counting as the
+/// expected error, so that a typo cannot make it pass.
+pub fn fn_assert_throws(
+    context: &mut NativeContext,
+    args: NativeFnArgs,
+) -> Result<NativeFnReturn, RuntimeError> {
+    let Some((function, arguments)) = args.split_first() else {
+        return Err(RuntimeError::new("Expected at least 1 parameter, found 0"));
+    };
+    let function = text(function);
+    let arguments = arguments.to_vec();
+
+    match context.resolve(&function, &arguments) {
+        None => return Err(failure(None, format!("no function named {}", function))),
+        Some(Callee::Script { arity }) if arity != arguments.len() => {
+            return Err(failure(
+                None,
+                format!(
+                    "{} takes {} arguments, given {}",
+                    function,
+                    arity,
+                    arguments.len()
+                ),
+            ));
+        }
+        Some(_) => {}
+    }
+
+    match context.call(&function, arguments) {
+        Ok(_) => Err(RuntimeError::with_kind(
+            RuntimeErrorKind::AssertionFailed,
+            format!("Assertion failed: expected {} to fail", function),
+        )),
+        Err(err)
+            if matches!(
+                err.kind,
+                RuntimeErrorKind::Error | RuntimeErrorKind::AssertionFailed
+            ) =>
+        {
+            Ok(Value::String(Shared::from(err.message)).into_rc())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+register_method!(
+    "assert",
+    fn_assert,
+    "assert(message, condition)",
+    "Fails with the message unless the condition is true."
+);
+register_method!(
+    "assert_eq",
+    fn_assert_eq,
+    "assert_eq(actual, expected, message?)",
+    "Fails unless the two values are equal, as compared by ==."
+);
+register_method!(
+    "assert_near",
+    fn_assert_near,
+    "assert_near(actual, expected, tolerance?, message?)",
+    "Fails unless two numbers differ by at most the tolerance, 1e-9 by default."
+);
+register_contextual_method!(
+    "assert_throws",
+    fn_assert_throws,
+    "assert_throws(function, args...)",
+    "Calls the function named by the first argument with the others, fails if \
+     it succeeds and returns its error message otherwise."
+);
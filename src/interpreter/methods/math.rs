@@ -1,6 +1,8 @@
 use crate::{
     interpreter::{
-        methods::{NativeFnArgs, NativeFnReturn}, runtime_errors::RuntimeError, value::{Value}
+        methods::{NativeFnArgs, NativeFnReturn},
+        runtime_errors::RuntimeError,
+        value::Value,
     },
     register_method, takes_arguments,
 };
@@ -9,7 +11,7 @@ pub fn fn_sin(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
     let (angle,) = takes_arguments!(args, 1)?;
 
     let number = angle.to_f64()?;
-    
+
     Ok(Value::Float(f64::sin(number)).into_rc())
 }
 
@@ -23,5 +25,5 @@ pub fn fn_cos(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
     Ok(Value::Float(number.cos()).into_rc())
 }
 
-register_method!("sin", fn_sin);
-register_method!("cos", fn_cos);
+register_method!("sin", fn_sin, "sin(angle)", "Sine of an angle in radians.");
+register_method!("cos", fn_cos, "cos(angle)", "Cosine of an angle in radians.");
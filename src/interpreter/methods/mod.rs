@@ -1,33 +1,89 @@
+mod assert;
 mod math;
 mod print;
 mod println;
 mod readln;
 mod string;
 
-use std::rc::Rc;
+use std::collections::HashMap;
 
-use super::{runtime_errors::RuntimeError, value::Value};
+use once_cell::sync::Lazy;
 
-pub type NativeFn = fn(Vec<Rc<Value>>) -> Result<Rc<Value>, RuntimeError>;
+use super::{context::NativeContext, runtime_errors::RuntimeError, value::Value};
+use crate::{
+    interner::Symbol,
+    shared::{MaybeSync, Shared},
+};
+
+pub type NativeFn =
+    fn(&mut NativeContext, Vec<Shared<Value>>) -> Result<Shared<Value>, RuntimeError>;
+
+/// The original native signature, without access to the interpreter.
+pub type SimpleNativeFn = fn(Vec<Shared<Value>>) -> Result<Shared<Value>, RuntimeError>;
+
+#[derive(Clone, Copy)]
+pub enum NativeFunction {
+    Contextual(NativeFn),
+    Simple(SimpleNativeFn),
+}
+
+impl NativeFunction {
+    pub fn call(
+        self,
+        context: &mut NativeContext,
+        args: NativeFnArgs,
+    ) -> Result<NativeFnReturn, RuntimeError> {
+        match self {
+            NativeFunction::Contextual(func) => func(context, args),
+            NativeFunction::Simple(func) => func(args),
+        }
+    }
+}
 
 pub struct Method {
     pub name: &'static str,
-    pub func: NativeFn,
+    pub func: NativeFunction,
+    /// How the builtin is called, e.g. `sin(angle)`. Shown by editors.
+    pub signature: &'static str,
+    /// A short description, empty when the builtin was registered without one.
+    pub doc: &'static str,
 }
 
 inventory::collect!(Method);
 
-pub type NativeFnArgs = Vec<Rc<Value>>;
-pub type NativeFnReturn = Rc<Value>;
+pub type NativeFnArgs = Vec<Shared<Value>>;
+pub type NativeFnReturn = Shared<Value>;
 
-pub fn get_method(name: String, args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
-    for method in inventory::iter::<Method> {
-        if method.name == name {
-            return (method.func)(args);
-        }
-    }
+/// A function registered on a single `Interpreter` with
+/// `Interpreter::register_function`. Unlike `NativeFn` it can capture state,
+/// which must be thread-safe with the `sync` feature.
+pub trait HostFunction:
+    Fn(&mut NativeContext, NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> + MaybeSync
+{
+}
+
+impl<F> HostFunction for F where
+    F: Fn(&mut NativeContext, NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> + MaybeSync
+{
+}
+
+pub type HostFn = dyn HostFunction;
+
+/// Registered methods keyed by their interned name, built on first lookup.
+static METHODS: Lazy<HashMap<Symbol, NativeFunction>> = Lazy::new(|| {
+    inventory::iter::<Method>
+        .into_iter()
+        .map(|method| (Symbol::intern(method.name), method.func))
+        .collect()
+});
 
-    Err(RuntimeError::new(format!("Method not found: {}", name)))
+pub fn get_method(name: Symbol) -> Option<NativeFunction> {
+    METHODS.get(&name).copied()
+}
+
+/// Every registered builtin, in no particular order.
+pub fn methods() -> impl Iterator<Item = &'static Method> {
+    inventory::iter::<Method>.into_iter()
 }
 
 #[macro_export]
@@ -95,14 +151,88 @@ macro_rules! takes_arguments {
 }
 
 
+/// Registers a builtin. The optional signature and description are shown by
+/// editors and listed by `rmp doc --builtins`:
+///
+/// ```ignore
+/// register_method!("sin", fn_sin, "sin(angle)", "Sine of an angle in radians.");
+/// ```
+///
+/// The description is Markdown, like a `///` comment, so it may end with
+/// examples in fenced code blocks.
 #[macro_export]
 macro_rules! register_method {
     ($name:expr, $func:path) => {
+        $crate::register_method!($name, $func, concat!($name, "(...)"), "");
+    };
+    ($name:expr, $func:path, $signature:expr, $doc:expr) => {
+        inventory::submit! {
+            $crate::interpreter::methods::Method {
+                name: $name,
+                func: $crate::interpreter::methods::NativeFunction::Simple($func),
+                signature: $signature,
+                doc: $doc,
+            }
+        }
+    };
+}
+
+/// Registers a builtin that also receives the `NativeContext` of the call,
+/// to call script functions, read variables or write to the interpreter's
+/// output. Takes the same optional signature and description as
+/// `register_method!`.
+#[macro_export]
+macro_rules! register_contextual_method {
+    ($name:expr, $func:path) => {
+        $crate::register_contextual_method!($name, $func, concat!($name, "(...)"), "");
+    };
+    ($name:expr, $func:path, $signature:expr, $doc:expr) => {
         inventory::submit! {
             $crate::interpreter::methods::Method {
                 name: $name,
-                func: $func,
+                func: $crate::interpreter::methods::NativeFunction::Contextual($func),
+                signature: $signature,
+                doc: $doc,
             }
         }
     };
 }
+
+/// Registers an ordinary Rust function as a builtin. Arguments are converted
+/// with `FromValue` and the result with `IntoValue`, so the function is only
+/// called with the right number of arguments of the right types:
+///
+/// ```ignore
+/// fn hypot(a: f64, b: f64) -> f64 {
+///     a.hypot(b)
+/// }
+/// register_typed_method!("hypot", hypot);
+/// ```
+#[macro_export]
+macro_rules! register_typed_method {
+    ($name:expr, $func:path) => {
+        $crate::register_typed_method!($name, $func, concat!($name, "(...)"), "");
+    };
+    ($name:expr, $func:path, $signature:expr, $doc:expr) => {
+        const _: () = {
+            fn typed_shim(
+                _context: &mut $crate::interpreter::context::NativeContext,
+                args: $crate::interpreter::methods::NativeFnArgs,
+            ) -> ::std::result::Result<
+                $crate::interpreter::methods::NativeFnReturn,
+                $crate::interpreter::runtime_errors::RuntimeError,
+            > {
+                $crate::interpreter::convert::TypedFunction::call_typed(&$func, $name, &args)
+            }
+
+            inventory::submit! {
+                $crate::interpreter::methods::Method {
+                    name: $name,
+                    func: $crate::interpreter::methods::NativeFunction::Contextual(typed_shim),
+                    signature: $signature,
+                    doc: $doc,
+                }
+            }
+        };
+    };
+}
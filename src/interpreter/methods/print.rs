@@ -1,15 +1,24 @@
 use crate::{
-    interpreter::{methods::{NativeFnArgs, NativeFnReturn}, runtime_errors::RuntimeError, value::Value},
-    register_method,
+    interpreter::{
+        context::NativeContext,
+        methods::{NativeFnArgs, NativeFnReturn},
+        runtime_errors::RuntimeError,
+        value::Value,
+    },
+    register_contextual_method,
 };
 
-pub fn fn_print(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
+pub fn fn_print(
+    context: &mut NativeContext,
+    args: NativeFnArgs,
+) -> Result<NativeFnReturn, RuntimeError> {
     for arg in args.iter() {
         // Keep Value alive
         let val_str = arg.to_string(); // Value::String(Rc<str>)
         if let Value::String(rc) = val_str {
             // rc now lives as long as this iteration
-            print!("{}", rc); // &Rc<str> implements Display
+            write!(context.output(), "{}", rc)
+                .map_err(|err| RuntimeError::new(format!("Unable to write output: {}", err)))?;
         } else {
             unreachable!()
         }
@@ -18,4 +27,31 @@ pub fn fn_print(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
     Ok(Value::Empty.into_rc())
 }
 
-register_method!("print", fn_print);
+pub fn fn_eprint(
+    context: &mut NativeContext,
+    args: NativeFnArgs,
+) -> Result<NativeFnReturn, RuntimeError> {
+    for arg in args.iter() {
+        if let Value::String(rc) = arg.to_string() {
+            write!(context.error_output(), "{}", rc)
+                .map_err(|err| RuntimeError::new(format!("Unable to write output: {}", err)))?;
+        } else {
+            unreachable!()
+        }
+    }
+
+    Ok(Value::Empty.into_rc())
+}
+
+register_contextual_method!(
+    "print",
+    fn_print,
+    "print(values...)",
+    "Writes the values to the output."
+);
+register_contextual_method!(
+    "eprint",
+    fn_eprint,
+    "eprint(values...)",
+    "Writes the values to the error output."
+);
@@ -1,21 +1,63 @@
 use crate::{
-    interpreter::{methods::{NativeFnArgs, NativeFnReturn}, runtime_errors::RuntimeError, value::Value},
-    register_method,
+    interpreter::{
+        context::NativeContext,
+        methods::{NativeFnArgs, NativeFnReturn},
+        runtime_errors::RuntimeError,
+        value::Value,
+    },
+    register_contextual_method,
 };
 
-pub fn fn_println(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
+pub fn fn_println(
+    context: &mut NativeContext,
+    args: NativeFnArgs,
+) -> Result<NativeFnReturn, RuntimeError> {
+    let output = context.output();
+    let write_error = |err| RuntimeError::new(format!("Unable to write output: {}", err));
+
     for arg in args.iter() {
         // Force the Value into a Value::String
         let val_str = arg.to_string(); // returns Value::String(Rc<str>)
         if let Value::String(rc) = val_str {
             // Rc<str> derefs to str, so print works directly
-            print!("{}", rc);
+            write!(output, "{}", rc).map_err(write_error)?;
         } else {
             unreachable!(); // to_string() always returns Value::String
         }
     }
-    println!();
+    writeln!(output).map_err(write_error)?;
 
     Ok(Value::Empty.into_rc())
 }
-register_method!("println", fn_println);
+
+pub fn fn_eprintln(
+    context: &mut NativeContext,
+    args: NativeFnArgs,
+) -> Result<NativeFnReturn, RuntimeError> {
+    let output = context.error_output();
+    let write_error = |err| RuntimeError::new(format!("Unable to write output: {}", err));
+
+    for arg in args.iter() {
+        if let Value::String(rc) = arg.to_string() {
+            write!(output, "{}", rc).map_err(write_error)?;
+        } else {
+            unreachable!();
+        }
+    }
+    writeln!(output).map_err(write_error)?;
+
+    Ok(Value::Empty.into_rc())
+}
+
+register_contextual_method!(
+    "println",
+    fn_println,
+    "println(values...)",
+    "Writes the values and a newline to the output."
+);
+register_contextual_method!(
+    "eprintln",
+    fn_eprintln,
+    "eprintln(values...)",
+    "Writes the values and a newline to the error output."
+);
@@ -1,33 +1,41 @@
-use std::{
-    io::{self, stdout, BufRead, Write},
-    rc::Rc,
-};
-
 use crate::{
-    interpreter::{methods::{NativeFnArgs, NativeFnReturn}, runtime_errors::RuntimeError, value::Value},
-    register_method,
+    interpreter::{
+        context::NativeContext,
+        methods::{NativeFnArgs, NativeFnReturn},
+        runtime_errors::RuntimeError,
+        value::Value,
+    },
+    register_contextual_method,
+    shared::Shared,
 };
 
-pub fn fn_readln(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
+pub fn fn_readln(
+    context: &mut NativeContext,
+    args: NativeFnArgs,
+) -> Result<NativeFnReturn, RuntimeError> {
+    let output = context.output();
+    let write_error = |err| RuntimeError::new(format!("Unable to write output: {}", err));
+
     // Print all arguments without converting to String::convert
     for arg in args.iter() {
         let val_str = arg.to_string();
         if let Value::String(rc) = val_str {
-            print!("{}", rc);
+            write!(output, "{}", rc).map_err(write_error)?;
         } else {
             unreachable!();
         }
     }
 
-    // Flush stdout
-    stdout().flush().expect("Unable to flush");
+    // Flush the prompt before blocking on input
+    output.flush().map_err(write_error)?;
 
-    // Read line from stdin
+    // Read line from the interpreter's input
     let mut line = String::new();
-    let stdin = io::stdin();
 
-    stdin.lock().read_line(&mut line)
-        .map_err(|err| { RuntimeError::new(format!("Unable to read line: {}", err.to_string())) })?;
+    context
+        .input()
+        .read_line(&mut line)
+        .map_err(|err| RuntimeError::new(format!("Unable to read line: {}", err)))?;
 
     // Remove trailing newline
     if line.ends_with('\n') {
@@ -38,7 +46,12 @@ pub fn fn_readln(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
         }
     }
 
-    Ok(Value::String(Rc::from(line)).into_rc())
+    Ok(Value::String(Shared::from(line)).into_rc())
 }
 
-register_method!("readln", fn_readln);
+register_contextual_method!(
+    "readln",
+    fn_readln,
+    "readln(prompt?)",
+    "Prints the optional prompt and returns a line read from the input."
+);
@@ -1,9 +1,13 @@
 use crate::{
-    interpreter::{methods::{NativeFnArgs, NativeFnReturn}, runtime_errors::RuntimeError, value::Value},
+    interpreter::{
+        methods::{NativeFnArgs, NativeFnReturn},
+        runtime_errors::RuntimeError,
+        value::Value,
+    },
     register_method, takes_arguments,
 };
 
-use std::rc::Rc;
+use crate::shared::Shared;
 
 /// Concatenate multiple Values into a single string
 pub fn fn_str_concat(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
@@ -19,7 +23,7 @@ pub fn fn_str_concat(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError>
         }
     }
 
-    Ok(Value::String(Rc::from(concat_str)).into_rc())
+    Ok(Value::String(Shared::from(concat_str)).into_rc())
 }
 
 /// Convert a Value to a numeric Value (Integer or Float)
@@ -31,5 +35,17 @@ pub fn fn_to_number(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError>
     Ok(result.into_rc())
 }
 
-register_method!("str_concat", fn_str_concat);
-register_method!("to_number", fn_to_number);
+register_method!(
+    "str_concat",
+    fn_str_concat,
+    "str_concat(values...)",
+    "Concatenates the values as strings.\n\n\
+     ```\nstr_concat(\"x = \", 4); // \"x = 4\"\n```"
+);
+register_method!(
+    "to_number",
+    fn_to_number,
+    "to_number(value)",
+    "Converts a value to an integer or a float.\n\n\
+     ```\nto_number(\"42\"); // 42\nto_number(\"0.5\"); // 0.5\n```"
+);
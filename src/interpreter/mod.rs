@@ -2,8 +2,19 @@ mod core;
 pub use core::Interpreter;
 pub use core::ControlFlow;
 pub mod call_stack;
+pub mod config;
+pub mod context;
+pub mod convert;
+pub mod coverage;
+pub mod debugger;
 pub mod execution_context;
+pub mod interrupt;
+pub mod io;
 pub mod methods;
+pub mod object;
+pub mod observer;
+pub mod profiler;
 pub mod runtime_errors;
 pub mod scope;
+pub mod tracer;
 pub mod value;
@@ -0,0 +1,148 @@
+use std::{any::Any, collections::HashMap, fmt};
+
+use crate::{
+    interner::Symbol,
+    interpreter::{
+        context::NativeContext,
+        convert::TypedFunction,
+        methods::{HostFn, NativeFnArgs, NativeFnReturn},
+        runtime_errors::RuntimeError,
+    },
+    shared::{MaybeSync, Shared, SharedAny},
+};
+
+/// Renders a host object for `print`, string concatenation and `to_string`.
+trait Formatter: Fn(&HostObject) -> Option<String> + MaybeSync {}
+
+impl<F: Fn(&HostObject) -> Option<String> + MaybeSync> Formatter for F {}
+
+/// Describes a kind of host object: its name, the methods scripts can call
+/// on it and how it is printed.
+///
+/// Methods are called like any other function, with the object as the first
+/// argument: a method `read` on a sensor is called as `read(sensor)`.
+pub struct ObjectType {
+    name: String,
+    methods: HashMap<Symbol, Shared<HostFn>>,
+    formatter: Option<Box<dyn Formatter>>,
+}
+
+impl ObjectType {
+    pub fn new(name: &str) -> Self {
+        ObjectType {
+            name: name.to_string(),
+            methods: HashMap::new(),
+            formatter: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Adds a method. `method` receives the object as its first argument.
+    pub fn with_method<F>(mut self, name: &str, method: F) -> Self
+    where
+        F: Fn(&mut NativeContext, NativeFnArgs) -> Result<NativeFnReturn, RuntimeError>
+            + MaybeSync
+            + 'static,
+    {
+        self.methods
+            .insert(Symbol::intern(name), Shared::new(method));
+        self
+    }
+
+    /// Adds a method written as an ordinary Rust function, see
+    /// `Interpreter::register_typed_function`. The object is passed as the
+    /// first argument, typically taken as `Shared<T>`.
+    pub fn with_typed_method<F, Args>(self, name: &str, method: F) -> Self
+    where
+        F: for<'a> TypedFunction<'a, Args> + MaybeSync + 'static,
+    {
+        let label = name.to_string();
+        self.with_method(name, move |_, args| method.call_typed(&label, &args))
+    }
+
+    /// Sets how objects holding a `T` are printed. Without a formatter they
+    /// print as `<name>`.
+    pub fn with_formatter<T, F>(mut self, formatter: F) -> Self
+    where
+        T: Any,
+        F: Fn(&T) -> String + MaybeSync + 'static,
+    {
+        self.formatter = Some(Box::new(move |object: &HostObject| {
+            object.downcast_ref::<T>().map(&formatter)
+        }));
+        self
+    }
+
+    pub(crate) fn method(&self, name: Symbol) -> Option<Shared<HostFn>> {
+        self.methods.get(&name).cloned()
+    }
+}
+
+/// A host value handed to scripts as an opaque `Value::Object`.
+///
+/// Cloning shares the underlying value, and two objects are equal only when
+/// they share it.
+#[derive(Clone)]
+pub struct HostObject {
+    object_type: Shared<ObjectType>,
+    data: SharedAny,
+}
+
+impl HostObject {
+    pub fn new<T: Any + MaybeSync>(object_type: &Shared<ObjectType>, data: T) -> Self {
+        HostObject {
+            object_type: object_type.clone(),
+            data: Shared::new(data),
+        }
+    }
+
+    pub fn object_type(&self) -> &ObjectType {
+        &self.object_type
+    }
+
+    pub fn type_name(&self) -> &str {
+        self.object_type.name()
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.data.downcast_ref::<T>()
+    }
+
+    pub fn downcast<T: Any + MaybeSync>(&self) -> Option<Shared<T>> {
+        self.data.clone().downcast::<T>().ok()
+    }
+}
+
+impl PartialEq for HostObject {
+    fn eq(&self, other: &Self) -> bool {
+        Shared::ptr_eq(&self.data, &other.data)
+    }
+}
+
+impl fmt::Display for HostObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self
+            .object_type
+            .formatter
+            .as_ref()
+            .and_then(|format| format(self))
+        {
+            Some(text) => f.write_str(&text),
+            None => write!(f, "<{}>", self.type_name()),
+        }
+    }
+}
+
+impl fmt::Debug for HostObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "HostObject({} @ {:p})",
+            self.type_name(),
+            Shared::as_ptr(&self.data)
+        )
+    }
+}
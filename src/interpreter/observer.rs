@@ -0,0 +1,36 @@
+use crate::{
+    interner::Symbol,
+    interpreter::{profiler::FunctionKind, value::Value},
+    shared::{MaybeSend, Shared},
+};
+
+/// Receives events from the interpreter as a program runs. Every method does
+/// nothing by default, so observers implement only what they need.
+///
+/// `depth` is the number of frames on the call stack: calls are reported
+/// before their frame is pushed and returns after it is popped, so a call and
+/// its return have the depth of the caller's statements.
+///
+/// An interpreter without observers only checks that it has none, so they
+/// cost nothing when unused.
+pub trait Observer: MaybeSend {
+    /// A statement starting on `line` is about to run.
+    fn statement(&mut self, _line: usize, _depth: usize) {}
+
+    /// `function` is called with `arguments`.
+    fn enter_function(
+        &mut self,
+        _function: Symbol,
+        _kind: FunctionKind,
+        _arguments: &[Shared<Value>],
+        _depth: usize,
+    ) {
+    }
+
+    /// `function` returned `value`, or `None` when it ended with a tail call
+    /// (`return g(x);`), which is reported as a call at the same depth.
+    fn exit_function(&mut self, _function: Symbol, _value: Option<&Value>, _depth: usize) {}
+
+    /// `let name = value;` ran.
+    fn define_variable(&mut self, _name: Symbol, _value: &Value, _depth: usize) {}
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{interner::Symbol, interpreter::call_stack::CallStack};
+
+/// Number of source lines listed in the report.
+const REPORTED_LINES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionKind {
+    User,
+    Builtin,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionProfile {
+    pub kind: FunctionKind,
+    pub calls: usize,
+    /// Time between entering and leaving the function, children included.
+    /// Recursive calls are only counted once.
+    pub inclusive: Duration,
+    /// Time spent in the function's own body, children excluded.
+    pub exclusive: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LineProfile {
+    /// Times a statement starting on this line ran.
+    pub hits: usize,
+    /// Time spent running the statements of this line, including the
+    /// builtins they call but not the user functions.
+    pub time: Duration,
+}
+
+/// A distinct call stack: the stack of `parent` with `function` on top.
+#[derive(Debug)]
+struct StackNode {
+    parent: Option<usize>,
+    function: Symbol,
+    /// Time spent in `function` itself while called through this stack.
+    time: Duration,
+}
+
+#[derive(Debug)]
+struct ActiveCall {
+    function: Symbol,
+    stack: usize,
+    /// The line that was running when the call was made.
+    caller_line: Option<usize>,
+    started: Instant,
+    children: Duration,
+}
+
+/// Collects call counts and wall time per function, and time per source
+/// line, while the interpreter runs.
+///
+/// The interpreter calls `enter` right after pushing a frame on the `CallStack`,
+/// `exit` right after popping it and `statement` before each statement.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    functions: HashMap<Symbol, FunctionProfile>,
+    lines: HashMap<usize, LineProfile>,
+    stacks: Vec<StackNode>,
+    /// Finds the node of a stack from its parent's and its top function.
+    stack_ids: HashMap<(Option<usize>, Symbol), usize>,
+    active: Vec<ActiveCall>,
+    /// The line running now and since when its time is counted.
+    line: Option<usize>,
+    line_started: Option<Instant>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn enter(&mut self, call_stack: &CallStack, kind: FunctionKind) {
+        let Some(frame) = call_stack.frames.last() else {
+            return;
+        };
+        let now = Instant::now();
+        self.charge_line(now);
+
+        let profile = self
+            .functions
+            .entry(frame.function)
+            .or_insert(FunctionProfile {
+                kind,
+                calls: 0,
+                inclusive: Duration::ZERO,
+                exclusive: Duration::ZERO,
+            });
+        profile.calls += 1;
+
+        let parent = self.active.last().map(|call| call.stack);
+        let stack = *self
+            .stack_ids
+            .entry((parent, frame.function))
+            .or_insert_with(|| {
+                self.stacks.push(StackNode {
+                    parent,
+                    function: frame.function,
+                    time: Duration::ZERO,
+                });
+                self.stacks.len() - 1
+            });
+
+        self.active.push(ActiveCall {
+            function: frame.function,
+            stack,
+            caller_line: self.line,
+            started: now,
+            children: Duration::ZERO,
+        });
+    }
+
+    pub fn exit(&mut self) {
+        let Some(call) = self.active.pop() else {
+            return;
+        };
+        let now = Instant::now();
+        self.charge_line(now);
+        self.line = call.caller_line;
+
+        let elapsed = now.duration_since(call.started);
+        let own_time = elapsed.saturating_sub(call.children);
+
+        if let Some(parent) = self.active.last_mut() {
+            parent.children += elapsed;
+        }
+
+        let recursive = self
+            .active
+            .iter()
+            .any(|active| active.function == call.function);
+        if let Some(profile) = self.functions.get_mut(&call.function) {
+            profile.exclusive += own_time;
+            if !recursive {
+                profile.inclusive += elapsed;
+            }
+        }
+
+        self.stacks[call.stack].time += own_time;
+    }
+
+    /// A statement starting on `line` is about to run.
+    pub fn statement(&mut self, line: usize) {
+        self.charge_line(Instant::now());
+        self.line = Some(line);
+        self.lines.entry(line).or_default().hits += 1;
+    }
+
+    /// Adds the time since the last event to the line running until `now`.
+    fn charge_line(&mut self, now: Instant) {
+        if let (Some(line), Some(started)) = (self.line, self.line_started) {
+            self.lines.entry(line).or_default().time += now.duration_since(started);
+        }
+        self.line_started = Some(now);
+    }
+
+    /// Closes the calls still in progress after a run fails part-way through,
+    /// keeping the time they spent so far.
+    pub fn unwind(&mut self) {
+        self.unwind_to(0);
+        self.charge_line(Instant::now());
+        self.line = None;
+    }
+
+    /// Closes the calls opened since `depth` calls were in progress, after a
+    /// call made by a native function fails.
+    pub fn unwind_to(&mut self, depth: usize) {
+        while self.active.len() > depth {
+            self.exit();
+        }
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = (Symbol, &FunctionProfile)> {
+        self.functions
+            .iter()
+            .map(|(name, profile)| (*name, profile))
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = (usize, &LineProfile)> {
+        self.lines.iter().map(|(line, profile)| (*line, profile))
+    }
+
+    /// Renders the collected stacks in the folded format read by flamegraph
+    /// tools: one `outer;inner <microseconds>` line per distinct stack.
+    pub fn folded_stacks(&self) -> String {
+        let mut stacks: Vec<_> = self
+            .stacks
+            .iter()
+            .map(|node| {
+                let mut names = vec![node.function.resolve()];
+                let mut parent = node.parent;
+                while let Some(index) = parent {
+                    names.push(self.stacks[index].function.resolve());
+                    parent = self.stacks[index].parent;
+                }
+                names.reverse();
+                (names.join(";"), node.time)
+            })
+            .collect();
+        stacks.sort();
+
+        let mut out = String::new();
+        for (stack, time) in stacks {
+            out.push_str(&format!("{} {}\n", stack, time.as_micros()));
+        }
+        out
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut functions: Vec<_> = self
+            .functions()
+            .map(|(name, profile)| (name.to_string(), profile))
+            .collect();
+        functions.sort_by(|a, b| {
+            b.1.exclusive
+                .cmp(&a.1.exclusive)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+
+        writeln!(
+            f,
+            "{:<24} {:<8} {:>8} {:>14} {:>14}",
+            "function", "kind", "calls", "inclusive ms", "exclusive ms"
+        )?;
+        for (name, profile) in functions {
+            let kind = match profile.kind {
+                FunctionKind::User => "user",
+                FunctionKind::Builtin => "builtin",
+            };
+            writeln!(
+                f,
+                "{:<24} {:<8} {:>8} {:>14.3} {:>14.3}",
+                name,
+                kind,
+                profile.calls,
+                millis(profile.inclusive),
+                millis(profile.exclusive)
+            )?;
+        }
+
+        let mut lines: Vec<_> = self.lines().collect();
+        lines.sort_by(|a, b| b.1.time.cmp(&a.1.time).then_with(|| a.0.cmp(&b.0)));
+
+        writeln!(f)?;
+        writeln!(f, "{:<8} {:>8} {:>14}", "line", "hits", "time ms")?;
+        for (line, profile) in lines.into_iter().take(REPORTED_LINES) {
+            writeln!(
+                f,
+                "{:<8} {:>8} {:>14.3}",
+                line,
+                profile.hits,
+                millis(profile.time)
+            )?;
+        }
+        Ok(())
+    }
+}
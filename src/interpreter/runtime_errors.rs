@@ -2,8 +2,26 @@ use std::fmt;
 
 use crate::interpreter::call_stack::StackFrame;
 
+/// Frames printed from each end of a call stack before the middle is elided.
+const DISPLAYED_STACK_FRAMES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeErrorKind {
+    Error,
+    CallDepthExceeded,
+    StepLimitExceeded,
+    DeadlineExceeded,
+    StringTooLong,
+    ScopeLimitExceeded,
+    VariableLimitExceeded,
+    Interrupted,
+    /// Raised by `assert` and the other assertion builtins.
+    AssertionFailed,
+}
+
 #[derive(Debug, Clone)]
 pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
     pub message: String,
     pub stack: Vec<StackFrame>,
 }
@@ -21,7 +39,12 @@ impl StackAttachable for RuntimeError {
 
 impl RuntimeError {
     pub fn new<S: Into<String>>(msg: S) -> Self {
+        Self::with_kind(RuntimeErrorKind::Error, msg)
+    }
+
+    pub fn with_kind<S: Into<String>>(kind: RuntimeErrorKind, msg: S) -> Self {
         RuntimeError {
+            kind,
             message: msg.into(),
             stack: vec![],
         }
@@ -33,7 +56,16 @@ impl fmt::Display for RuntimeError {
         writeln!(f, "Runtime Error: {}", self.message)?;
         if !self.stack.is_empty() {
             writeln!(f, "Call stack:")?;
-            for frame in self.stack.iter().rev() {
+            let omitted = self.stack.len().saturating_sub(2 * DISPLAYED_STACK_FRAMES);
+            for (index, frame) in self.stack.iter().rev().enumerate() {
+                if omitted > 0 && index >= DISPLAYED_STACK_FRAMES {
+                    if index == DISPLAYED_STACK_FRAMES {
+                        writeln!(f, "  ... {} more frames ...", omitted)?;
+                    }
+                    if index < DISPLAYED_STACK_FRAMES + omitted {
+                        continue;
+                    }
+                }
                 let location_str = frame
                     .location
                     .map(|loc| loc.to_string())
@@ -1,24 +1,54 @@
-use std::{collections::HashMap, rc::Rc};
+use std::collections::HashMap;
 
-use crate::{interpreter::value::Value, node::FunctionDeclaration};
+use crate::{
+    interner::Symbol, interpreter::value::Value, node::FunctionDeclaration, shared::Shared,
+};
 
 pub type ScopeId = usize;
 
 #[derive(Debug)]
 pub struct Scope {
     parent: Option<ScopeId>,
-    variables: HashMap<String, Rc<Value>>,
-    functions: HashMap<String, FunctionDeclaration>,
+    variables: HashMap<Symbol, Shared<Value>>,
+    /// Shared so that looking a function up to call it does not copy its body.
+    functions: HashMap<Symbol, Shared<FunctionDeclaration>>,
 }
 
+/// Scopes are created and discarded in stack order, so the arena is a stack:
+/// leaving a scope drops it together with everything created after it.
 #[derive(Debug)]
 pub struct ScopeArena {
     scopes: Vec<Scope>,
+    variable_count: usize,
 }
 
 impl ScopeArena {
     pub fn new() -> Self {
-        Self { scopes: Vec::new() }
+        Self {
+            scopes: Vec::new(),
+            variable_count: 0,
+        }
+    }
+
+    /// Number of live scopes.
+    pub fn len(&self) -> usize {
+        self.scopes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    /// Number of variables defined across all live scopes.
+    pub fn variable_count(&self) -> usize {
+        self.variable_count
+    }
+
+    /// Drops every scope with an id of `len` or higher.
+    pub fn truncate(&mut self, len: usize) {
+        for scope in self.scopes.drain(len.min(self.scopes.len())..) {
+            self.variable_count -= scope.variables.len();
+        }
     }
 
     pub fn new_scope(&mut self, parent: Option<ScopeId>) -> ScopeId {
@@ -32,24 +62,24 @@ impl ScopeArena {
         self.scopes.len() - 1
     }
 
-    pub fn define_variable(&mut self, scope_id: ScopeId, name: impl Into<String>, value: Rc<Value>) {
-        self.scopes[scope_id].variables.insert(name.into(), value);
+    pub fn define_variable(&mut self, scope_id: ScopeId, name: Symbol, value: Shared<Value>) {
+        if self.scopes[scope_id].variables.insert(name, value).is_none() {
+            self.variable_count += 1;
+        }
     }
 
     pub fn define_function(
         &mut self,
         scope_id: ScopeId,
-        name: impl Into<String>,
-        function: FunctionDeclaration,
+        name: Symbol,
+        function: Shared<FunctionDeclaration>,
     ) {
-        self.scopes[scope_id]
-            .functions
-            .insert(name.into(), function);
+        self.scopes[scope_id].functions.insert(name, function);
     }
 
-    pub fn lookup_variable(&self, mut scope_id: ScopeId, name: &str) -> Option<Rc<Value>> {
+    pub fn lookup_variable(&self, mut scope_id: ScopeId, name: Symbol) -> Option<Shared<Value>> {
         while let Some(scope) = self.scopes.get(scope_id) {
-            if let Some(value) = scope.variables.get(name) {
+            if let Some(value) = scope.variables.get(&name) {
                 return Some(value.clone());
             }
             match scope.parent {
@@ -60,13 +90,36 @@ impl ScopeArena {
         None
     }
 
+    /// Variables defined directly in `scope_id`, not including its parents.
+    pub fn variables(&self, scope_id: ScopeId) -> impl Iterator<Item = (Symbol, &Shared<Value>)> {
+        self.scopes[scope_id]
+            .variables
+            .iter()
+            .map(|(name, value)| (*name, value))
+    }
+
+    /// Functions defined directly in `scope_id`, not including its parents.
+    pub fn functions(
+        &self,
+        scope_id: ScopeId,
+    ) -> impl Iterator<Item = (Symbol, &Shared<FunctionDeclaration>)> {
+        self.scopes[scope_id]
+            .functions
+            .iter()
+            .map(|(name, function)| (*name, function))
+    }
+
+    pub fn defines_variable(&self, scope_id: ScopeId, name: Symbol) -> bool {
+        self.scopes[scope_id].variables.contains_key(&name)
+    }
+
     pub fn lookup_function(
         &self,
         mut scope_id: ScopeId,
-        name: &str,
-    ) -> Option<&FunctionDeclaration> {
+        name: Symbol,
+    ) -> Option<&Shared<FunctionDeclaration>> {
         while let Some(scope) = self.scopes.get(scope_id) {
-            if let Some(function) = scope.functions.get(name) {
+            if let Some(function) = scope.functions.get(&name) {
                 return Some(function);
             }
             match scope.parent {
@@ -0,0 +1,116 @@
+use std::{collections::HashSet, fmt, io::Write};
+
+use crate::{
+    interner::Symbol,
+    interpreter::{io::Output, observer::Observer, profiler::FunctionKind, value::Value},
+    shared::Shared,
+};
+
+/// An `Observer` that logs a program's execution, one event per line,
+/// indented by call depth:
+///
+/// ```text
+/// line 6: let result = scale(4);
+/// call scale(4)
+///   line 3: let doubled = n * 2;
+///   let doubled = 8
+///   line 4: return doubled + base;
+/// return scale = 18
+/// let result = 18
+/// ```
+///
+/// Functions returning nothing log `return f`, and a call that ends with a
+/// tail call logs `return f via tail call` before the call it hands over to.
+pub struct Tracer {
+    lines: Vec<String>,
+    output: Box<dyn Output>,
+    functions: HashSet<Symbol>,
+    /// For each frame of the call stack, whether its events are logged.
+    traced_frames: Vec<bool>,
+}
+
+impl Tracer {
+    /// Logs to `output` the execution of `source`, whose lines are quoted
+    /// with each statement.
+    pub fn new(source: &str, output: Box<dyn Output>) -> Self {
+        Tracer {
+            lines: source.lines().map(|line| line.trim().to_string()).collect(),
+            output,
+            functions: HashSet::new(),
+            traced_frames: Vec::new(),
+        }
+    }
+
+    /// Only logs calls to `function` and what happens while they run. Calling
+    /// it again traces several functions.
+    pub fn with_function(mut self, function: &str) -> Self {
+        self.functions.insert(Symbol::intern(function));
+        self
+    }
+
+    /// Whether events at `depth` happen inside a traced call.
+    fn is_traced(&self, depth: usize) -> bool {
+        self.functions.is_empty()
+            || depth
+                .checked_sub(1)
+                .and_then(|frame| self.traced_frames.get(frame))
+                .copied()
+                .unwrap_or(false)
+    }
+
+    fn log(&mut self, depth: usize, event: fmt::Arguments) {
+        let _ = writeln!(self.output, "{:indent$}{}", "", event, indent = depth * 2);
+    }
+}
+
+impl Observer for Tracer {
+    fn statement(&mut self, line: usize, depth: usize) {
+        if self.is_traced(depth) {
+            let text = line
+                .checked_sub(1)
+                .and_then(|index| self.lines.get(index))
+                .map_or("", String::as_str)
+                .to_string();
+            self.log(depth, format_args!("line {}: {}", line, text));
+        }
+    }
+
+    fn enter_function(
+        &mut self,
+        function: Symbol,
+        _kind: FunctionKind,
+        arguments: &[Shared<Value>],
+        depth: usize,
+    ) {
+        // Frames left over by a run that failed are dropped here.
+        self.traced_frames.truncate(depth);
+        let traced = self.is_traced(depth) || self.functions.contains(&function);
+        self.traced_frames.push(traced);
+
+        if traced {
+            let arguments: Vec<String> = arguments.iter().map(|value| value.repr()).collect();
+            self.log(depth, format_args!("call {}({})", function, arguments.join(", ")));
+        }
+    }
+
+    fn exit_function(&mut self, function: Symbol, value: Option<&Value>, depth: usize) {
+        let traced = self.traced_frames.get(depth).copied().unwrap_or(false);
+        self.traced_frames.truncate(depth);
+
+        if traced {
+            match value {
+                Some(Value::Empty) => self.log(depth, format_args!("return {}", function)),
+                Some(value) => {
+                    self.log(depth, format_args!("return {} = {}", function, value.repr()))
+                }
+                None => self.log(depth, format_args!("return {} via tail call", function)),
+            }
+        }
+    }
+
+    fn define_variable(&mut self, name: Symbol, value: &Value, depth: usize) {
+        if self.is_traced(depth) {
+            self.log(depth, format_args!("let {} = {}", name, value.repr()));
+        }
+    }
+}
@@ -1,6 +1,9 @@
-use std::{ops, rc::Rc};
+use std::ops;
 
-use crate::{interpreter::runtime_errors::RuntimeError};
+use crate::{
+    interpreter::{object::HostObject, runtime_errors::RuntimeError},
+    shared::Shared,
+};
 
 // Integer values and float should be distinguished, also boolean properly
 // handled.
@@ -8,20 +11,48 @@ use crate::{interpreter::runtime_errors::RuntimeError};
 pub enum Value {
     Integer(i64),
     Float(f64),
-    String(Rc<str>),
+    String(Shared<str>),
     Boolean(bool),
+    /// An opaque value provided by the host.
+    Object(HostObject),
     Empty,
 }
 
 impl Value {
+    /// Name of the value's type as shown in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Object(_) => "object",
+            Value::Empty => "empty",
+        }
+    }
+
     /// Convert to a string `Value::String(...)` (keeps same semantics you had).
     pub fn to_string(&self) -> Value {
         match self {
-            Value::Integer(i) => Value::String(Rc::from(i.to_string())),
-            Value::Float(f) => Value::String(Rc::from(f.to_string())),
-            Value::Boolean(b) => Value::String(Rc::from(b.to_string())),
-            Value::Empty => Value::String(Rc::from("")),
-            Value::String(s) => Value::String(s.clone()), // cheap Rc clone
+            Value::Integer(i) => Value::String(Shared::from(i.to_string())),
+            Value::Float(f) => Value::String(Shared::from(f.to_string())),
+            Value::Boolean(b) => Value::String(Shared::from(b.to_string())),
+            Value::Object(o) => Value::String(Shared::from(o.to_string())),
+            Value::Empty => Value::String(Shared::from("")),
+            Value::String(s) => Value::String(s.clone()), // cheap Shared clone
+        }
+    }
+
+    /// The value as the REPL and the debuggers show it: its text, with
+    /// strings quoted so `"1"` and `1` can be told apart.
+    pub fn repr(&self) -> String {
+        match self {
+            Value::String(s) => format!("{:?}", s.as_ref()),
+            Value::Integer(i) => i.to_string(),
+            Value::Float(f) => f.to_string(),
+            Value::Boolean(b) => b.to_string(),
+            Value::Object(o) => o.to_string(),
+            Value::Empty => String::new(),
         }
     }
 
@@ -43,6 +74,10 @@ impl Value {
                 }
             }
             Value::Empty => Ok(Value::Integer(0)),
+            Value::Object(o) => Err(RuntimeError::new(format!(
+                "Unable to convert {} object to number",
+                o.type_name()
+            ))),
             Value::String(s) => {
                 // Try integer parse first, then float
                 if let Ok(i) = s.parse::<i64>() {
@@ -73,8 +108,8 @@ impl Value {
         }
     }
 
-    pub fn into_rc(self) -> Rc<Value> {
-        Rc::new(self)
+    pub fn into_rc(self) -> Shared<Value> {
+        Shared::new(self)
     }
 
     pub fn to_bool(&self) -> bool {
@@ -90,6 +125,9 @@ impl Value {
             // Empty = false
             Value::Empty => false,
 
+            // Host objects are always true
+            Value::Object(_) => true,
+
             // Strings: trim whitespace, then:
             // "" or "0" or "false" (case-insensitive) → false
             // anything else → true
@@ -116,6 +154,7 @@ impl Value {
             (Value::String(a), Value::String(b)) => a == b,
             (Value::Boolean(a), Value::Boolean(b)) => a == b,
             (Value::Empty, Value::Empty) => true,
+            (Value::Object(a), Value::Object(b)) => a == b,
 
             // cross numeric
             (Value::Integer(a), Value::Float(b)) => (*a as f64) == *b,
@@ -262,7 +301,7 @@ impl Value {
                 let mut buf = String::with_capacity(ls.len() + rs.len());
                 buf.push_str(&ls);
                 buf.push_str(&rs);
-                return Ok(Value::String(Rc::from(buf)));
+                return Ok(Value::String(Shared::from(buf)));
             }
         }
 
@@ -331,7 +370,7 @@ impl Eq for Value {}
 #[cfg(test)]
 mod tests {
     use super::Value;
-    use std::rc::Rc;
+    use crate::shared::Shared;
 
     #[test]
     fn and_uses_truthiness_rules() {
@@ -357,8 +396,8 @@ mod tests {
     #[test]
     fn or_uses_truthiness_rules() {
         let empty = Value::Empty;
-        let string_truthy = Value::String(Rc::from("yep"));
-        let string_false = Value::String(Rc::from("false"));
+        let string_truthy = Value::String(Shared::from("yep"));
+        let string_false = Value::String(Shared::from("false"));
 
         assert_eq!(empty.or_value(&string_truthy), Value::Boolean(true));
         assert_eq!(string_false.or_value(&empty), Value::Boolean(false));
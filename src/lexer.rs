@@ -1,5 +1,8 @@
+use crate::interner::Symbol;
 use crate::lexer_errors::{LexerInvalidTokenError, LexerInvalidTokenKind};
 
+use crate::shared::Shared;
+
 use std::fmt;
 
 #[derive(PartialEq, Clone, Debug, Copy)]
@@ -27,6 +30,11 @@ pub enum TokenType {
     BlockStart,
     BlockEnd,
     Return,
+    /// A `//` comment, only produced by `TokenParser::with_comments`.
+    Comment,
+    /// A `///` comment, documenting the function declared right after it.
+    /// Always produced, as the parser attaches it to the declaration.
+    DocComment,
     Eof,
 }
 
@@ -50,6 +58,8 @@ impl fmt::Display for TokenType {
             TokenType::BlockStart => "BlockStart",
             TokenType::BlockEnd => "BlockEnd",
             TokenType::Return => "Return",
+            TokenType::Comment => "Comment",
+            TokenType::DocComment => "DocComment",
             TokenType::Eof => "Eof",
         };
         f.write_str(text)
@@ -100,6 +110,80 @@ pub enum OperatorType {
     Unary(UnaryOperatorSubtype),
 }
 
+impl OperatorType {
+    /// Binding power of the operator and whether it is right-associative.
+    pub fn precedence(&self) -> (i32, bool) {
+        match self {
+            OperatorType::Additive(_) => (1, false),
+            OperatorType::Multiplicative(_) => (2, false),
+            OperatorType::Exponential => (3, true),
+            OperatorType::Comp(_) => (4, false),
+            OperatorType::Boolean(_) => (5, false),
+            OperatorType::Unary(_) => (1, false), // It does not apply for binary ops
+        }
+    }
+}
+
+impl fmt::Display for OperatorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            OperatorType::Additive(AdditiveOperatorSubtype::Add) => "+",
+            OperatorType::Additive(AdditiveOperatorSubtype::Sub) => "-",
+            OperatorType::Multiplicative(MultiplicativeOperatorSubtype::Mul) => "*",
+            OperatorType::Multiplicative(MultiplicativeOperatorSubtype::Div) => "/",
+            OperatorType::Exponential => "^",
+            OperatorType::Comp(CompOperatorSubtype::Eq) => "==",
+            OperatorType::Comp(CompOperatorSubtype::Neq) => "!=",
+            OperatorType::Comp(CompOperatorSubtype::Gt) => ">",
+            OperatorType::Comp(CompOperatorSubtype::Lt) => "<",
+            OperatorType::Comp(CompOperatorSubtype::Gte) => ">=",
+            OperatorType::Comp(CompOperatorSubtype::Lte) => "<=",
+            OperatorType::Boolean(BooleanOperatorSubtype::And) => "&&",
+            OperatorType::Boolean(BooleanOperatorSubtype::Or) => "||",
+            OperatorType::Unary(UnaryOperatorSubtype::Min) => "-",
+            OperatorType::Unary(UnaryOperatorSubtype::Not) => "!",
+        };
+        f.write_str(text)
+    }
+}
+
+/// The text of a token.
+#[derive(PartialEq, Clone, Debug)]
+pub enum TokenValue {
+    /// Names, keywords, operators and punctuation, interned so the parser
+    /// and the runtime can compare them cheaply.
+    Symbol(Symbol),
+    /// Literals and comments. They are kept out of the interner, which never
+    /// frees what it holds, so programs that are parsed over and over, as in
+    /// an editor, do not grow it.
+    Text(Shared<str>),
+}
+
+impl TokenValue {
+    pub fn symbol(&self) -> Option<Symbol> {
+        match self {
+            TokenValue::Symbol(symbol) => Some(*symbol),
+            TokenValue::Text(_) => None,
+        }
+    }
+
+    pub fn text(&self) -> Shared<str> {
+        match self {
+            TokenValue::Symbol(symbol) => Shared::from(&*symbol.resolve()),
+            TokenValue::Text(text) => text.clone(),
+        }
+    }
+}
+
+impl fmt::Display for TokenValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenValue::Symbol(symbol) => symbol.fmt(f),
+            TokenValue::Text(text) => f.write_str(text),
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub struct Token {
     pub start: usize,
@@ -107,18 +191,18 @@ pub struct Token {
     pub line: usize,
     pub token_type: TokenType,
     pub operator_type: Option<OperatorType>,
-    pub value: Option<String>,
+    pub value: Option<TokenValue>,
 }
 
 impl Token {
+    /// The interned name of the token, `None` for literals and comments.
+    pub fn symbol(&self) -> Option<Symbol> {
+        self.value.as_ref().and_then(TokenValue::symbol)
+    }
+
     pub fn operator_predecende(self) -> (i32, bool) {
         match self.operator_type {
-            Some(OperatorType::Additive(_)) => (1, false),
-            Some(OperatorType::Multiplicative(_)) => (2, false),
-            Some(OperatorType::Exponential) => (3, true),
-            Some(OperatorType::Comp(_)) => (4, false),
-            Some(OperatorType::Boolean(_)) => (5, false),
-            Some(OperatorType::Unary(_)) => (1, false), // It does not apply for binary ops
+            Some(operator_type) => operator_type.precedence(),
             None => (1, false),
         }
     }
@@ -129,6 +213,7 @@ pub struct TokenParser {
     column: usize,
     line: usize,
     program: String,
+    keep_comments: bool,
 }
 
 impl TokenParser {
@@ -138,9 +223,17 @@ impl TokenParser {
             column: 1,
             line: 1,
             program,
+            keep_comments: false,
         }
     }
 
+    /// Emits `//` comments as `TokenType::Comment` tokens instead of skipping
+    /// them, for tools that rewrite source code.
+    pub fn with_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
     fn peek(&self) -> Option<char> {
         self.program[self.pos..].chars().next()
     }
@@ -167,8 +260,12 @@ impl TokenParser {
         self.program[start..self.pos].to_string()
     }
 
-    fn slice_range_to_string(&self, start: usize, end: usize) -> String {
-        self.program[start..end].to_string()
+    fn intern_slice(&self, start: usize, end: usize) -> TokenValue {
+        TokenValue::Symbol(Symbol::intern(&self.program[start..end]))
+    }
+
+    fn text_slice(&self, start: usize, end: usize) -> TokenValue {
+        TokenValue::Text(Shared::from(&self.program[start..end]))
     }
 
     pub fn parse(&mut self) -> Result<Vec<Token>, LexerInvalidTokenError> {
@@ -181,13 +278,31 @@ impl TokenParser {
                 }
 
                 '/' if self.peek_with_offset(1) == Some('/') => {
+                    let start = self.pos;
+                    let line = self.line;
                     self.digest();
                     self.digest();
                     while let Some(ch) = self.peek() {
-                        self.digest();
                         if ch == '\n' {
                             break;
                         }
+                        self.digest();
+                    }
+                    let text = &self.program[start..self.pos];
+                    let is_doc = text.starts_with("///") && !text.starts_with("////");
+                    if is_doc || self.keep_comments {
+                        tokens.push(Token {
+                            start,
+                            end: self.pos,
+                            line,
+                            token_type: if is_doc {
+                                TokenType::DocComment
+                            } else {
+                                TokenType::Comment
+                            },
+                            operator_type: None,
+                            value: Some(self.text_slice(start, self.pos)),
+                        });
                     }
                 }
 
@@ -200,7 +315,7 @@ impl TokenParser {
                         line: self.line,
                         token_type: TokenType::EndOfstatement,
                         operator_type: None,
-                        value: Some(";".to_string()),
+                        value: Some(TokenValue::Symbol(Symbol::intern(";"))),
                     });
                 }
 
@@ -214,7 +329,7 @@ impl TokenParser {
                         line: self.line,
                         token_type: TokenType::Operator,
                         operator_type: Some(OperatorType::Boolean(BooleanOperatorSubtype::And)),
-                        value: Some("&&".to_string()),
+                        value: Some(TokenValue::Symbol(Symbol::intern("&&"))),
                     });
                 }
 
@@ -228,7 +343,7 @@ impl TokenParser {
                         line: self.line,
                         token_type: TokenType::Operator,
                         operator_type: Some(OperatorType::Boolean(BooleanOperatorSubtype::Or)),
-                        value: Some("==".to_string()),
+                        value: Some(TokenValue::Symbol(Symbol::intern("||"))),
                     });
                 }
 
@@ -242,7 +357,7 @@ impl TokenParser {
                         line: self.line,
                         token_type: TokenType::Operator,
                         operator_type: Some(OperatorType::Comp(CompOperatorSubtype::Neq)),
-                        value: Some("!=".to_string()),
+                        value: Some(TokenValue::Symbol(Symbol::intern("!="))),
                     });
                 }
                 '>' => {
@@ -256,7 +371,7 @@ impl TokenParser {
                             line: self.line,
                             token_type: TokenType::Operator,
                             operator_type: Some(OperatorType::Comp(CompOperatorSubtype::Gte)),
-                            value: Some(">=".to_string()),
+                            value: Some(TokenValue::Symbol(Symbol::intern(">="))),
                         });
                     } else {
                         tokens.push(Token {
@@ -265,7 +380,7 @@ impl TokenParser {
                             line: self.line,
                             token_type: TokenType::Operator,
                             operator_type: Some(OperatorType::Comp(CompOperatorSubtype::Gt)),
-                            value: Some(">".to_string()),
+                            value: Some(TokenValue::Symbol(Symbol::intern(">"))),
                         });
                     }
                 }
@@ -281,7 +396,7 @@ impl TokenParser {
                             line: self.line,
                             token_type: TokenType::Operator,
                             operator_type: Some(OperatorType::Comp(CompOperatorSubtype::Lte)),
-                            value: Some("<=".to_string()),
+                            value: Some(TokenValue::Symbol(Symbol::intern("<="))),
                         });
                     } else {
                         tokens.push(Token {
@@ -290,7 +405,7 @@ impl TokenParser {
                             line: self.line,
                             token_type: TokenType::Operator,
                             operator_type: Some(OperatorType::Comp(CompOperatorSubtype::Lt)),
-                            value: Some("<".to_string()),
+                            value: Some(TokenValue::Symbol(Symbol::intern("<"))),
                         });
                     }
                 }
@@ -306,7 +421,7 @@ impl TokenParser {
                             line: self.line,
                             token_type: TokenType::Operator,
                             operator_type: Some(OperatorType::Comp(CompOperatorSubtype::Eq)),
-                            value: Some("==".to_string()),
+                            value: Some(TokenValue::Symbol(Symbol::intern("=="))),
                         });
                     } else {
                         tokens.push(Token {
@@ -315,7 +430,7 @@ impl TokenParser {
                             line: self.line,
                             token_type: TokenType::Assignment,
                             operator_type: None,
-                            value: Some("=".to_string()),
+                            value: Some(TokenValue::Symbol(Symbol::intern("="))),
                         });
                     }
                 }
@@ -329,7 +444,7 @@ impl TokenParser {
                             break;
                         }
                     }
-                    let value = self.slice_range_to_string(start + 1, self.pos - 1);
+                    let value = self.text_slice(start + 1, self.pos - 1);
                     tokens.push(Token {
                         start,
                         end: self.pos,
@@ -367,7 +482,7 @@ impl TokenParser {
                         line: self.line,
                         token_type,
                         operator_type: None,
-                        value: Some(text.to_string()),
+                        value: Some(TokenValue::Symbol(Symbol::intern(text))),
                     });
                 }
 
@@ -408,7 +523,7 @@ impl TokenParser {
                             NumeralType::Integer
                         }),
                         operator_type: None,
-                        value: Some(self.slice_to_string(start)),
+                        value: Some(self.text_slice(start, self.pos)),
                     });
                 }
 
@@ -434,7 +549,7 @@ impl TokenParser {
                         line: self.line,
                         token_type: TokenType::Operator,
                         operator_type,
-                        value: Some(self.slice_to_string(start)),
+                        value: Some(self.intern_slice(start, self.pos)),
                     });
                 }
 
@@ -447,7 +562,7 @@ impl TokenParser {
                         line: self.line,
                         token_type: TokenType::Operator,
                         operator_type: Some(OperatorType::Unary(UnaryOperatorSubtype::Not)),
-                        value: Some("!".to_string()),
+                        value: Some(TokenValue::Symbol(Symbol::intern("!"))),
                     });
                 }
 
@@ -462,7 +577,7 @@ impl TokenParser {
                             line: self.line,
                             token_type: TokenType::Operator,
                             operator_type: Some(OperatorType::Boolean(BooleanOperatorSubtype::And)),
-                            value: Some("&&".to_string()),
+                            value: Some(TokenValue::Symbol(Symbol::intern("&&"))),
                         });
                     } else {
                         return Err(LexerInvalidTokenError {
@@ -484,7 +599,7 @@ impl TokenParser {
                             line: self.line,
                             token_type: TokenType::Operator,
                             operator_type: Some(OperatorType::Boolean(BooleanOperatorSubtype::Or)),
-                            value: Some("||".to_string()),
+                            value: Some(TokenValue::Symbol(Symbol::intern("||"))),
                         });
                     } else {
                         return Err(LexerInvalidTokenError {
@@ -513,7 +628,7 @@ impl TokenParser {
                         line: self.line,
                         token_type,
                         operator_type: None,
-                        value: Some(self.slice_to_string(start)),
+                        value: Some(self.intern_slice(start, self.pos)),
                     });
                 }
 
@@ -563,7 +678,7 @@ mod tests {
             assert!(
                 matches!(token, TokenType::NumeralLiteral(_)),
                 "The token must be a NumeralLiteral, {} was found",
-                token.to_string()
+                token
             );
         }
 
@@ -662,4 +777,58 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn comments_are_kept_only_on_request() -> Result<(), Box<dyn Error>> {
+        let program = "let a = 1; // one\n// two\n";
+
+        let tokens = parse_program(program.to_string())?;
+        assert!(tokens.iter().all(|t| t.token_type != TokenType::Comment));
+
+        let comments: Vec<(usize, String)> = TokenParser::new(program.to_string())
+            .with_comments()
+            .parse()?
+            .into_iter()
+            .filter(|t| t.token_type == TokenType::Comment)
+            .map(|t| (t.line, t.value.unwrap().to_string()))
+            .collect();
+        assert_eq!(
+            comments,
+            vec![(1, "// one".to_string()), (2, "// two".to_string())]
+        );
+
+        Ok(())
+    }
+    #[test]
+    fn doc_comments_are_always_kept() -> Result<(), Box<dyn Error>> {
+        let program = "/// Doubles n.\n//// not a doc comment\nfunc double(n) { return n * 2; }";
+
+        let docs: Vec<(usize, String)> = parse_program(program.to_string())?
+            .into_iter()
+            .filter(|t| t.token_type == TokenType::DocComment)
+            .map(|t| (t.line, t.value.unwrap().to_string()))
+            .collect();
+        assert_eq!(docs, vec![(1, "/// Doubles n.".to_string())]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_names_are_interned() -> Result<(), Box<dyn Error>> {
+        let program = "let lexer_name = \"lexer literal\" + 12345.5; // lexer comment";
+
+        let tokens = TokenParser::new(program.to_string())
+            .with_comments()
+            .parse()?;
+        assert_eq!(tokens[1].symbol(), Symbol::lookup("lexer_name"));
+        assert!(tokens[1].symbol().is_some());
+        for text in ["lexer literal", "12345.5", "// lexer comment"] {
+            assert_eq!(Symbol::lookup(text), None, "{} was interned", text);
+            assert!(tokens
+                .iter()
+                .any(|token| token.value == Some(TokenValue::Text(Shared::from(text)))));
+        }
+
+        Ok(())
+    }
 }
@@ -1,7 +1,22 @@
+pub mod analysis;
+pub mod ast_dump;
+pub mod cli;
+pub mod doc;
+pub mod dap;
+pub mod engine;
 pub mod error;
+pub mod formatter;
+pub mod interner;
 pub mod interpreter;
 pub mod lexer;
 pub mod lexer_errors;
+pub mod lsp;
 pub mod node;
 pub mod parser;
 pub mod parser_errors;
+pub mod repl;
+pub mod shared;
+pub mod testing;
+
+pub use engine::Engine;
+pub use error::Error;
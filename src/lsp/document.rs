@@ -0,0 +1,532 @@
+use crate::{
+    analysis::{self, SCRIPT_FUNCTIONS},
+    formatter::expression_text,
+    interpreter::methods::methods,
+    lexer::{Token, TokenParser, TokenType},
+    node::{Expression, Literal},
+    parser::Parser,
+    parser_errors::ParserErrorKind,
+};
+
+const KEYWORDS: &[&str] = &["let", "func", "if", "else", "return", "true", "false"];
+
+/// A position as LSP counts it: zero-based line and UTF-16 column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Variable,
+    Parameter,
+    Function,
+}
+
+/// A name introduced by `let`, `func` or a function parameter.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    /// Byte range of the name.
+    pub start: usize,
+    pub end: usize,
+    /// Source shown on hover, e.g. `let rate = 0.5` or `func add(a, b)`.
+    pub detail: String,
+    /// Type of the value of a `let` initialized with a literal.
+    pub value_type: Option<&'static str>,
+    /// The function the definition is in, if any.
+    pub container: Option<String>,
+    /// Byte range of the body of the function that a function or parameter
+    /// belongs to, while it is known.
+    body: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Function,
+    Variable,
+    Keyword,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String,
+}
+
+/// An open `.rmp` file, analyzed when it is opened or changed.
+pub struct Document {
+    text: String,
+    line_starts: Vec<usize>,
+    tokens: Vec<Token>,
+    definitions: Vec<Definition>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(text.match_indices('\n').map(|(index, _)| index + 1));
+        let mut document = Document {
+            text,
+            line_starts,
+            tokens: Vec::new(),
+            definitions: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        document.analyze();
+        document
+    }
+
+    fn analyze(&mut self) {
+        self.tokens = match TokenParser::new(self.text.clone()).parse() {
+            Ok(tokens) => tokens,
+            Err(err) => {
+                let start = Position {
+                    line: err.line.saturating_sub(1),
+                    character: err.column.saturating_sub(1),
+                };
+                let end = Position {
+                    character: start.character + 1,
+                    ..start
+                };
+                self.error(Range { start, end }, err.kind.to_string());
+                return;
+            }
+        };
+        self.definitions = collect_definitions(&self.tokens);
+
+        let ast = match Parser::new(self.tokens.clone()).parse() {
+            Ok(ast) => ast,
+            Err(err) => {
+                let range = match &err.kind {
+                    ParserErrorKind::UnrecognizedToken(token)
+                    | ParserErrorKind::UnexpectedToken(_, token) => {
+                        self.range(token.start, token.end.max(token.start))
+                    }
+                    _ => self.range(self.text.len(), self.text.len()),
+                };
+                self.error(range, err.kind.to_string());
+                return;
+            }
+        };
+
+        describe_definitions(&ast, &mut self.definitions);
+
+        let host_functions: Vec<&str> = SCRIPT_FUNCTIONS.iter().map(|(name, _)| *name).collect();
+        for diagnostic in analysis::analyze(&ast, &host_functions) {
+            let range = match diagnostic.line {
+                Some(line) => self.line_range(line.saturating_sub(1)),
+                None => self.range(0, 0),
+            };
+            self.diagnostics.push(Diagnostic {
+                range,
+                severity: Severity::Warning,
+                message: diagnostic.message,
+            });
+        }
+    }
+
+    fn error(&mut self, range: Range, message: String) {
+        self.diagnostics.push(Diagnostic {
+            range,
+            severity: Severity::Error,
+            message,
+        });
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Functions and variables, for the document outline. Parameters are
+    /// left out.
+    pub fn symbols(&self) -> impl Iterator<Item = &Definition> {
+        self.definitions
+            .iter()
+            .filter(|definition| definition.kind != DefinitionKind::Parameter)
+    }
+
+    pub fn definition_range(&self, definition: &Definition) -> Range {
+        self.range(definition.start, definition.end)
+    }
+
+    /// The definition of the name at `position`.
+    pub fn definition(&self, position: Position) -> Option<&Definition> {
+        let (token, offset) = self.symbol_at(position)?;
+        let name = token.symbol()?.resolve();
+        if self.is_function_name(token) {
+            self.resolve(&name, offset, |kind| kind == DefinitionKind::Function)
+        } else {
+            self.resolve(&name, offset, |kind| kind != DefinitionKind::Function)
+        }
+    }
+
+    /// Markdown describing the name at `position`, and the range of that name.
+    pub fn hover(&self, position: Position) -> Option<(Range, String)> {
+        let (token, _) = self.symbol_at(position)?;
+        let range = self.range(token.start, token.end);
+        let name = token.symbol()?.resolve();
+
+        let contents = match self.definition(position) {
+            Some(definition) => {
+                let mut contents = format!("```rmp\n{}\n```", definition.detail);
+                if let Some(value_type) = definition.value_type {
+                    contents.push_str(&format!("\n\n{}", value_type));
+                }
+                if let (DefinitionKind::Parameter, Some(function)) =
+                    (definition.kind, &definition.container)
+                {
+                    contents.push_str(&format!("\n\nParameter of `{}`", function));
+                }
+                contents
+            }
+            None if self.is_function_name(token) => {
+                if let Some(method) = methods().find(|method| method.name == &*name) {
+                    let mut contents = format!("```rmp\n{}\n```\n\nBuiltin", method.signature);
+                    if !method.doc.is_empty() {
+                        contents.push_str(&format!(": {}", method.doc));
+                    }
+                    contents
+                } else {
+                    let (_, signature) = SCRIPT_FUNCTIONS.iter().find(|(known, _)| *known == &*name)?;
+                    format!("```rmp\n{}\n```\n\nProvided by `rmp run`", signature)
+                }
+            }
+            None => return None,
+        };
+        Some((range, contents))
+    }
+
+    /// Every name that can be completed: definitions, builtins and keywords.
+    pub fn completions(&self) -> Vec<Completion> {
+        let mut completions: Vec<Completion> = Vec::new();
+        let mut push = |label: &str, kind, detail: &str| {
+            if !completions.iter().any(|completion| completion.label == label) {
+                completions.push(Completion {
+                    label: label.to_string(),
+                    kind,
+                    detail: detail.to_string(),
+                });
+            }
+        };
+
+        for definition in &self.definitions {
+            let kind = match definition.kind {
+                DefinitionKind::Function => CompletionKind::Function,
+                _ => CompletionKind::Variable,
+            };
+            push(&definition.name, kind, &definition.detail);
+        }
+        let mut builtins: Vec<_> = methods().collect();
+        builtins.sort_by_key(|method| method.name);
+        for method in builtins {
+            push(method.name, CompletionKind::Function, method.signature);
+        }
+        for (name, signature) in SCRIPT_FUNCTIONS {
+            push(name, CompletionKind::Function, signature);
+        }
+        for keyword in KEYWORDS {
+            push(keyword, CompletionKind::Keyword, "");
+        }
+        completions
+    }
+
+    /// The symbol token under or right before the cursor.
+    fn symbol_at(&self, position: Position) -> Option<(&Token, usize)> {
+        let offset = self.offset(position);
+        let token = self.tokens.iter().find(|token| {
+            token.token_type == TokenType::Symbol && token.start <= offset && offset <= token.end
+        })?;
+        Some((token, offset))
+    }
+
+    fn is_function_name(&self, token: &Token) -> bool {
+        let index = self
+            .tokens
+            .iter()
+            .position(|candidate| std::ptr::eq(candidate, token));
+        index.is_some_and(|index| {
+            let next = self.tokens.get(index + 1).map(|next| &next.token_type);
+            let previous = index
+                .checked_sub(1)
+                .and_then(|previous| self.tokens.get(previous))
+                .map(|previous| &previous.token_type);
+            next == Some(&TokenType::ParenthesisL)
+                && previous != Some(&TokenType::Declaration)
+        })
+    }
+
+    /// Finds the definition `name` at `offset` most likely refers to. Scoping
+    /// is dynamic, so this is a guess: a parameter of the enclosing
+    /// function, else the closest definition above, else any definition.
+    fn resolve(
+        &self,
+        name: &str,
+        offset: usize,
+        accepts: impl Fn(DefinitionKind) -> bool,
+    ) -> Option<&Definition> {
+        let candidates = || {
+            self.definitions
+                .iter()
+                .filter(|definition| definition.name == name && accepts(definition.kind))
+        };
+        let in_body = |definition: &&Definition| {
+            definition
+                .body
+                .is_some_and(|(start, end)| start <= offset && offset <= end)
+        };
+
+        candidates()
+            .filter(|definition| definition.kind == DefinitionKind::Parameter)
+            .filter(in_body)
+            .last()
+            .or_else(|| {
+                candidates()
+                    .filter(|definition| definition.start <= offset)
+                    .last()
+            })
+            .or_else(|| candidates().next())
+    }
+
+    fn offset(&self, position: Position) -> usize {
+        let Some(&line_start) = self.line_starts.get(position.line) else {
+            return self.text.len();
+        };
+        let mut units = 0;
+        for (index, ch) in self.text[line_start..].char_indices() {
+            if units >= position.character || ch == '\n' {
+                return line_start + index;
+            }
+            units += ch.len_utf16();
+        }
+        self.text.len()
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let character = self.text[self.line_starts[line]..offset]
+            .encode_utf16()
+            .count();
+        Position { line, character }
+    }
+
+    fn range(&self, start: usize, end: usize) -> Range {
+        Range {
+            start: self.position(start),
+            end: self.position(end),
+        }
+    }
+
+    /// The text of `line`, without its indentation.
+    fn line_range(&self, line: usize) -> Range {
+        let Some(&start) = self.line_starts.get(line) else {
+            return self.range(self.text.len(), self.text.len());
+        };
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .map_or(self.text.len(), |next| next - 1);
+        let indent = self.text[start..end].len() - self.text[start..end].trim_start().len();
+        self.range(start + indent, end)
+    }
+}
+
+/// Finds definitions from the tokens alone, so they are known even while the
+/// program does not parse.
+fn collect_definitions(tokens: &[Token]) -> Vec<Definition> {
+    let mut definitions: Vec<Definition> = Vec::new();
+    // Functions whose body is open: the index of their definition and the
+    // brace depth inside it.
+    let mut open_functions: Vec<(usize, usize)> = Vec::new();
+    let mut pending_function = None;
+    let mut depth = 0;
+
+    let name = |token: &Token| {
+        token
+            .value
+            .as_ref()
+            .map(|value| value.to_string())
+            .unwrap_or_default()
+    };
+    let symbol_at = |index: usize| {
+        tokens
+            .get(index)
+            .filter(|token| token.token_type == TokenType::Symbol)
+    };
+
+    for (index, token) in tokens.iter().enumerate() {
+        let container = open_functions
+            .last()
+            .map(|(function, _)| definitions[*function].name.clone());
+        match token.token_type {
+            TokenType::Declaration => {
+                if let Some(symbol) = symbol_at(index + 1) {
+                    definitions.push(Definition {
+                        name: name(symbol),
+                        kind: DefinitionKind::Variable,
+                        start: symbol.start,
+                        end: symbol.end,
+                        detail: format!("let {}", name(symbol)),
+                        value_type: None,
+                        container,
+                        body: None,
+                    });
+                }
+            }
+            TokenType::FunctionDeclaration => {
+                let Some(symbol) = symbol_at(index + 1) else {
+                    continue;
+                };
+                let function = name(symbol);
+                let parameters: Vec<&Token> = tokens[(index + 2).min(tokens.len())..]
+                    .iter()
+                    .skip(1)
+                    .take_while(|token| {
+                        matches!(
+                            token.token_type,
+                            TokenType::Symbol | TokenType::ArgumentSeparator
+                        )
+                    })
+                    .filter(|token| token.token_type == TokenType::Symbol)
+                    .collect();
+                let parameter_names: Vec<String> = parameters.iter().map(|token| name(token)).collect();
+
+                pending_function = Some(definitions.len());
+                definitions.push(Definition {
+                    name: function.clone(),
+                    kind: DefinitionKind::Function,
+                    start: symbol.start,
+                    end: symbol.end,
+                    detail: format!("func {}({})", function, parameter_names.join(", ")),
+                    value_type: None,
+                    container,
+                    body: None,
+                });
+                for parameter in parameters {
+                    definitions.push(Definition {
+                        name: name(parameter),
+                        kind: DefinitionKind::Parameter,
+                        start: parameter.start,
+                        end: parameter.end,
+                        detail: name(parameter),
+                        value_type: None,
+                        container: Some(function.clone()),
+                        body: None,
+                    });
+                }
+            }
+            TokenType::BlockStart => {
+                depth += 1;
+                if let Some(function) = pending_function.take() {
+                    open_functions.push((function, depth));
+                    definitions[function].body = Some((token.start, usize::MAX));
+                }
+            }
+            TokenType::BlockEnd => {
+                if let Some(&(function, function_depth)) = open_functions.last() {
+                    if function_depth == depth {
+                        open_functions.pop();
+                        if let Some(body) = &mut definitions[function].body {
+                            body.1 = token.end;
+                        }
+                    }
+                }
+                depth = depth.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+
+    // Parameters share the body of their function.
+    let mut body = None;
+    for definition in &mut definitions {
+        match definition.kind {
+            DefinitionKind::Function => body = definition.body,
+            DefinitionKind::Parameter => definition.body = body,
+            DefinitionKind::Variable => {}
+        }
+    }
+    definitions
+}
+
+/// Completes the details of `let` definitions with their initializer, taken
+/// from the tree. Declarations appear in the tree in source order.
+fn describe_definitions(ast: &Expression, definitions: &mut [Definition]) {
+    let mut declarations = Vec::new();
+    collect_declarations(ast, &mut declarations);
+
+    let variables = definitions
+        .iter_mut()
+        .filter(|definition| definition.kind == DefinitionKind::Variable);
+    for (definition, declaration) in variables.zip(declarations) {
+        if definition.name == declaration.name {
+            definition.detail = declaration.detail;
+            definition.value_type = declaration.value_type;
+        }
+    }
+}
+
+struct Declaration {
+    name: String,
+    detail: String,
+    value_type: Option<&'static str>,
+}
+
+fn collect_declarations(expression: &Expression, declarations: &mut Vec<Declaration>) {
+    let block = match expression {
+        Expression::Program(program) => &program.body,
+        Expression::Statement(inner, _) => return collect_declarations(inner, declarations),
+        Expression::Declaration(identifier, value) => {
+            let name = identifier.name.to_string();
+            let value_type = match value.as_ref() {
+                Expression::Literal(Literal::Integer(_)) => Some("integer"),
+                Expression::Literal(Literal::Float(_)) => Some("float"),
+                Expression::Literal(Literal::String(_)) => Some("string"),
+                Expression::Literal(Literal::Boolean(_)) => Some("boolean"),
+                _ => None,
+            };
+            declarations.push(Declaration {
+                detail: format!("let {} = {}", name, expression_text(value)),
+                name,
+                value_type,
+            });
+            return;
+        }
+        Expression::FunctionDeclaration(function) => &function.block,
+        Expression::Block(block) => block,
+        Expression::IfConditional(_, if_block, else_block) => {
+            for statement in if_block.iter().chain(else_block.iter().flatten()) {
+                collect_declarations(statement, declarations);
+            }
+            return;
+        }
+        _ => return,
+    };
+    for statement in block {
+        collect_declarations(statement, declarations);
+    }
+}
@@ -0,0 +1,267 @@
+//! A Language Server Protocol server for `.rmp` files, run by the `rmp-lsp`
+//! binary over stdio.
+//!
+//! It publishes diagnostics from the lexer, parser and `analysis`, and
+//! answers hover, go-to-definition, document symbol and completion
+//! requests. Documents are synchronized in full on every change.
+
+pub mod document;
+pub(crate) mod transport;
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value};
+
+use document::{CompletionKind, DefinitionKind, Document, Position, Range, Severity};
+use transport::{read_message, write_message};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serves requests read from `input` until the client sends `exit` or closes
+/// the input. Returns the exit code: 0 when the client shut the server down
+/// first, 1 otherwise.
+pub fn serve(mut input: impl BufRead, output: impl Write) -> io::Result<i32> {
+    let mut server = Server {
+        output,
+        documents: HashMap::new(),
+        shut_down: false,
+    };
+
+    while let Some(body) = read_message(&mut input)? {
+        let message: Value = match serde_json::from_str(&body) {
+            Ok(message) => message,
+            Err(err) => {
+                server.respond_error(Value::Null, PARSE_ERROR, &err.to_string())?;
+                continue;
+            }
+        };
+        if message["method"] == "exit" {
+            break;
+        }
+        server.handle(message)?;
+    }
+    Ok(if server.shut_down { 0 } else { 1 })
+}
+
+struct Server<W> {
+    output: W,
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+}
+
+/// A request that cannot be answered: an error code and message.
+type Failure = (i64, String);
+
+impl<W: Write> Server<W> {
+    fn handle(&mut self, message: Value) -> io::Result<()> {
+        let Some(method) = message["method"].as_str() else {
+            // A response to a request the server never sends.
+            return Ok(());
+        };
+        let params = &message["params"];
+
+        let Some(id) = message.get("id").cloned() else {
+            return self.notification(method, params);
+        };
+        let result = if self.shut_down {
+            Err((INVALID_REQUEST, "The server is shut down".to_string()))
+        } else {
+            self.request(method, params)
+        };
+        match result {
+            Ok(result) => self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result })),
+            Err((code, message)) => self.respond_error(id, code, &message),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, Failure> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "rmp-lsp", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => {
+                let (_, document, position) = self.document_position(params)?;
+                Ok(match document.hover(position) {
+                    Some((range, contents)) => json!({
+                        "contents": { "kind": "markdown", "value": contents },
+                        "range": range_json(range),
+                    }),
+                    None => Value::Null,
+                })
+            }
+            "textDocument/definition" => {
+                let (uri, document, position) = self.document_position(params)?;
+                Ok(match document.definition(position) {
+                    Some(definition) => json!({
+                        "uri": uri,
+                        "range": range_json(document.definition_range(definition)),
+                    }),
+                    None => Value::Null,
+                })
+            }
+            "textDocument/documentSymbol" => {
+                let (uri, document) = self.document(params)?;
+                let symbols: Vec<Value> = document
+                    .symbols()
+                    .map(|definition| {
+                        let kind = match definition.kind {
+                            DefinitionKind::Function => 12,
+                            _ => 13,
+                        };
+                        let mut symbol = json!({
+                            "name": definition.name,
+                            "kind": kind,
+                            "location": {
+                                "uri": uri,
+                                "range": range_json(document.definition_range(definition)),
+                            },
+                        });
+                        if let Some(container) = &definition.container {
+                            symbol["containerName"] = json!(container);
+                        }
+                        symbol
+                    })
+                    .collect();
+                Ok(Value::Array(symbols))
+            }
+            "textDocument/completion" => {
+                let (_, document) = self.document(params)?;
+                let items: Vec<Value> = document
+                    .completions()
+                    .into_iter()
+                    .map(|completion| {
+                        let kind = match completion.kind {
+                            CompletionKind::Function => 3,
+                            CompletionKind::Variable => 6,
+                            CompletionKind::Keyword => 14,
+                        };
+                        json!({ "label": completion.label, "kind": kind, "detail": completion.detail })
+                    })
+                    .collect();
+                Ok(Value::Array(items))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let uri = params["textDocument"]["uri"].as_str().map(str::to_string);
+        match (method, uri) {
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.update(uri, text.to_string())
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // Full synchronization: the last change holds the whole text.
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                match text {
+                    Some(text) => self.update(uri, text.to_string()),
+                    None => Ok(()),
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri, Vec::new())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn update(&mut self, uri: String, text: String) -> io::Result<()> {
+        let document = Document::new(text);
+        let diagnostics = document
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| {
+                let severity = match diagnostic.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                };
+                json!({
+                    "range": range_json(diagnostic.range),
+                    "severity": severity,
+                    "source": "rmp",
+                    "message": diagnostic.message,
+                })
+            })
+            .collect();
+        self.documents.insert(uri.clone(), document);
+        self.publish_diagnostics(&uri, diagnostics)
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Value>) -> io::Result<()> {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    fn document<'a>(&'a self, params: &'a Value) -> Result<(&'a str, &'a Document), Failure> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or_else(|| (INVALID_PARAMS, "Missing textDocument.uri".to_string()))?;
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document {}", uri)))?;
+        Ok((uri, document))
+    }
+
+    fn document_position<'a>(
+        &'a self,
+        params: &'a Value,
+    ) -> Result<(&'a str, &'a Document, Position), Failure> {
+        let (uri, document) = self.document(params)?;
+        let field = |name: &str| {
+            params["position"][name]
+                .as_u64()
+                .map(|value| value as usize)
+                .ok_or_else(|| (INVALID_PARAMS, format!("Missing position.{}", name)))
+        };
+        let position = Position {
+            line: field("line")?,
+            character: field("character")?,
+        };
+        Ok((uri, document, position))
+    }
+
+    fn respond_error(&mut self, id: Value, code: i64, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }))
+    }
+
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        write_message(&mut self.output, &message)
+    }
+}
+
+fn range_json(range: Range) -> Value {
+    json!({
+        "start": { "line": range.start.line, "character": range.start.character },
+        "end": { "line": range.end.line, "character": range.end.character },
+    })
+}
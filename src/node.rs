@@ -1,5 +1,6 @@
-use std::rc::Rc;
+use crate::shared::Shared;
 
+use crate::interner::Symbol;
 use crate::lexer::{NumeralType, OperatorType, Token, TokenType, UnaryOperatorSubtype};
 
 #[derive(Debug, PartialEq, Clone)]
@@ -7,12 +8,12 @@ pub enum Literal {
     Boolean(bool),
     Integer(i64),
     Float(f64),
-    String(Rc<str>),
+    String(Shared<str>),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Identifier {
-    pub name: String,
+    pub name: Symbol,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -32,17 +33,32 @@ pub struct FunctionDeclaration {
     pub identifier: Identifier,
     pub arguments: Vec<Identifier>,
     pub block: Block,
+    /// The `///` comments right above the declaration, without their `///`
+    /// and one space, joined by newlines.
+    pub doc: Option<Shared<str>>,
 }
 
 pub type Block = Vec<Expression>;
 
+/// Source text with no meaning to the interpreter, kept in blocks by
+/// `Parser::with_trivia` for tools that rewrite source code.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Trivia {
+    /// A `//` comment. A trailing comment follows the previous statement on
+    /// its line.
+    Comment { text: Shared<str>, trailing: bool },
+    /// One or more empty lines between statements.
+    BlankLine,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     Literal(Literal),
     BinaryOperation(Box<Expression>, OperatorType, Box<Expression>),
     UnaryOperation(OperatorType, Box<Expression>),
     Program(Program), // Change to block?
-    Statement(Box<Expression>),
+    /// A statement of a block and the line it starts on.
+    Statement(Box<Expression>, usize),
     FunctionCall(FunctionCall),
     Identifier(Identifier),
     Declaration(Identifier, Box<Expression>),
@@ -50,10 +66,11 @@ pub enum Expression {
     FunctionDeclaration(FunctionDeclaration),
     Return(Box<Expression>),
     IfConditional(Box<Expression>, Block, Option<Block>),
+    Trivia(Trivia),
 }
 
 pub fn build_function_call_node(
-    method_name: String,
+    method_name: Symbol,
     args: Vec<Expression>,
     location: usize,
 ) -> Box<Expression> {
@@ -84,7 +101,7 @@ pub fn build_binary_op_node(
     Box::new(Expression::BinaryOperation(left, operator, right))
 }
 
-pub fn build_assignment_node(identifier: String, expr: Box<Expression>) -> Box<Expression> {
+pub fn build_assignment_node(identifier: Symbol, expr: Box<Expression>) -> Box<Expression> {
     Box::new(Expression::Declaration(
         Identifier { name: identifier },
         expr,
@@ -96,18 +113,19 @@ pub fn build_return_node(expr: Box<Expression>) -> Box<Expression> {
 }
 
 pub fn build_function_declaration_node(
-    identifier: String,
-    args: Vec<String>,
+    identifier: Symbol,
+    args: Vec<Symbol>,
     block: Block,
+    doc: Option<Shared<str>>,
 ) -> Box<Expression> {
     Box::new(Expression::FunctionDeclaration(FunctionDeclaration {
         identifier: Identifier { name: identifier },
-        // TODO: How can I prevent cloning?
         arguments: args
-            .iter()
-            .map(|arg| Identifier { name: arg.clone() })
+            .into_iter()
+            .map(|arg| Identifier { name: arg })
             .collect(),
         block,
+        doc,
     }))
 }
 
@@ -117,11 +135,7 @@ pub fn build_node(
     right: Option<Box<Expression>>,
 ) -> Box<Expression> {
     // Safely get token value
-    let value = token
-        .value
-        .as_ref()
-        .expect("Token value missing")
-        .to_string();
+    let value = token.value.as_ref().expect("Token value missing").text();
 
     match token.token_type {
         TokenType::NumeralLiteral(numeral_type) => match numeral_type {
@@ -134,7 +148,7 @@ pub fn build_node(
                 build_numerical_literal_node(Literal::Float(f))
             }
         },
-        TokenType::StringLiteral => build_numerical_literal_node(Literal::String(Rc::from(value))),
+        TokenType::StringLiteral => build_numerical_literal_node(Literal::String(value)),
         TokenType::BooleanLiteral => {
             let b = value.parse::<bool>().unwrap_or_default();
             build_numerical_literal_node(Literal::Boolean(b))
@@ -150,8 +164,14 @@ pub fn build_node(
                 right.expect("Right operand missing"),
             )
         }
-        TokenType::Assignment => build_assignment_node(value, left.expect("Left operand missing")),
-        TokenType::Symbol => Box::new(Expression::Identifier(Identifier { name: value })),
+        TokenType::Assignment => {
+            let symbol = token.symbol().expect("Token value missing");
+            build_assignment_node(symbol, left.expect("Left operand missing"))
+        }
+        TokenType::Symbol => {
+            let symbol = token.symbol().expect("Token value missing");
+            Box::new(Expression::Identifier(Identifier { name: symbol }))
+        }
         _ => panic!("Unexpected token type to process when building node."),
     }
 }
@@ -170,6 +190,6 @@ pub fn build_program_node(body: Vec<Expression>) -> Box<Expression> {
     Box::new(Expression::Program(Program { body }))
 }
 
-pub fn build_statement_node(expr: Box<Expression>) -> Expression {
-    Expression::Statement(expr)
+pub fn build_statement_node(expr: Box<Expression>, line: usize) -> Expression {
+    Expression::Statement(expr, line)
 }
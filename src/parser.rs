@@ -1,16 +1,32 @@
+use std::collections::VecDeque;
+
 use crate::lexer::{
-    self, AdditiveOperatorSubtype, OperatorType, Token, TokenType, UnaryOperatorSubtype,
+    self, AdditiveOperatorSubtype, OperatorType, Token, TokenType, TokenValue,
+    UnaryOperatorSubtype,
 };
 use crate::node::{
     build_assignment_node, build_conditional_node, build_function_declaration_node,
     build_function_call_node, build_node, build_program_node, build_return_node,
-    build_statement_node, build_unary_node, Block, Expression,
+    build_statement_node, build_unary_node, Block, Expression, Trivia,
 };
 use crate::parser_errors::{ParserError, ParserErrorKind};
+use crate::shared::Shared;
 
 pub struct Parser {
     pos: usize,
     tokens: Vec<lexer::Token>,
+    /// Comments not yet placed in the tree, in source order. Only used by
+    /// `with_trivia`.
+    comments: VecDeque<lexer::Token>,
+    /// Comments found before the `{` of a block, waiting to be placed before
+    /// the statement the block belongs to.
+    hoisted_comments: Vec<lexer::Token>,
+    keep_trivia: bool,
+    /// Every `///` comment, in source order, to attach to the function
+    /// declared right after it.
+    doc_comments: Vec<lexer::Token>,
+    /// Line of the last token or comment consumed, to detect blank lines.
+    last_line: usize,
 }
 
 fn error_unexpected_token(token: &Token, expected_token_type: &TokenType) -> ParserError {
@@ -31,6 +47,13 @@ fn error_eof() -> ParserError {
     }
 }
 
+fn comment_node(comment: Token, trailing: bool) -> Expression {
+    let text = comment
+        .value
+        .map_or_else(|| Shared::from(""), |text| text.text());
+    Expression::Trivia(Trivia::Comment { text, trailing })
+}
+
 fn error_unexpected_empty_value() -> ParserError {
     ParserError {
         kind: ParserErrorKind::UnexpectedEmptyValue,
@@ -39,7 +62,51 @@ fn error_unexpected_empty_value() -> ParserError {
 
 impl Parser {
     pub fn new(tokens: Vec<lexer::Token>) -> Self {
-        Parser { pos: 0, tokens }
+        let (doc_comments, tokens): (Vec<Token>, Vec<Token>) = tokens
+            .into_iter()
+            .filter(|token| token.token_type != TokenType::Comment)
+            .partition(|token| token.token_type == TokenType::DocComment);
+        Parser {
+            pos: 0,
+            tokens,
+            comments: VecDeque::new(),
+            hoisted_comments: Vec::new(),
+            keep_trivia: false,
+            doc_comments,
+            last_line: 0,
+        }
+    }
+
+    /// Like `new`, but keeps comments and blank lines between statements in
+    /// the tree as `Expression::Trivia`, for tools that rewrite source code.
+    /// Comments inside a statement's expressions are moved before the
+    /// statement. `tokens` come from `TokenParser::with_comments`.
+    pub fn with_trivia(tokens: Vec<lexer::Token>) -> Self {
+        let (comments, tokens): (Vec<Token>, Vec<Token>) = tokens.into_iter().partition(|token| {
+            matches!(token.token_type, TokenType::Comment | TokenType::DocComment)
+        });
+        let doc_comments = comments
+            .iter()
+            .filter(|token| token.token_type == TokenType::DocComment)
+            .cloned()
+            .collect();
+        let comments = VecDeque::from(comments);
+        let first_line = comments
+            .front()
+            .into_iter()
+            .chain(tokens.first())
+            .map(|token| token.line)
+            .min()
+            .unwrap_or(1);
+        Parser {
+            pos: 0,
+            tokens,
+            comments,
+            hoisted_comments: Vec::new(),
+            keep_trivia: true,
+            doc_comments,
+            last_line: first_line,
+        }
     }
 
     fn peek(&self, pos: Option<usize>) -> Option<&lexer::Token> {
@@ -58,6 +125,7 @@ impl Parser {
         }
 
         self.pos += 1;
+        self.last_line = token.line;
         Ok(token)
     }
 
@@ -76,6 +144,7 @@ impl Parser {
     }
 
     fn parse_function_declaration(&mut self) -> Result<Box<Expression>, ParserError> {
+        let doc = self.doc_comment();
         self.digest(TokenType::FunctionDeclaration)?;
 
         let identifier = self.digest(TokenType::Symbol)?;
@@ -92,7 +161,7 @@ impl Parser {
             // Function arguments
             args.push(
                 self.digest(TokenType::Symbol)?
-                    .value
+                    .symbol()
                     .ok_or_else(error_unexpected_empty_value)?,
             );
 
@@ -110,9 +179,39 @@ impl Parser {
 
         let block = self.parse_block_with_delimiters()?;
 
-        let identifier = identifier.value.ok_or_else(error_unexpected_empty_value)?;
+        let identifier = identifier.symbol().ok_or_else(error_unexpected_empty_value)?;
 
-        Ok(build_function_declaration_node(identifier, args, block))
+        Ok(build_function_declaration_node(identifier, args, block, doc))
+    }
+
+    /// The `///` comments on the lines right above the next token, with
+    /// nothing else between them and it.
+    fn doc_comment(&self) -> Option<Shared<str>> {
+        let token = self.peek(None)?;
+        let previous_end = self
+            .pos
+            .checked_sub(1)
+            .map_or(0, |previous| self.tokens[previous].end);
+        let before = self
+            .doc_comments
+            .partition_point(|comment| comment.start < token.start);
+
+        let mut line = token.line;
+        let mut lines = Vec::new();
+        for comment in self.doc_comments[..before].iter().rev() {
+            if comment.start < previous_end || comment.line + 1 != line {
+                break;
+            }
+            line = comment.line;
+            let text = comment.value.as_ref().map(TokenValue::text).unwrap_or_default();
+            let text = text.strip_prefix("///").unwrap_or(&text);
+            lines.push(text.strip_prefix(' ').unwrap_or(text).trim_end().to_string());
+        }
+        if lines.is_empty() {
+            return None;
+        }
+        lines.reverse();
+        Some(Shared::from(lines.join("\n")))
     }
 
     fn parse_block_with_delimiters(&mut self) -> Result<Block, ParserError> {
@@ -126,7 +225,21 @@ impl Parser {
     fn parse_block(&mut self) -> Result<Block, ParserError> {
         let mut body = vec![];
 
+        if self.keep_trivia && self.pos > 0 {
+            let block_start = self.tokens[self.pos - 1].start;
+            while matches!(self.comments.front(), Some(comment) if comment.start < block_start) {
+                let comment = self.comments.pop_front().expect("a pending comment");
+                self.hoisted_comments.push(comment);
+            }
+        }
+
         while let Some(token) = self.peek(None) {
+            if self.keep_trivia {
+                let is_end = matches!(token.token_type, TokenType::Eof | TokenType::BlockEnd);
+                let (start, line) = (token.start, token.line);
+                self.push_leading_trivia(&mut body, start, line, is_end);
+            }
+            let token = self.peek(None).ok_or_else(error_eof)?;
             if token.token_type == TokenType::Eof {
                 self.digest(TokenType::Eof)?; // consume EOF
                 break;
@@ -135,15 +248,77 @@ impl Parser {
                 break;
             }
 
+            let statement_index = body.len();
+            let (statement_start, statement_line) = (token.start, token.line);
             let stmt = self.parse_statement()?;
             self.consume_statement_terminator(stmt.as_ref())?;
 
-            body.push(build_statement_node(stmt));
+            body.push(build_statement_node(stmt, statement_line));
+            if self.keep_trivia {
+                self.push_statement_trivia(&mut body, statement_index, statement_start);
+            }
         }
 
         Ok(body)
     }
 
+    /// Places the comments before the token at `start`, and a blank line
+    /// before that token unless it ends the block.
+    fn push_leading_trivia(&mut self, body: &mut Block, start: usize, line: usize, is_end: bool) {
+        while let Some(comment) = self.comments.front() {
+            if comment.start >= start {
+                break;
+            }
+            let line = comment.line;
+            self.push_blank_line(body, line);
+            let comment = self.comments.pop_front().expect("a pending comment");
+            body.push(comment_node(comment, false));
+            self.last_line = line;
+        }
+        if !is_end {
+            self.push_blank_line(body, line);
+        }
+    }
+
+    /// Moves the comments left inside the statement at `statement_index`
+    /// before it, then attaches a comment on its last line as trailing.
+    fn push_statement_trivia(
+        &mut self,
+        body: &mut Block,
+        statement_index: usize,
+        statement_start: usize,
+    ) {
+        let end = self.tokens[self.pos - 1].end;
+        let hoisted = self
+            .hoisted_comments
+            .partition_point(|comment| comment.start < statement_start);
+        let mut inner: Vec<Expression> = self
+            .hoisted_comments
+            .drain(hoisted..)
+            .map(|comment| comment_node(comment, false))
+            .collect();
+        while matches!(self.comments.front(), Some(comment) if comment.start < end) {
+            let comment = self.comments.pop_front().expect("a pending comment");
+            inner.push(comment_node(comment, false));
+        }
+        body.splice(statement_index..statement_index, inner);
+
+        // A comment after the `}` closing this block belongs to the outer one.
+        let next_start = self.peek(None).map_or(usize::MAX, |token| token.start);
+        if matches!(self.comments.front(),
+            Some(comment) if comment.line == self.last_line && comment.start < next_start)
+        {
+            let comment = self.comments.pop_front().expect("a pending comment");
+            body.push(comment_node(comment, true));
+        }
+    }
+
+    fn push_blank_line(&self, body: &mut Block, line: usize) {
+        if line > self.last_line + 1 {
+            body.push(Expression::Trivia(Trivia::BlankLine));
+        }
+    }
+
     fn parse_statement(&mut self) -> Result<Box<Expression>, ParserError> {
         let token = self.peek(None).ok_or_else(error_eof)?;
 
@@ -168,7 +343,7 @@ impl Parser {
         self.digest(TokenType::Assignment)?;
         let expr = self.parse_expression(0)?;
         Ok(build_assignment_node(
-            symbol.value.ok_or_else(error_eof)?,
+            symbol.symbol().ok_or_else(error_eof)?,
             expr,
         ))
     }
@@ -203,26 +378,15 @@ impl Parser {
 
             Ok(if_block)
         } else {
+            let line = self.peek(None).ok_or_else(error_eof)?.line;
             let stmt = self.parse_statement()?;
             self.consume_statement_terminator(stmt.as_ref())?;
-            Ok(vec![build_statement_node(stmt)])
+            Ok(vec![build_statement_node(stmt, line)])
         }
     }
 
     fn parse_expression(&mut self, precedence: i32) -> Result<Box<Expression>, ParserError> {
-        let token = self.peek(None).ok_or_else(error_eof)?;
-        let next = self.peek(Some(self.pos + 1));
-
-        if token.token_type == TokenType::Symbol
-            && matches!(
-                next.map(|t| t.token_type.clone()),
-                Some(TokenType::ParenthesisL)
-            )
-        {
-            self.parse_function_call()
-        } else {
-            self.parse_binary_expression(precedence)
-        }
+        self.parse_binary_expression(precedence)
     }
 
     fn parse_function_call(&mut self) -> Result<Box<Expression>, ParserError> {
@@ -232,7 +396,7 @@ impl Parser {
         self.digest(TokenType::ParenthesisR)?;
 
         Ok(build_function_call_node(
-            method_name.value.ok_or_else(error_eof)?,
+            method_name.symbol().ok_or_else(error_eof)?,
             args,
             method_name.line,
         ))
@@ -311,6 +475,15 @@ impl Parser {
                 }
             }
 
+            TokenType::Symbol
+                if matches!(
+                    self.peek(Some(self.pos + 1)).map(|t| &t.token_type),
+                    Some(TokenType::ParenthesisL)
+                ) =>
+            {
+                self.parse_function_call()
+            }
+
             TokenType::Symbol
             | TokenType::StringLiteral
             | TokenType::BooleanLiteral
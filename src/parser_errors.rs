@@ -19,7 +19,7 @@ impl fmt::Display for ParserErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ParserErrorKind::UnrecognizedToken(token) => {
-                let found = token.value.clone().unwrap_or_default();
+                let found = token.value.as_ref().map(|value| value.to_string()).unwrap_or_default();
                 write!(
                     f,
                     "Syntax error: Unrecognized token {} at line {} and character {}",
@@ -27,7 +27,7 @@ impl fmt::Display for ParserErrorKind {
                 )
             }
             ParserErrorKind::UnexpectedToken(expected, token) => {
-                let found = token.value.clone().unwrap_or_default();
+                let found = token.value.as_ref().map(|value| value.to_string()).unwrap_or_default();
                 write!(
                     f,
                     "Syntax error: Expected token {} at line {} and character {}, instead found {}",
@@ -0,0 +1,189 @@
+use std::fs;
+
+use crate::{
+    ast_dump,
+    error::Error,
+    interpreter::value::Value,
+    lexer::{TokenParser, TokenType},
+    Engine,
+};
+
+const HELP: &str = "\
+:vars        list the variables defined in this session
+:funcs       list the functions defined in this session
+:load <file> run a file in this session
+:reset       forget everything defined in this session
+:ast <expr>  show the tree an expression parses to
+:help        show this message
+:quit        leave the REPL";
+
+/// What the REPL should do after handling an input.
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    /// Nothing to show, e.g. after a `let`.
+    Empty,
+    /// Text to show on stdout.
+    Output(String),
+    /// An error to show on stderr.
+    Error(String),
+    Quit,
+}
+
+/// An interactive session: inputs are evaluated one after another in the same
+/// interpreter, so their definitions stay available until `:reset`.
+pub struct Repl {
+    engine: Engine,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self::with_engine(Engine::new())
+    }
+
+    pub fn with_engine(engine: Engine) -> Self {
+        Repl { engine }
+    }
+
+    pub fn engine_mut(&mut self) -> &mut Engine {
+        &mut self.engine
+    }
+
+    /// Whether `input` can be evaluated, or more lines are needed because a
+    /// `{`, `(` or string is still open.
+    pub fn is_complete(input: &str) -> bool {
+        let mut depth = 0i32;
+        let mut in_string = false;
+        let mut chars = input.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '"' => in_string = !in_string,
+                _ if in_string => {}
+                '/' if chars.peek() == Some(&'/') => {
+                    while chars.next_if(|ch| *ch != '\n').is_some() {}
+                }
+                '{' | '(' => depth += 1,
+                '}' | ')' => depth -= 1,
+                _ => {}
+            }
+        }
+        !in_string && depth <= 0
+    }
+
+    /// Handles a complete input: a meta-command starting with `:`, or code to
+    /// evaluate. The value of a trailing expression is shown.
+    pub fn handle(&mut self, input: &str) -> Reply {
+        let input = input.trim();
+        if input.is_empty() {
+            return Reply::Empty;
+        }
+        if let Some(command) = input.strip_prefix(':') {
+            return self.run_command(command);
+        }
+
+        match self.engine.eval(&terminated(input)) {
+            Ok(Value::Empty) => Reply::Empty,
+            Ok(value) => Reply::Output(value.repr()),
+            Err(err) => Reply::Error(format_error(&err)),
+        }
+    }
+
+    fn run_command(&mut self, command: &str) -> Reply {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+
+        match name {
+            "vars" => {
+                let mut vars: Vec<_> = self
+                    .engine
+                    .interpreter()
+                    .globals()
+                    .map(|(name, value)| format!("{} = {}", name, value.repr()))
+                    .collect();
+                vars.sort();
+                listing(vars)
+            }
+            "funcs" => {
+                let mut funcs: Vec<_> = self
+                    .engine
+                    .interpreter()
+                    .global_functions()
+                    .map(|(name, function)| {
+                        let parameters: Vec<String> = function
+                            .arguments
+                            .iter()
+                            .map(|argument| argument.name.to_string())
+                            .collect();
+                        format!("{}({})", name, parameters.join(", "))
+                    })
+                    .collect();
+                funcs.sort();
+                listing(funcs)
+            }
+            "load" if !argument.is_empty() => match fs::read_to_string(argument) {
+                Ok(source) => match self.engine.eval(&source) {
+                    Ok(_) => Reply::Output(format!("Loaded {}", argument)),
+                    Err(err) => Reply::Error(format_error(&err)),
+                },
+                Err(err) => Reply::Error(format!("Unable to read {}: {}", argument, err)),
+            },
+            "reset" => {
+                self.engine.interpreter_mut().reset();
+                Reply::Output("Session reset".to_string())
+            }
+            "ast" if !argument.is_empty() => match Engine::compile(&terminated(argument)) {
+                Ok(ast) => Reply::Output(ast_dump::to_text(&ast).trim_end().to_string()),
+                Err(err) => Reply::Error(format_error(&err)),
+            },
+            "help" => Reply::Output(HELP.to_string()),
+            "quit" | "q" => Reply::Quit,
+            "load" | "ast" => Reply::Error(format!(":{} expects an argument", name)),
+            _ => Reply::Error(format!("Unknown command :{}, see :help", name)),
+        }
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lets expressions be typed without their closing `;`, which goes right
+/// after the last token so that a trailing comment does not swallow it.
+/// `rmp -e` terminates its code the same way.
+pub(crate) fn terminated(input: &str) -> String {
+    let Ok(tokens) = TokenParser::new(input.to_string()).parse() else {
+        // Evaluating the input reports the error.
+        return input.to_string();
+    };
+    let last = tokens
+        .iter()
+        .rev()
+        .find(|token| !matches!(token.token_type, TokenType::Eof | TokenType::DocComment));
+    match last {
+        Some(token)
+            if !matches!(
+                token.token_type,
+                TokenType::EndOfstatement | TokenType::BlockEnd
+            ) =>
+        {
+            format!("{};{}", &input[..token.end], &input[token.end..])
+        }
+        _ => input.to_string(),
+    }
+}
+
+fn listing(lines: Vec<String>) -> Reply {
+    if lines.is_empty() {
+        Reply::Empty
+    } else {
+        Reply::Output(lines.join("\n"))
+    }
+}
+
+fn format_error(err: &Error) -> String {
+    err.to_string().trim_end().to_string()
+}
@@ -0,0 +1,40 @@
+//! Pointer and marker traits that switch to thread-safe variants when the
+//! `sync` feature is enabled.
+//!
+//! By default values and host functions are reference counted with `Rc` and
+//! nothing is required to be `Send`. With `sync`, `Shared` is `Arc` and host
+//! functions and I/O handles must be `Send` (and host functions `Sync`), which
+//! makes parsed programs `Send + Sync` and interpreters `Send`.
+
+#[cfg(not(feature = "sync"))]
+pub use std::rc::Rc as Shared;
+#[cfg(feature = "sync")]
+pub use std::sync::Arc as Shared;
+
+/// `Send` with the `sync` feature, implemented by every type without it.
+#[cfg(feature = "sync")]
+pub trait MaybeSend: Send {}
+#[cfg(feature = "sync")]
+impl<T: Send + ?Sized> MaybeSend for T {}
+
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// `Send + Sync` with the `sync` feature, implemented by every type without it.
+#[cfg(feature = "sync")]
+pub trait MaybeSync: Send + Sync {}
+#[cfg(feature = "sync")]
+impl<T: Send + Sync + ?Sized> MaybeSync for T {}
+
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSync {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSync for T {}
+
+/// A shared, type-erased host value, thread-safe with the `sync` feature.
+#[cfg(not(feature = "sync"))]
+pub type SharedAny = Shared<dyn std::any::Any>;
+#[cfg(feature = "sync")]
+pub type SharedAny = Shared<dyn std::any::Any + Send + Sync>;
@@ -0,0 +1,351 @@
+//! Runs the `test_*` functions of programs and reports their results, for
+//! `rmp test`.
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::Error,
+    interner::Symbol,
+    interpreter::{
+        coverage::Coverage,
+        runtime_errors::{RuntimeError, RuntimeErrorKind},
+    },
+    node::Expression,
+    shared::Shared,
+    Engine,
+};
+
+/// The name under which a file that does not lex or parse is reported.
+const COMPILE_ERROR: &str = "<compile>";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Passed,
+    /// An assertion did not hold.
+    Failed(String),
+    /// The test, or the top level of its file, raised any other error.
+    Errored(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub file: String,
+    pub name: String,
+    pub outcome: Outcome,
+    pub duration: Duration,
+    /// What the test and the top level of its file printed.
+    pub output: String,
+}
+
+/// The `test_*` functions declared at the top level of `program`, in order.
+pub fn discover(program: &Expression) -> Vec<Symbol> {
+    let Expression::Program(program) = program else {
+        return Vec::new();
+    };
+    program
+        .body
+        .iter()
+        .filter_map(|statement| match statement {
+            Expression::Statement(statement, _) => match statement.as_ref() {
+                Expression::FunctionDeclaration(function) => Some(function.identifier.name),
+                _ => None,
+            },
+            Expression::FunctionDeclaration(function) => Some(function.identifier.name),
+            _ => None,
+        })
+        .filter(|name| name.resolve().starts_with("test_"))
+        .collect()
+}
+
+/// Runs the tests of `source`, read from `file`. Each test gets an
+/// interpreter of its own, which runs the top level of the file before
+/// calling the test, so tests cannot see what the others defined.
+///
+/// A file that does not compile is reported as a single errored test.
+pub fn run_file(file: &str, source: &str) -> Vec<TestResult> {
+    run_tests(file, source, None)
+}
+
+/// `run_file`, adding the lines and branches each test ran to `coverage`.
+pub fn run_file_with_coverage(
+    file: &str,
+    source: &str,
+    coverage: &mut Coverage,
+) -> Vec<TestResult> {
+    run_tests(file, source, Some(coverage))
+}
+
+fn run_tests(file: &str, source: &str, mut coverage: Option<&mut Coverage>) -> Vec<TestResult> {
+    let tests = match Engine::compile(source) {
+        Ok(program) => discover(&program),
+        Err(err) => {
+            return vec![TestResult {
+                file: file.to_string(),
+                name: COMPILE_ERROR.to_string(),
+                outcome: Outcome::Errored(err.to_string().trim_end().to_string()),
+                duration: Duration::ZERO,
+                output: String::new(),
+            }]
+        }
+    };
+
+    tests
+        .into_iter()
+        .map(|test| run_test(file, source, test, coverage.as_deref_mut()))
+        .collect()
+}
+
+fn run_test(
+    file: &str,
+    source: &str,
+    test: Symbol,
+    coverage: Option<&mut Coverage>,
+) -> TestResult {
+    let output = CapturedOutput::default();
+    let mut engine = Engine::new();
+    let interpreter = engine.interpreter_mut();
+    interpreter.set_input(Box::new(io::empty()));
+    interpreter.set_output(Box::new(output.clone()));
+    interpreter.set_error_output(Box::new(output.clone()));
+    if coverage.is_some() {
+        interpreter.enable_coverage();
+    }
+
+    let started = Instant::now();
+    let result = engine.eval(source).and_then(|_| {
+        engine
+            .interpreter_mut()
+            .call(&test.resolve(), &[])
+            .map_err(Error::Runtime)
+    });
+    let duration = started.elapsed();
+    if let (Some(coverage), Some(covered)) = (coverage, engine.interpreter().coverage()) {
+        coverage.merge(covered);
+    }
+
+    let outcome = match result {
+        Ok(_) => Outcome::Passed,
+        Err(Error::Runtime(err)) if err.kind == RuntimeErrorKind::AssertionFailed => {
+            Outcome::Failed(describe(&err))
+        }
+        Err(Error::Runtime(err)) => Outcome::Errored(describe(&err)),
+        Err(err) => Outcome::Errored(err.to_string().trim_end().to_string()),
+    };
+
+    TestResult {
+        file: file.to_string(),
+        name: test.to_string(),
+        outcome,
+        duration,
+        output: output.contents(),
+    }
+}
+
+/// The message of `err`, with the line it was raised on when known.
+fn describe(err: &RuntimeError) -> String {
+    match err.stack.last().and_then(|frame| frame.location) {
+        Some(line) => format!("{} (line {})", err.message, line),
+        None => err.message.clone(),
+    }
+}
+
+/// Collects what a test prints. Shared with the interpreter, which owns the
+/// writers it is given.
+#[derive(Clone, Default)]
+struct CapturedOutput(Shared<Mutex<Vec<u8>>>);
+
+impl CapturedOutput {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Counts of passed, failed and errored tests.
+fn tally<'a>(results: impl IntoIterator<Item = &'a TestResult>) -> (usize, usize, usize) {
+    results
+        .into_iter()
+        .fold((0, 0, 0), |(passed, failed, errored), result| match result.outcome {
+            Outcome::Passed => (passed + 1, failed, errored),
+            Outcome::Failed(_) => (passed, failed + 1, errored),
+            Outcome::Errored(_) => (passed, failed, errored + 1),
+        })
+}
+
+/// One line per test, with the message and output of those that did not
+/// pass, then a summary:
+///
+/// ```text
+/// test math.rmp::test_add ... ok
+/// test math.rmp::test_half ... FAILED
+///     Assertion failed: expected 2, got 3 (line 8)
+///
+/// 2 tests, 1 passed, 1 failed, 0 errored
+/// ```
+pub fn human_report(results: &[TestResult]) -> String {
+    let mut report = String::new();
+    for result in results {
+        let (status, message) = match &result.outcome {
+            Outcome::Passed => ("ok", None),
+            Outcome::Failed(message) => ("FAILED", Some(message)),
+            Outcome::Errored(message) => ("ERROR", Some(message)),
+        };
+        let _ = writeln!(report, "test {}::{} ... {}", result.file, result.name, status);
+        if let Some(message) = message {
+            for line in message.lines() {
+                let _ = writeln!(report, "    {}", line);
+            }
+            for line in result.output.lines() {
+                let _ = writeln!(report, "    | {}", line);
+            }
+        }
+    }
+
+    let (passed, failed, errored) = tally(results);
+    let _ = writeln!(
+        report,
+        "\n{} {}, {} passed, {} failed, {} errored",
+        results.len(),
+        if results.len() == 1 { "test" } else { "tests" },
+        passed,
+        failed,
+        errored
+    );
+    report
+}
+
+/// The Test Anything Protocol, version 13. Tests that did not pass carry
+/// their message and output in a YAML block.
+pub fn tap_report(results: &[TestResult]) -> String {
+    let mut report = format!("TAP version 13\n1..{}\n", results.len());
+    for (index, result) in results.iter().enumerate() {
+        let (status, details) = match &result.outcome {
+            Outcome::Passed => ("ok", None),
+            Outcome::Failed(message) => ("not ok", Some(("fail", message))),
+            Outcome::Errored(message) => ("not ok", Some(("error", message))),
+        };
+        let _ = writeln!(
+            report,
+            "{} {} - {}::{}",
+            status,
+            index + 1,
+            result.file,
+            result.name
+        );
+        if let Some((severity, message)) = details {
+            let _ = writeln!(report, "  ---");
+            let _ = writeln!(report, "  message: {}", serde_json::json!(message));
+            let _ = writeln!(report, "  severity: {}", severity);
+            if !result.output.is_empty() {
+                let _ = writeln!(report, "  output: {}", serde_json::json!(result.output));
+            }
+            let _ = writeln!(report, "  ...");
+        }
+    }
+    report
+}
+
+/// JUnit XML, with a test suite per file and the output of every test.
+pub fn junit_report(results: &[TestResult]) -> String {
+    let (_, failed, errored) = tally(results);
+    let total: Duration = results.iter().map(|result| result.duration).sum();
+    let mut report = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        report,
+        "<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">",
+        results.len(),
+        failed,
+        errored,
+        total.as_secs_f64()
+    );
+
+    let mut suites: Vec<(&str, Vec<&TestResult>)> = Vec::new();
+    for result in results {
+        match suites.last_mut() {
+            Some((file, tests)) if *file == result.file => tests.push(result),
+            _ => suites.push((&result.file, vec![result])),
+        }
+    }
+
+    for (file, tests) in suites {
+        let (_, failed, errored) = tally(tests.iter().copied());
+        let time: Duration = tests.iter().map(|test| test.duration).sum();
+        let _ = writeln!(
+            report,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">",
+            escape_xml(file),
+            tests.len(),
+            failed,
+            errored,
+            time.as_secs_f64()
+        );
+        for test in tests {
+            let _ = write!(
+                report,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape_xml(&test.name),
+                escape_xml(file),
+                test.duration.as_secs_f64()
+            );
+            let failure = match &test.outcome {
+                Outcome::Passed => None,
+                Outcome::Failed(message) => Some(("failure", message)),
+                Outcome::Errored(message) => Some(("error", message)),
+            };
+            if failure.is_none() && test.output.is_empty() {
+                report.push_str("/>\n");
+                continue;
+            }
+            report.push_str(">\n");
+            if let Some((element, message)) = failure {
+                let _ = writeln!(
+                    report,
+                    "      <{} message=\"{}\"/>",
+                    element,
+                    escape_xml(message)
+                );
+            }
+            if !test.output.is_empty() {
+                let _ = writeln!(
+                    report,
+                    "      <system-out>{}</system-out>",
+                    escape_xml(&test.output)
+                );
+            }
+            report.push_str("    </testcase>\n");
+        }
+        report.push_str("  </testsuite>\n");
+    }
+    report.push_str("</testsuites>\n");
+    report
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,455 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn rmp(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rmp"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Commands that fail on their arguments exit without reading stdin.
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn run_reads_stdin_and_passes_script_arguments() {
+    let program = "println(arg_count() + \" \" + arg(0) + arg(1));";
+
+    let output = rmp(&["run", "-", "a", "b"], program);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "2 ab\n");
+
+    // A bare file argument is short for `run`.
+    let output = rmp(&["-", "--profile"], program);
+    assert_eq!(stdout(&output), "1 --profile\n");
+}
+
+#[test]
+fn eval_prints_the_value_of_the_code() {
+    let output = rmp(&["-e", "1 + 2 * 3"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "7\n");
+
+    let output = rmp(&["-e", "let x = 1;"], "");
+    assert_eq!(stdout(&output), "");
+
+    let output = rmp(&["-e", "1 + 2 // sum"], "");
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[test]
+fn exit_codes_tell_failures_apart() {
+    assert_eq!(rmp(&[], "").status.code(), Some(1));
+    assert_eq!(rmp(&["run", "missing.rmp"], "").status.code(), Some(1));
+    assert_eq!(rmp(&["run", "-"], "let x = $;").status.code(), Some(2));
+    assert_eq!(rmp(&["run", "-"], "let = 1;").status.code(), Some(3));
+
+    let output = rmp(&["run", "-"], "missing();");
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr(&output).contains("Method not found: missing"));
+}
+
+#[test]
+fn check_reports_problems_without_running() {
+    let output = rmp(&["check", "-"], "println(\"ran\");");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+
+    let program = "\
+func add(a, b) { return a + b; }
+add(1);
+nope(arg(0));
+return 1;
+";
+    let output = rmp(&["check", "-"], program);
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(
+        stderr(&output),
+        "line 2: Function 'add' expects 2 arguments, called with 1\n\
+         line 3: Call to undefined function 'nope'\n\
+         'return' outside a function\n"
+    );
+
+    assert_eq!(rmp(&["check", "-"], "let = 1;").status.code(), Some(3));
+}
+
+#[test]
+fn tokens_lists_the_token_stream() {
+    let output = rmp(&["tokens", "-"], "let x = 1 + 2;");
+    assert_eq!(
+        stdout(&output),
+        "1:0..3 Declaration \"let\"\n\
+         1:4..5 Symbol \"x\"\n\
+         1:6..7 Assignment \"=\"\n\
+         1:8..9 NumeralLiteral \"1\"\n\
+         1:10..11 Operator +\n\
+         1:12..13 NumeralLiteral \"2\"\n\
+         1:13..14 EndOfStatement \";\"\n\
+         1:14..14 Eof\n"
+    );
+}
+
+#[test]
+fn ast_renders_text_json_and_dot() {
+    let program = "let x = -f(1);";
+
+    let output = rmp(&["ast", "-"], program);
+    assert_eq!(
+        stdout(&output),
+        "Program\n  Statement\n    Declaration x\n      UnaryOperation -\n        \
+         FunctionCall f (line 1)\n          Literal 1\n"
+    );
+
+    let output = rmp(&["ast", "--format", "json", "-"], program);
+    assert_eq!(
+        stdout(&output),
+        concat!(
+            r#"{"body":[{"expression":{"name":"x","type":"Declaration","value":"#,
+            r#"{"operand":{"arguments":[{"type":"Literal","value":1}],"line":1,"name":"f","#,
+            r#""type":"FunctionCall"},"operator":"-","type":"UnaryOperation"}},"#,
+            r#""type":"Statement"}],"type":"Program"}"#,
+            "\n"
+        )
+    );
+
+    let output = rmp(&["ast", "--format", "dot", "-"], "f(1);");
+    assert_eq!(
+        stdout(&output),
+        "digraph ast {\n  node [shape=box];\n  n0 [label=\"Program\"];\n  \
+         n1 [label=\"Statement\"];\n  n2 [label=\"FunctionCall f (line 1)\"];\n  \
+         n3 [label=\"Literal 1\"];\n  n2 -> n3;\n  n1 -> n2;\n  n0 -> n1;\n}\n"
+    );
+
+    assert_eq!(
+        rmp(&["ast", "--format", "yaml", "-"], program).status.code(),
+        Some(1)
+    );
+}
+
+#[test]
+fn tokens_and_ast_stop_quietly_when_the_reader_does() {
+    let program = "let x = 1;\n".repeat(20_000);
+    for command in ["tokens", "ast"] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rmp"))
+            .args([command, "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // Closed before anything is written, like `rmp tokens - | head -0`.
+        drop(child.stdout.take());
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(program.as_bytes())
+            .unwrap();
+
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(output.status.code(), Some(0), "{}", command);
+        assert_eq!(stderr(&output), "", "{}", command);
+    }
+}
+
+#[test]
+fn fmt_prints_stdin_and_checks_files() {
+    let output = rmp(&["fmt", "-"], "let  x=1 ; // one");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "let x = 1; // one\n");
+
+    let dir = std::env::temp_dir().join(format!("rmp_fmt_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("messy.rmp");
+    std::fs::write(&path, "func f(a){return a;}").unwrap();
+    let path = path.to_str().unwrap();
+
+    let output = rmp(&["fmt", "--check", path], "");
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(stderr(&output), format!("{} is not formatted\n", path));
+
+    assert_eq!(rmp(&["fmt", path], "").status.code(), Some(0));
+    assert_eq!(
+        std::fs::read_to_string(path).unwrap(),
+        "func f(a) {\n    return a;\n}\n"
+    );
+    assert_eq!(rmp(&["fmt", "--check", path], "").status.code(), Some(0));
+
+    assert_eq!(rmp(&["fmt", "-"], "let = 1;").status.code(), Some(3));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn debug_pauses_and_reads_commands_from_stdin() {
+    let path = std::env::temp_dir().join(format!("rmp_debug_test_{}.rmp", std::process::id()));
+    std::fs::write(&path, "let x = 2;\nfunc f(a) {\n    return a * x;\n}\nprintln(f(3));\n").unwrap();
+    let path = path.to_str().unwrap();
+
+    let output = rmp(&["debug", "--break", "3", path], "bt\nv\np x\nc\n");
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "Paused at line 3 (breakpoint)\n   3 |     return a * x;\n\
+         (rmp) #0 f (line 3)\n#1 <program> (line 5)\n\
+         (rmp) Arguments:\n  a = 3\n\
+         (rmp) x = 2\n\
+         (rmp) 6\nProgram finished\n"
+    );
+}
+
+#[test]
+fn run_traces_to_stderr() {
+    let program = "func f(a) {\n    return a + 1;\n}\nprintln(f(1));\n";
+
+    let output = rmp(&["run", "--trace-function", "f", "-"], program);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "2\n");
+    assert_eq!(
+        stderr(&output),
+        "call f(1)\n  line 2: return a + 1;\nreturn f = 2\n"
+    );
+}
+
+#[test]
+fn test_runs_test_functions_and_reports_results() {
+    let dir = std::env::temp_dir().join(format!("rmp_test_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("math.rmp"),
+        "func half(n) {\n    return n / 2;\n}\n\
+         func test_half() {\n    assert_eq(half(8), 4);\n}\n\
+         func test_odd() {\n    println(\"halving 3\");\n    assert_eq(half(3), 1, \"odd\");\n}\n\
+         func test_missing() {\n    return missing;\n}\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("notes.txt"), "func test_ignored() {}").unwrap();
+    let dir_path = dir.to_str().unwrap();
+    let file = dir.join("math.rmp");
+    let file = file.to_str().unwrap();
+
+    let output = rmp(&["test", dir_path], "");
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "test {file}::test_half ... ok\n\
+             test {file}::test_odd ... FAILED\n    \
+             Assertion failed: odd: expected 1, got 1.5 (line 9)\n    \
+             | halving 3\n\
+             test {file}::test_missing ... ERROR\n    \
+             Undefined variable missing\n\
+             \n3 tests, 1 passed, 1 failed, 1 errored\n"
+        )
+    );
+
+    let output = rmp(&["test", "--format", "tap", file], "");
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "TAP version 13\n1..3\nok 1 - {file}::test_half\n\
+             not ok 2 - {file}::test_odd\n  ---\n  \
+             message: \"Assertion failed: odd: expected 1, got 1.5 (line 9)\"\n  \
+             severity: fail\n  output: \"halving 3\\n\"\n  ...\n\
+             not ok 3 - {file}::test_missing\n  ---\n  \
+             message: \"Undefined variable missing\"\n  severity: error\n  ...\n"
+        )
+    );
+
+    let output = rmp(&["test", "--format", "junit", file], "");
+    let report = stdout(&output);
+    assert!(report.starts_with("<?xml"));
+    assert!(report.contains("<testsuites tests=\"3\" failures=\"1\" errors=\"1\""));
+    assert!(report.contains(
+        "<failure message=\"Assertion failed: odd: expected 1, got 1.5 (line 9)\"/>"
+    ));
+    assert!(report.contains("<system-out>halving 3\n</system-out>"));
+
+    std::fs::write(dir.join("math.rmp"), "func test_one() {\n    assert(\"one\", true);\n}\n")
+        .unwrap();
+    assert_eq!(rmp(&["test", dir_path], "").status.code(), Some(0));
+
+    let lcov = dir.join("coverage.lcov");
+    let output = rmp(&["test", "--lcov", lcov.to_str().unwrap(), file], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        std::fs::read_to_string(&lcov).unwrap(),
+        format!("TN:\nSF:{file}\nBRF:0\nBRH:0\nDA:1,1\nDA:2,1\nLF:2\nLH:2\nend_of_record\n")
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn run_reports_coverage_to_stderr() {
+    let program = "let x = 1;\nif (x > 1) {\n    println(x);\n}\n";
+
+    let output = rmp(&["run", "--coverage", "-"], program);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stderr(&output),
+        "\n      1     1 | let x = 1;\n      \
+         1     2 | if (x > 1) {  [then 0, else 1]\n  \
+         #####     3 |     println(x);\n      \
+         -     4 | }\n\
+         \nLines: 2 of 3 (66.7%)\nBranches: 1 of 2 (50.0%)\n"
+    );
+}
+
+#[test]
+fn doc_renders_markdown_and_html() {
+    let program = "/// Halves `n`.\n///\n/// ```\n/// half(8); // 4\n/// ```\n\
+                   func half(n) {\n    return n / 2;\n}\n";
+
+    let output = rmp(&["doc", "-"], program);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "# Functions\n\n## half\n\n```rmp\nhalf(n)\n```\n\nHalves `n`.\n\n\
+         ### Examples\n\n```rmp\nhalf(8); // 4\n```\n"
+    );
+
+    let output = rmp(&["doc", "--format", "html", "-"], program);
+    let page = stdout(&output);
+    assert!(page.contains("<h2 id=\"half\">half</h2>\n<pre><code>half(n)</code></pre>\n"));
+    assert!(page.contains("<p>Halves <code>n</code>.</p>"));
+
+    let output = rmp(&["doc", "--builtins"], "");
+    assert!(stdout(&output).contains("## to_number\n\n```rmp\nto_number(value)\n```\n"));
+}
+
+#[test]
+fn run_watch_reruns_the_program_when_it_changes() {
+    use std::{
+        io::{BufRead, BufReader, Read},
+        sync::mpsc,
+        time::Duration,
+    };
+
+    let path = std::env::temp_dir().join(format!("rmp_watch_test_{}.rmp", std::process::id()));
+    std::fs::write(&path, "println(1);\n").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rmp"))
+        .args(["run", "--watch", path.to_str().unwrap()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let (lines, received) = mpsc::channel();
+    let stderr = BufReader::new(child.stderr.take().unwrap());
+    std::thread::spawn(move || {
+        for line in stderr.lines().map_while(Result::ok) {
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    // Reads stderr up to the status line printed after each run.
+    let next_run = || {
+        let mut run = Vec::new();
+        loop {
+            let line = received
+                .recv_timeout(Duration::from_secs(10))
+                .expect("the program should run again");
+            if line.starts_with('[') {
+                run.push(line);
+                return run;
+            }
+            if !line.is_empty() {
+                run.push(line);
+            }
+        }
+    };
+
+    let status = |outcome: &str| {
+        format!("[{}] Watching {} for changes, Ctrl-C to stop", outcome, path.display())
+    };
+    assert_eq!(next_run(), vec![status("finished")]);
+
+    std::fs::write(&path, "let = 1;\n").unwrap();
+    let run = next_run();
+    assert!(run[0].starts_with("Parsing error:"), "{:?}", run);
+    assert_eq!(run.last(), Some(&status("failed with exit code 3")));
+
+    // A path that cannot be read for a while is waited out.
+    std::fs::remove_file(&path).unwrap();
+    std::fs::create_dir(&path).unwrap();
+    std::thread::sleep(Duration::from_millis(600));
+    std::fs::remove_dir(&path).unwrap();
+    std::fs::write(&path, "println(2);\n").unwrap();
+    assert_eq!(next_run(), vec![status("finished")]);
+
+    child.kill().unwrap();
+    let mut output = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut output).unwrap();
+    child.wait().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    // Output that is not a terminal is not cleared between runs.
+    assert_eq!(output, "1\n2\n");
+}
+
+#[test]
+fn run_watch_interrupts_a_run_when_the_program_changes() {
+    use std::{
+        io::{BufRead, BufReader, Read},
+        sync::mpsc,
+        time::Duration,
+    };
+
+    let path = std::env::temp_dir().join(format!("rmp_watch_loop_{}.rmp", std::process::id()));
+    let endless = "println(1);\nfunc spin(n) { return spin(n + 1); }\nspin(0);\n";
+    std::fs::write(&path, endless).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rmp"))
+        .args(["run", "--watch", path.to_str().unwrap()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "1\n");
+
+    let (lines, received) = mpsc::channel();
+    let stderr = BufReader::new(child.stderr.take().unwrap());
+    std::thread::spawn(move || {
+        for line in stderr.lines().map_while(Result::ok) {
+            if !line.is_empty() && lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    // The endless run never prints a status line; the change stops it.
+    std::thread::sleep(Duration::from_millis(300));
+    std::fs::write(&path, "println(2);\n").unwrap();
+    let status = received
+        .recv_timeout(Duration::from_secs(10))
+        .expect("the changed program should run");
+    assert_eq!(
+        status,
+        format!("[finished] Watching {} for changes, Ctrl-C to stop", path.display())
+    );
+
+    child.kill().unwrap();
+    let mut output = String::new();
+    stdout.read_to_string(&mut output).unwrap();
+    child.wait().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output, "2\n");
+}
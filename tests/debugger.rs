@@ -0,0 +1,282 @@
+mod framing;
+
+use framing::{frame, unframe};
+use std::{
+    collections::VecDeque,
+    io::{self, Cursor, Write},
+    sync::{Arc, Mutex},
+};
+
+use parser::{
+    dap,
+    interpreter::{
+        debugger::{DebugCommand, DebugFrontend, Debugger, Pause},
+        runtime_errors::RuntimeErrorKind,
+    },
+    Engine, Error,
+};
+use serde_json::{json, Value};
+
+const PROGRAM: &str = "\
+let base = 10;
+func scale(n) {
+    let doubled = n * 2;
+    return doubled + base;
+}
+let result = scale(4);
+println(result);
+";
+
+/// Answers pauses with a fixed list of commands, recording where each pause
+/// happened and what the frames and scopes looked like.
+struct Script {
+    commands: VecDeque<DebugCommand>,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl DebugFrontend for Script {
+    fn paused(&mut self, pause: &mut Pause<'_>) -> DebugCommand {
+        let frames: Vec<String> = pause
+            .frames()
+            .iter()
+            .map(|frame| {
+                let scopes: Vec<String> = pause
+                    .scopes(frame)
+                    .iter()
+                    .map(|scope| {
+                        let variables: Vec<String> = scope
+                            .variables
+                            .iter()
+                            .map(|(name, value)| format!("{}={:?}", name, value))
+                            .collect();
+                        format!("{}[{}]", scope.name, variables.join(","))
+                    })
+                    .collect();
+                let function = frame.function.map_or("<program>".to_string(), |f| f.to_string());
+                format!("{}@{} {}", function, frame.line.unwrap_or(0), scopes.join(" "))
+            })
+            .collect();
+        self.log.lock().unwrap().push(format!(
+            "{:?} line {}: {}",
+            pause.reason(),
+            pause.line(),
+            frames.join(" | ")
+        ));
+        self.commands.pop_front().unwrap_or(DebugCommand::Continue)
+    }
+}
+
+fn debug(debugger: impl FnOnce(Script) -> Debugger, commands: &[DebugCommand]) -> (Vec<String>, Result<(), Error>) {
+    debug_program(PROGRAM, debugger, commands)
+}
+
+fn debug_program(
+    program: &str,
+    debugger: impl FnOnce(Script) -> Debugger,
+    commands: &[DebugCommand],
+) -> (Vec<String>, Result<(), Error>) {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let script = Script {
+        commands: commands.iter().copied().collect(),
+        log: log.clone(),
+    };
+    let mut engine = Engine::new();
+    engine.interpreter_mut().set_output(Box::new(io::sink()));
+    engine.interpreter_mut().attach_debugger(debugger(script));
+    let result = engine.eval(program).map(|_| ());
+    let log = log.lock().unwrap().clone();
+    (log, result)
+}
+
+#[test]
+fn pauses_at_breakpoints_with_frames_and_scopes() {
+    let (log, result) = debug(
+        |script| {
+            let mut debugger = Debugger::new(script);
+            debugger.set_breakpoint(3);
+            debugger
+        },
+        &[],
+    );
+    assert!(result.is_ok());
+    assert_eq!(
+        log,
+        ["Breakpoint line 3: scale@3 Locals[] Arguments[n=Integer(4)] | <program>@6 Script[base=Integer(10)] Globals[]"]
+    );
+}
+
+#[test]
+fn steps_into_over_and_out_of_calls() {
+    use DebugCommand::*;
+
+    let lines = |log: Vec<String>| -> Vec<String> {
+        log.iter()
+            .map(|entry| entry.split(':').next().unwrap().to_string())
+            .collect()
+    };
+
+    let (log, _) = debug(|script| Debugger::new(script).stop_on_entry(), &[StepOver, StepOver, StepOver]);
+    assert_eq!(lines(log), ["Entry line 1", "Step line 2", "Step line 6", "Step line 7"]);
+
+    let (log, _) = debug(
+        |script| Debugger::new(script).stop_on_entry(),
+        &[StepInto, StepInto, StepInto, StepOut],
+    );
+    assert_eq!(
+        lines(log),
+        ["Entry line 1", "Step line 2", "Step line 6", "Step line 3", "Step line 7"]
+    );
+}
+
+#[test]
+fn tail_calls_keep_the_parameters_apart_from_inherited_definitions() {
+    let program = "\
+func scale(n) {
+    return n * factor;
+}
+func apply(n) {
+    let factor = 3;
+    return scale(n);
+}
+apply(2);
+";
+    let (log, result) = debug_program(
+        program,
+        |script| {
+            let mut debugger = Debugger::new(script);
+            debugger.set_breakpoint(2);
+            debugger
+        },
+        &[],
+    );
+    assert!(result.is_ok());
+    assert_eq!(
+        log,
+        ["Breakpoint line 2: scale@2 Locals[] Arguments[n=Integer(2)] Block[factor=Integer(3)] | <program>@6 Script[] Globals[]"]
+    );
+}
+
+#[test]
+fn steps_through_each_run_of_a_one_line_tail_call() {
+    let program = "\
+func count(n) { if (n > 0) { return count(n - 1); } }
+count(2);
+";
+    let (log, result) = debug_program(
+        program,
+        |script| {
+            let mut debugger = Debugger::new(script);
+            debugger.set_breakpoint(1);
+            debugger
+        },
+        &[],
+    );
+    assert!(result.is_ok());
+    let lines: Vec<&str> = log.iter().map(|entry| entry.split(':').next().unwrap()).collect();
+    assert_eq!(
+        lines,
+        ["Breakpoint line 1", "Breakpoint line 1", "Breakpoint line 1", "Breakpoint line 1"]
+    );
+}
+
+#[test]
+fn stopping_ends_the_run() {
+    let (log, result) = debug(|script| Debugger::new(script).stop_on_entry(), &[DebugCommand::Stop]);
+    assert_eq!(log.len(), 1);
+    assert!(matches!(
+        result,
+        Err(Error::Runtime(err)) if err.kind == RuntimeErrorKind::Interrupted
+    ));
+}
+
+/// A writer whose bytes can still be read after it was handed to `serve`.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Numbers `commands` as the requests of a client.
+fn requests(commands: &[Value]) -> Vec<Value> {
+    let mut requests = commands.to_vec();
+    for (seq, request) in requests.iter_mut().enumerate() {
+        request["seq"] = json!(seq + 1);
+        request["type"] = json!("request");
+    }
+    requests
+}
+
+#[test]
+fn serves_a_debug_session_over_dap() {
+    let path = std::env::temp_dir().join(format!("rmp_dap_test_{}.rmp", std::process::id()));
+    std::fs::write(&path, PROGRAM).unwrap();
+    let program = path.to_string_lossy().into_owned();
+
+    let input = frame(&requests(&[
+        json!({"command": "initialize", "arguments": {"adapterID": "rmp"}}),
+        json!({"command": "launch", "arguments": {"program": program}}),
+        json!({"command": "setBreakpoints", "arguments": {
+            "source": {"path": program}, "breakpoints": [{"line": 3}]
+        }}),
+        json!({"command": "configurationDone"}),
+        json!({"command": "stackTrace", "arguments": {"threadId": 1}}),
+        json!({"command": "scopes", "arguments": {"frameId": 1}}),
+        json!({"command": "variables", "arguments": {"variablesReference": 2}}),
+        json!({"command": "evaluate", "arguments": {"expression": "base"}}),
+        json!({"command": "continue", "arguments": {"threadId": 1}}),
+        json!({"command": "disconnect"}),
+    ]));
+    let output = SharedBuffer::default();
+
+    let code = dap::serve(Cursor::new(input), output.clone()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(code, 0);
+
+    let messages = unframe(&output.0.lock().unwrap());
+    let find = |kind: &str, name: &str| -> Value {
+        let field = if kind == "event" { "event" } else { "command" };
+        messages
+            .iter()
+            .find(|message| message["type"] == kind && message[field] == name)
+            .unwrap_or_else(|| panic!("no {} {}", kind, name))
+            .clone()
+    };
+
+    assert_eq!(find("event", "initialized")["body"], json!({}));
+    assert_eq!(
+        find("response", "setBreakpoints")["body"]["breakpoints"],
+        json!([{"verified": true, "line": 3}])
+    );
+    assert_eq!(find("event", "stopped")["body"]["reason"], "breakpoint");
+
+    let stack = find("response", "stackTrace");
+    let names: Vec<&Value> = stack["body"]["stackFrames"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|frame| &frame["name"])
+        .collect();
+    assert_eq!(names, [&json!("scale"), &json!("<program>")]);
+    assert_eq!(stack["body"]["stackFrames"][0]["line"], 3);
+
+    let scopes = find("response", "scopes");
+    assert_eq!(scopes["body"]["scopes"][1]["name"], "Arguments");
+    assert_eq!(
+        find("response", "variables")["body"]["variables"],
+        json!([{"name": "n", "value": "4", "type": "integer", "variablesReference": 0}])
+    );
+    assert_eq!(find("response", "evaluate")["body"]["result"], "10");
+
+    assert_eq!(find("event", "output")["body"]["output"], "18\n");
+    assert_eq!(find("event", "exited")["body"]["exitCode"], 0);
+    find("event", "terminated");
+    assert_eq!(find("response", "disconnect")["success"], true);
+}
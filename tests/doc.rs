@@ -0,0 +1,49 @@
+use parser::{
+    doc::{program_docs, FunctionDoc},
+    interpreter::value::Value,
+    Engine,
+};
+
+#[test]
+fn doc_comments_document_the_function_below_them() {
+    let program = Engine::compile(
+        "/// Not attached: a statement follows.\n\
+         let unit = 2;\n\
+         /// Scales `n`.\n\
+         ///\n\
+         /// ```\n\
+         /// scale(3); // 6\n\
+         /// ```\n\
+         func scale(n) {\n    return n * unit;\n}\n\
+         // A plain comment.\n\
+         func plain() {\n    return 1;\n}\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        program_docs(&program),
+        vec![
+            FunctionDoc {
+                name: "scale".to_string(),
+                signature: "scale(n)".to_string(),
+                description: "Scales `n`.".to_string(),
+                examples: vec!["scale(3); // 6".to_string()],
+            },
+            FunctionDoc {
+                name: "plain".to_string(),
+                signature: "plain()".to_string(),
+                description: String::new(),
+                examples: Vec::new(),
+            },
+        ]
+    );
+
+    // Doc comments do not change what the program does.
+    let mut engine = Engine::new();
+    assert_eq!(
+        engine
+            .eval("/// Doubles.\nfunc double(n) { return n * 2; }\ndouble(4);")
+            .unwrap(),
+        Value::Integer(8)
+    );
+}
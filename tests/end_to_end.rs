@@ -1,23 +1,51 @@
 mod harness;
 
-use harness::{reset_assertions, take_assertions, AssertionRecord};
+use harness::{register_assert, AssertionRecord, CapturedOutput};
+use std::io::Cursor;
 use parser::{
-    interpreter::{Interpreter, runtime_errors::RuntimeError},
-    lexer, parser as ast_parser,
+    interpreter::{
+        config::InterpreterConfig,
+        methods::{NativeFnArgs, NativeFnReturn},
+        tracer::Tracer,
+        value::Value,
+        runtime_errors::{RuntimeError, RuntimeErrorKind},
+        Interpreter,
+    },
+    node::Expression,
+    register_method, register_typed_method, takes_arguments, Engine,
 };
 
-fn run_source(source: &str) -> (Result<(), RuntimeError>, Vec<AssertionRecord>) {
-    reset_assertions();
+fn fn_simple_double(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
+    let (value,) = takes_arguments!(args, 1)?;
+    value.mul_value(&Value::Integer(2)).map(Value::into_rc)
+}
 
-    let mut token_parser = lexer::TokenParser::new(source.to_string());
-    let tokens = token_parser.parse().expect("lexer should succeed");
+register_method!("simple_double", fn_simple_double);
+
+fn typed_repeat(text: &str, times: i64, separator: Option<&str>) -> String {
+    vec![text; times.max(0) as usize].join(separator.unwrap_or(""))
+}
 
-    let mut parser = ast_parser::Parser::new(tokens);
-    let ast = parser.parse().expect("parser should succeed");
+register_typed_method!("typed_repeat", typed_repeat);
 
-    let mut interpreter = Interpreter::new();
+fn parse_source(source: &str) -> Box<Expression> {
+    Engine::compile(source).expect("source should lex and parse")
+}
+
+fn run_source(source: &str) -> (Result<(), RuntimeError>, Vec<AssertionRecord>) {
+    run_source_with_config(source, InterpreterConfig::default())
+}
+
+fn run_source_with_config(
+    source: &str,
+    config: InterpreterConfig,
+) -> (Result<(), RuntimeError>, Vec<AssertionRecord>) {
+    let ast = parse_source(source);
+
+    let mut interpreter = Interpreter::with_config(config);
+    let log = register_assert(&mut interpreter);
     let result = interpreter.run(Some(ast.as_ref()));
-    let assertions = take_assertions();
+    let assertions = std::mem::take(&mut *log.lock().unwrap());
 
     (result, assertions)
 }
@@ -330,4 +358,1289 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn returns_inside_if_blocks_leave_the_function() {
+        let source = r#"
+        func sign(n) {
+            if (n < 0) return -1;
+            if (n == 0) {
+                if (true) {
+                    return 0;
+                }
+            }
+            return 1;
+        }
+
+        func classify(n) {
+            if (n > 10) {
+                return "big";
+            } else {
+                return "small";
+            }
+            assert("Statement after if/else return not executed", false);
+        }
+
+        let negative = sign(-5);
+        let zero = sign(0);
+        let positive = sign(3);
+        let small = classify(2);
+        assert("return inside if leaves function", negative == -1);
+        assert("return inside nested if leaves function", zero == 0);
+        assert("falls through when no branch returns", positive == 1);
+        assert("return inside else leaves function", small == "small");
+        "#;
+
+        expect_assertions(
+            source,
+            &[
+                "return inside if leaves function",
+                "return inside nested if leaves function",
+                "falls through when no branch returns",
+                "return inside else leaves function",
+            ],
+        );
+    }
+
+    #[test]
+    fn function_calls_are_binary_operands() {
+        let source = r#"
+        func double(n) {
+            return n * 2;
+        }
+
+        assert("call as left operand", (double(2) + 1) == 5);
+        assert("call as right operand", (1 + double(2)) == 5);
+        assert("calls on both sides", (double(1) * double(3)) == 12);
+        assert("call compared to string", str_concat("a", "b") == "ab");
+        "#;
+
+        expect_assertions(
+            source,
+            &[
+                "call as left operand",
+                "call as right operand",
+                "calls on both sides",
+                "call compared to string",
+            ],
+        );
+    }
+
+    #[test]
+    fn executes_recursion() {
+        let source = r#"
+        func factorial(n) {
+            if (n <= 1) {
+                return 1;
+            }
+            return n * factorial(n - 1);
+        }
+
+        func fibonacci(n) {
+            if (n < 2) {
+                return n;
+            }
+            return fibonacci(n - 1) + fibonacci(n - 2);
+        }
+
+        assert("recursive factorial", factorial(10) == 3628800);
+        assert("tree recursion", fibonacci(15) == 610);
+        "#;
+
+        expect_assertions(source, &["recursive factorial", "tree recursion"]);
+    }
+
+    #[test]
+    fn tail_calls_run_in_constant_stack() {
+        let source = r#"
+        func count_down(n, acc) {
+            if (n == 0) {
+                return acc;
+            }
+            return count_down(n - 1, acc + 1);
+        }
+
+        func is_even(n) {
+            if (n == 0) return true;
+            return is_odd(n - 1);
+        }
+
+        func is_odd(n) {
+            if (n == 0) return false;
+            return is_even(n - 1);
+        }
+
+        assert("self tail call", count_down(20000, 0) == 20000);
+        assert("mutual tail calls", is_even(10001) == false);
+        "#;
+
+        expect_assertions(source, &["self tail call", "mutual tail calls"]);
+    }
+
+    #[test]
+    fn tail_calls_see_the_callers_definitions() {
+        let source = r#"
+        func outer(a) {
+            func inner(b) {
+                return a + b;
+            }
+            let r = inner(1);
+            assert("normal call sees the caller's variables", r == 11);
+            return inner(2);
+        }
+
+        func scale(n) {
+            return n * factor;
+        }
+
+        func apply(n) {
+            let factor = 3;
+            return scale(n);
+        }
+
+        assert("nested tail callee sees the caller's variables", outer(10) == 12);
+        assert("tail callee sees the caller's locals", apply(2) == 6);
+        "#;
+
+        expect_assertions(
+            source,
+            &[
+                "normal call sees the caller's variables",
+                "nested tail callee sees the caller's variables",
+                "tail callee sees the caller's locals",
+            ],
+        );
+    }
+
+    #[test]
+    fn deep_recursion_raises_runtime_error() {
+        let source = r#"
+        func depth(n) {
+            return 1 + depth(n + 1);
+        }
+
+        depth(0);
+        "#;
+
+        let (result, _) = run_source(source);
+        let err = result.expect_err("unbounded recursion should fail");
+        assert!(
+            err.message.contains("Maximum call depth of 10000 exceeded"),
+            "unexpected error: {}",
+            err.message
+        );
+        assert_eq!(err.kind, RuntimeErrorKind::CallDepthExceeded);
+        assert_eq!(err.stack.len(), 10_000);
+
+        let rendered = err.to_string();
+        assert!(rendered.contains("... 9980 more frames ..."), "{}", rendered);
+        assert_eq!(rendered.matches("  at depth").count(), 20);
+    }
+
+    #[test]
+    fn max_call_depth_is_configurable() {
+        let source = r#"
+        func nested(n) {
+            if (n == 0) return 0;
+            return 1 + nested(n - 1);
+        }
+
+        assert("depth within limit", nested(5) == 5);
+        nested(10);
+        "#;
+
+        let config = InterpreterConfig {
+            max_call_depth: 8,
+            ..InterpreterConfig::default()
+        };
+        let (result, assertions) = run_source_with_config(source, config);
+        assert_eq!(assertions.len(), 1);
+        assert!(assertions[0].passed);
+        let err = result.expect_err("depth 10 should exceed a limit of 8");
+        assert_eq!(err.kind, RuntimeErrorKind::CallDepthExceeded);
+        assert!(err.message.contains("Maximum call depth of 8 exceeded"));
+    }
+
+    #[test]
+    fn deep_recursion_outgrows_the_thread_stack() {
+        // Far deeper than the 2 MiB stack of a test thread holds, and within
+        // the default call depth.
+        let source = r#"
+        func nested(n) {
+            if (n == 0) return 0;
+            return 1 + nested(n - 1);
+        }
+
+        assert("deep recursion", nested(5000) == 5000);
+        "#;
+
+        let (result, assertions) = run_source(source);
+        result.expect("deep recursion should run");
+        assert_eq!(assertions.len(), 1);
+        assert!(assertions[0].passed);
+    }
+
+    #[test]
+    fn profiler_records_calls_and_stacks() {
+        let source = r#"
+        func inner(n) {
+            return n * 2;
+        }
+
+        func outer(n) {
+            let doubled = inner(n);
+            return doubled + sin(0);
+        }
+
+        outer(1);
+        outer(2);
+        "#;
+
+        let ast = parse_source(source);
+        let mut interpreter = Interpreter::new();
+        interpreter.enable_profiler();
+        interpreter
+            .run(Some(ast.as_ref()))
+            .expect("program should run");
+
+        let profiler = interpreter.profiler().expect("profiler was enabled");
+        let calls = |name: &str| {
+            profiler
+                .functions()
+                .find(|(symbol, _)| &*symbol.resolve() == name)
+                .map(|(_, profile)| profile.calls)
+        };
+        assert_eq!(calls("outer"), Some(2));
+        assert_eq!(calls("inner"), Some(2));
+        assert_eq!(calls("sin"), Some(2));
+
+        let folded = profiler.folded_stacks();
+        let stacks: Vec<&str> = folded
+            .lines()
+            .filter_map(|line| line.rsplit_once(' ').map(|(stack, _)| stack))
+            .collect();
+        assert_eq!(stacks, vec!["outer", "outer;inner", "outer;sin"]);
+
+        // Every line with statements is profiled, not only those making calls.
+        let hits = |line: usize| {
+            profiler
+                .lines()
+                .find(|(number, _)| *number == line)
+                .map(|(_, profile)| profile.hits)
+        };
+        assert_eq!(hits(3), Some(2));
+        assert_eq!(hits(7), Some(2));
+        assert_eq!(hits(8), Some(2));
+        assert_eq!(hits(11), Some(1));
+        assert_eq!(hits(4), None);
+
+        let report = profiler.to_string();
+        assert!(report.contains("inclusive ms"), "{}", report);
+    }
+
+    #[test]
+    fn tracer_logs_statements_calls_and_definitions() {
+        let source = "\
+func twice(n) {
+    let result = n * 2;
+    return result;
+}
+func countdown(n) {
+    if (n == 0) return \"done\";
+    return countdown(n - 1);
+}
+let x = twice(4);
+countdown(1);
+";
+        let ast = parse_source(source);
+        let trace = CapturedOutput::default();
+        let mut interpreter = Interpreter::new();
+        interpreter.add_observer(Tracer::new(source, Box::new(trace.clone())));
+        interpreter.run(Some(ast.as_ref())).unwrap();
+
+        assert_eq!(
+            trace.contents(),
+            "\
+line 1: func twice(n) {
+line 5: func countdown(n) {
+line 9: let x = twice(4);
+call twice(4)
+  line 2: let result = n * 2;
+  let result = 8
+  line 3: return result;
+return twice = 8
+let x = 8
+line 10: countdown(1);
+call countdown(1)
+  line 6: if (n == 0) return \"done\";
+  line 7: return countdown(n - 1);
+return countdown via tail call
+call countdown(0)
+  line 6: if (n == 0) return \"done\";
+  line 6: if (n == 0) return \"done\";
+return countdown = \"done\"
+"
+        );
+    }
+
+    #[test]
+    fn tracer_can_follow_only_some_functions() {
+        let source = "\
+func inner(n) {
+    return n + 1;
+}
+func outer(n) {
+    return inner(n) * 2;
+}
+let a = inner(1);
+let b = outer(2);
+";
+        let ast = parse_source(source);
+        let trace = CapturedOutput::default();
+        let mut interpreter = Interpreter::new();
+        let tracer = Tracer::new(source, Box::new(trace.clone())).with_function("outer");
+        interpreter.add_observer(tracer);
+        interpreter.run(Some(ast.as_ref())).unwrap();
+
+        assert_eq!(
+            trace.contents(),
+            "\
+call outer(2)
+  line 5: return inner(n) * 2;
+  call inner(2)
+    line 2: return n + 1;
+  return inner = 3
+return outer = 6
+"
+        );
+    }
+
+    fn expect_limit(source: &str, config: InterpreterConfig, kind: RuntimeErrorKind) {
+        let (result, _) = run_source_with_config(source, config);
+        let err = result.expect_err("script should hit a resource limit");
+        assert_eq!(err.kind, kind, "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn step_limit_stops_endless_loops() {
+        let source = r#"
+        func spin(n) {
+            return spin(n + 1);
+        }
+        spin(0);
+        "#;
+
+        let config = InterpreterConfig {
+            max_steps: Some(10_000),
+            ..InterpreterConfig::default()
+        };
+        expect_limit(source, config, RuntimeErrorKind::StepLimitExceeded);
+    }
+
+    #[test]
+    fn time_limit_stops_endless_loops() {
+        let source = r#"
+        func spin(n) {
+            return spin(n + 1);
+        }
+        spin(0);
+        "#;
+
+        let config = InterpreterConfig {
+            time_limit: Some(std::time::Duration::from_millis(50)),
+            ..InterpreterConfig::default()
+        };
+        expect_limit(source, config, RuntimeErrorKind::DeadlineExceeded);
+    }
+
+    #[test]
+    fn string_length_limit_stops_exponential_growth() {
+        let source = r#"
+        func grow(s) {
+            return grow(s + s);
+        }
+        grow("ab");
+        "#;
+
+        let config = InterpreterConfig {
+            max_string_length: Some(1024),
+            ..InterpreterConfig::default()
+        };
+        expect_limit(source, config, RuntimeErrorKind::StringTooLong);
+
+        let source = r#"
+        func grow(s) {
+            return grow(str_concat(s, s));
+        }
+        grow("ab");
+        "#;
+
+        let config = InterpreterConfig {
+            max_string_length: Some(1024),
+            ..InterpreterConfig::default()
+        };
+        expect_limit(source, config, RuntimeErrorKind::StringTooLong);
+    }
+
+    #[test]
+    fn scope_and_variable_limits_stop_runaway_recursion() {
+        let source = r#"
+        func nest(n) {
+            if (true) {
+                return 1 + nest(n + 1);
+            }
+        }
+        nest(0);
+        "#;
+
+        let config = InterpreterConfig {
+            max_scopes: Some(50),
+            ..InterpreterConfig::default()
+        };
+        expect_limit(source, config, RuntimeErrorKind::ScopeLimitExceeded);
+
+        let source = r#"
+        func hoard(n) {
+            let a = n;
+            let b = n;
+            return 1 + hoard(n + 1);
+        }
+        hoard(0);
+        "#;
+
+        let config = InterpreterConfig {
+            max_variables: Some(30),
+            ..InterpreterConfig::default()
+        };
+        expect_limit(source, config, RuntimeErrorKind::VariableLimitExceeded);
+    }
+
+    #[test]
+    fn limits_allow_well_behaved_scripts() {
+        let source = r#"
+        func count_down(n) {
+            let next = n - 1;
+            if (n == 0) {
+                return "done";
+            }
+            return count_down(next);
+        }
+        assert("finished within limits", count_down(5000) == "done");
+        "#;
+
+        let config = InterpreterConfig {
+            max_steps: Some(1_000_000),
+            time_limit: Some(std::time::Duration::from_secs(10)),
+            max_string_length: Some(16),
+            max_scopes: Some(10),
+            max_variables: Some(10),
+            ..InterpreterConfig::default()
+        };
+        let (result, assertions) = run_source_with_config(source, config);
+        result.expect("script should stay within limits");
+        assert_eq!(assertions.len(), 1);
+        assert!(assertions[0].passed);
+    }
+
+    #[test]
+    fn interrupt_handle_stops_a_running_program() {
+        let endless = parse_source(
+            r#"
+            func spin(n) {
+                let next = n + 1;
+                return spin(next);
+            }
+            spin(0);
+            "#,
+        );
+
+        let mut interpreter = Interpreter::new();
+        let log = register_assert(&mut interpreter);
+        let handle = interpreter.interrupt_handle();
+        let trigger = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            handle.interrupt();
+        });
+
+        let err = interpreter
+            .run(Some(endless.as_ref()))
+            .expect_err("interrupted run should fail");
+        trigger.join().expect("trigger thread panicked");
+
+        assert_eq!(err.kind, RuntimeErrorKind::Interrupted);
+        assert_eq!(err.stack.len(), 1);
+        assert_eq!(&*err.stack[0].function.resolve(), "spin");
+
+        let follow_up = parse_source(
+            r#"
+            func add(a, b) {
+                return a + b;
+            }
+            assert("interpreter is reusable", add(1, 2) == 3);
+            "#,
+        );
+        interpreter
+            .run(Some(follow_up.as_ref()))
+            .expect("interpreter should run again after an interrupt");
+        let assertions = std::mem::take(&mut *log.lock().unwrap());
+        assert_eq!(assertions.len(), 1);
+        assert!(assertions[0].passed);
+    }
+
+    #[test]
+    fn host_functions_capture_state_per_interpreter() {
+        use std::sync::{Arc, Mutex};
+
+        let ast = parse_source(
+            r#"
+            record("first");
+            record("second");
+            "#,
+        );
+
+        let recorded = Arc::new(Mutex::new(Vec::<String>::new()));
+        let mut interpreter = Interpreter::new();
+        let sink = recorded.clone();
+        interpreter.register_function("record", move |_, args| {
+            let Value::String(text) = args[0].to_string() else {
+                unreachable!()
+            };
+            let mut sink = sink.lock().unwrap();
+            sink.push(text.to_string());
+            Ok(Value::Integer(sink.len() as i64).into_rc())
+        });
+        interpreter
+            .run(Some(ast.as_ref()))
+            .expect("host functions should be callable");
+
+        assert_eq!(
+            *recorded.lock().unwrap(),
+            vec!["first".to_string(), "second".to_string()]
+        );
+
+        let mut engine = Engine::new();
+        engine
+            .interpreter_mut()
+            .register_function("answer", |_, _| Ok(Value::Integer(42).into_rc()));
+        assert_eq!(engine.eval("answer();").unwrap(), Value::Integer(42));
+        assert_eq!(engine.eval("sin(0);").unwrap(), Value::Float(0.0));
+        assert!(
+            Engine::new().eval("answer();").is_err(),
+            "host functions must not leak to other interpreters"
+        );
+
+        let mut shadowing = Engine::new();
+        shadowing
+            .interpreter_mut()
+            .register_function("sin", |_, _| Ok(Value::Integer(-1).into_rc()));
+        assert_eq!(shadowing.eval("sin(0);").unwrap(), Value::Integer(-1));
+
+        let mut script_first = Engine::new();
+        script_first
+            .interpreter_mut()
+            .register_function("twice", |_, _| Ok(Value::Empty.into_rc()));
+        assert_eq!(
+            script_first
+                .eval("func twice(x) { return x * 2; } twice(4);")
+                .unwrap(),
+            Value::Integer(8)
+        );
+    }
+
+    #[test]
+    fn natives_can_use_the_interpreter_context() {
+        let mut engine = Engine::new();
+        engine
+            .interpreter_mut()
+            .register_function("apply_twice", |context, args| {
+                let name = args[0].to_string();
+                let Value::String(name) = name else {
+                    unreachable!()
+                };
+                let once = context.call(&name, vec![args[1].clone()])?;
+                context.call(&name, vec![once])
+            });
+        engine
+            .interpreter_mut()
+            .register_function("read_var", |context, args| {
+                let Value::String(name) = args[0].to_string() else {
+                    unreachable!()
+                };
+                context
+                    .lookup_variable(&name)
+                    .ok_or_else(|| RuntimeError::new(format!("no variable {}", name)))
+            });
+        engine
+            .interpreter_mut()
+            .register_function("stack_depth", |context, _| {
+                let names: Vec<String> = context
+                    .call_stack()
+                    .frames
+                    .iter()
+                    .map(|frame| frame.function.to_string())
+                    .collect();
+                Ok(Value::String(names.join(">").into()).into_rc())
+            });
+
+        let value = engine
+            .eval(
+                r#"
+                func increment(n) {
+                    return n + 1;
+                }
+                apply_twice("increment", 5);
+                "#,
+            )
+            .unwrap();
+        assert_eq!(value, Value::Integer(7));
+
+        let value = engine
+            .eval(
+                r#"
+                func scaled(factor) {
+                    let local = factor * 10;
+                    return read_var("local");
+                }
+                scaled(4);
+                "#,
+            )
+            .unwrap();
+        assert_eq!(value, Value::Integer(40));
+
+        let value = engine
+            .eval(
+                r#"
+                func inner() {
+                    return stack_depth();
+                }
+                func outer() {
+                    return inner() + "";
+                }
+                outer();
+                "#,
+            )
+            .unwrap();
+        assert_eq!(value, Value::String("outer>inner>stack_depth".into()));
+
+        let err = engine
+            .eval(
+                r#"
+                func fails(n) {
+                    return missing_variable;
+                }
+                apply_twice("fails", 1);
+                "#,
+            )
+            .expect_err("callback errors propagate");
+        let parser::Error::Runtime(err) = err else {
+            panic!("expected a runtime error");
+        };
+        let frames: Vec<String> = err
+            .stack
+            .iter()
+            .map(|frame| frame.function.to_string())
+            .collect();
+        assert_eq!(frames, vec!["apply_twice", "fails"]);
+    }
+
+    #[test]
+    fn simple_native_signature_is_still_supported() {
+        let mut engine = Engine::new();
+        assert_eq!(engine.eval("simple_double(21);").unwrap(), Value::Integer(42));
+    }
+
+    fn interpreter_with_io(input: &str) -> (Interpreter, CapturedOutput, CapturedOutput) {
+        let output = CapturedOutput::default();
+        let error_output = CapturedOutput::default();
+        let interpreter = Interpreter::with_io(
+            InterpreterConfig::default(),
+            Box::new(Cursor::new(input.to_string())),
+            Box::new(output.clone()),
+            Box::new(error_output.clone()),
+        );
+        (interpreter, output, error_output)
+    }
+
+    #[test]
+    fn print_builtins_write_to_the_configured_output() {
+        let (mut interpreter, output, error_output) = interpreter_with_io("");
+        let ast = parse_source(
+            r#"
+            print("a", 1, " ");
+            println(2.5, true);
+            eprintln("warning: ", 3);
+            eprint("done");
+            "#,
+        );
+
+        interpreter.run(Some(ast.as_ref())).unwrap();
+
+        assert_eq!(output.contents(), "a1 2.5true\n");
+        assert_eq!(error_output.contents(), "warning: 3\ndone");
+    }
+
+    #[test]
+    fn readln_reads_scripted_input() {
+        let (mut interpreter, output, _) = interpreter_with_io("Ada\r\n41\n");
+        let ast = parse_source(
+            r#"
+            let name = readln("name? ");
+            let age = to_number(readln("age? ")) + 1;
+            println("hello ", name, ", next year you are ", age);
+            let missing = readln();
+            println("[", missing, "]");
+            "#,
+        );
+
+        interpreter.run(Some(ast.as_ref())).unwrap();
+
+        assert_eq!(
+            output.contents(),
+            "name? age? hello Ada, next year you are 42\n[]\n"
+        );
+    }
+
+    #[test]
+    fn output_can_be_replaced_on_an_existing_interpreter() {
+        let mut engine = Engine::new();
+        let output = CapturedOutput::default();
+        engine.interpreter_mut().set_output(Box::new(output.clone()));
+        engine
+            .interpreter_mut()
+            .set_input(Box::new(Cursor::new("echo me\n")));
+
+        engine.eval("println(readln());").unwrap();
+
+        assert_eq!(output.contents(), "echo me\n");
+    }
+
+    #[test]
+    fn typed_natives_convert_arguments_and_results() {
+        let mut engine = Engine::new();
+        let interpreter = engine.interpreter_mut();
+        interpreter.register_typed_function("hypot", |a: f64, b: f64| a.hypot(b));
+        interpreter.register_typed_function("is_even", |n: i64| n % 2 == 0);
+        interpreter.register_typed_function("checked_sqrt", |x: f64| {
+            if x < 0.0 {
+                Err(RuntimeError::new("negative input"))
+            } else {
+                Ok(x.sqrt())
+            }
+        });
+        interpreter.register_typed_function("describe", |value: Value, label: Option<String>| {
+            format!("{}:{}", label.unwrap_or_default(), value.type_name())
+        });
+
+        assert_eq!(engine.eval("hypot(3, 4.0);").unwrap(), Value::Float(5.0));
+        assert_eq!(engine.eval("is_even(10);").unwrap(), Value::Boolean(true));
+        assert_eq!(engine.eval("checked_sqrt(16);").unwrap(), Value::Float(4.0));
+        assert_eq!(
+            engine.eval("describe(true);").unwrap(),
+            Value::String(":boolean".into())
+        );
+        assert_eq!(
+            engine.eval(r#"describe(1.5, "x");"#).unwrap(),
+            Value::String("x:float".into())
+        );
+        assert_eq!(
+            engine.eval(r#"typed_repeat("ab", 3);"#).unwrap(),
+            Value::String("ababab".into())
+        );
+        assert_eq!(
+            engine.eval(r#"typed_repeat("ab", 2, "-");"#).unwrap(),
+            Value::String("ab-ab".into())
+        );
+    }
+
+    #[test]
+    fn typed_natives_report_arity_and_type_errors() {
+        let mut engine = Engine::new();
+        let interpreter = engine.interpreter_mut();
+        interpreter.register_typed_function("hypot", |a: f64, b: f64| a.hypot(b));
+        interpreter.register_typed_function("is_even", |n: i64| n % 2 == 0);
+        interpreter.register_typed_function("checked_sqrt", |x: f64| {
+            if x < 0.0 {
+                Err(RuntimeError::new("negative input"))
+            } else {
+                Ok(x.sqrt())
+            }
+        });
+
+        let mut runtime_error = |source: &str| match engine.eval(source) {
+            Err(parser::Error::Runtime(err)) => err.message,
+            other => panic!("expected a runtime error, got {:?}", other),
+        };
+
+        assert_eq!(
+            runtime_error("hypot(3);"),
+            "Function 'hypot' expected 2 arguments, got 1"
+        );
+        assert_eq!(
+            runtime_error(r#"hypot(3, "4");"#),
+            "Function 'hypot' expected number for argument 2, got string"
+        );
+        assert_eq!(
+            runtime_error("is_even(2.5);"),
+            "Function 'is_even' expected integer for argument 1, got float"
+        );
+        assert_eq!(
+            runtime_error(r#"typed_repeat("a");"#),
+            "Function 'typed_repeat' expected 2 to 3 arguments, got 1"
+        );
+        assert_eq!(
+            runtime_error(r#"typed_repeat(1, 2);"#),
+            "Function 'typed_repeat' expected string for argument 1, got integer"
+        );
+        assert_eq!(runtime_error("checked_sqrt(-1);"), "negative input");
+    }
+
+    #[test]
+    fn host_can_call_script_functions() {
+        let mut interpreter = Interpreter::new();
+        let ast = parse_source(
+            r#"
+            let bonus = 5;
+            func double(n) {
+                return n * 2;
+            }
+            func score(n) {
+                return double(n) + bonus;
+            }
+            func label(name, points) {
+                return name + ": " + points;
+            }
+            func broken() {
+                return missing + 1;
+            }
+            "#,
+        );
+        interpreter.run(Some(ast.as_ref())).unwrap();
+
+        assert_eq!(
+            interpreter.call("score", &[Value::Integer(3)]).unwrap(),
+            Value::Integer(11)
+        );
+        assert_eq!(
+            interpreter
+                .call("label", &[Value::String("ada".into()), Value::Float(1.5)])
+                .unwrap(),
+            Value::String("ada: 1.5".into())
+        );
+
+        let err = interpreter.call("score", &[]).unwrap_err();
+        assert_eq!(err.message, "Function 'score' expected 1 arguments, got 0");
+
+        let err = interpreter.call("nope", &[]).unwrap_err();
+        assert_eq!(err.message, "Function not found: nope");
+
+        let err = interpreter.call("broken", &[]).unwrap_err();
+        let frames: Vec<_> = err
+            .stack
+            .iter()
+            .map(|frame| (frame.function.to_string(), frame.location))
+            .collect();
+        assert_eq!(frames, vec![("broken".to_string(), None)]);
+
+        // A failed call leaves the program's definitions in place.
+        assert_eq!(
+            interpreter.call("double", &[Value::Integer(21)]).unwrap(),
+            Value::Integer(42)
+        );
+
+        // Resetting the session forgets them.
+        interpreter.reset();
+        assert!(interpreter.call("double", &[Value::Integer(1)]).is_err());
+    }
+
+    #[test]
+    fn host_calls_respect_limits() {
+        let config = InterpreterConfig {
+            max_steps: Some(1_000),
+            ..InterpreterConfig::default()
+        };
+        let mut interpreter = Interpreter::with_config(config);
+        let ast = parse_source(
+            r#"
+            func spin(n) {
+                return spin(n + 1);
+            }
+            "#,
+        );
+        interpreter.run(Some(ast.as_ref())).unwrap();
+
+        let err = interpreter.call("spin", &[Value::Integer(0)]).unwrap_err();
+        assert_eq!(err.kind, RuntimeErrorKind::StepLimitExceeded);
+        assert!(interpreter.call("spin", &[Value::Integer(0)]).is_err());
+    }
+
+    #[test]
+    fn host_injects_and_reads_globals() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("x", Value::Integer(4));
+        interpreter.set_global("unused", Value::Boolean(true));
+        interpreter.set_readonly_global("rate", Value::Float(0.5));
+
+        let ast = parse_source(
+            r#"
+            func scaled(n) {
+                return n * rate;
+            }
+            let result = scaled(x) + 1;
+            let x = "shadowed";
+            "#,
+        );
+        interpreter.run(Some(ast.as_ref())).unwrap();
+
+        assert_eq!(interpreter.get_global("result"), Some(Value::Float(3.0)));
+        assert_eq!(interpreter.get_global("rate"), Some(Value::Float(0.5)));
+        assert_eq!(
+            interpreter.get_global("x"),
+            Some(Value::String("shadowed".into()))
+        );
+        assert_eq!(interpreter.get_global("missing"), None);
+
+        let mut globals: Vec<_> = interpreter
+            .globals()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            globals,
+            vec![
+                ("rate".to_string(), Value::Float(0.5)),
+                ("result".to_string(), Value::Float(3.0)),
+                ("unused".to_string(), Value::Boolean(true)),
+                ("x".to_string(), Value::String("shadowed".into())),
+            ]
+        );
+
+        // Injected globals outlive the session that shadowed them.
+        interpreter.reset();
+        let ast = parse_source("let doubled = x * 2;");
+        interpreter.run(Some(ast.as_ref())).unwrap();
+        assert_eq!(interpreter.get_global("doubled"), Some(Value::Integer(8)));
+        assert_eq!(interpreter.get_global("result"), None);
+    }
+
+    #[test]
+    fn readonly_globals_cannot_be_redefined() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_readonly_global("limit", Value::Integer(10));
+
+        for source in ["let limit = 1;", "func f() { let limit = 2; } f();"] {
+            let ast = parse_source(source);
+            let err = interpreter.run(Some(ast.as_ref())).unwrap_err();
+            assert_eq!(err.message, "Cannot redefine read-only global limit");
+        }
+
+        // The host can still update it, and make it writable again.
+        interpreter.set_global("limit", Value::Integer(20));
+        let ast = parse_source("let limit = limit + 1;");
+        interpreter.run(Some(ast.as_ref())).unwrap();
+        assert_eq!(interpreter.get_global("limit"), Some(Value::Integer(21)));
+    }
+
+    #[test]
+    fn definitions_persist_across_runs_until_reset() {
+        let mut engine = Engine::new();
+
+        engine.eval("let total = 10;").unwrap();
+        engine.eval("func add(n) { return total + n; }").unwrap();
+        assert_eq!(engine.eval("add(5);").unwrap(), Value::Integer(15));
+
+        // Redefining replaces the earlier definition.
+        engine.eval("let total = 100;").unwrap();
+        assert_eq!(engine.eval("add(1);").unwrap(), Value::Integer(101));
+
+        // Definitions made before a failure are kept.
+        assert!(engine.eval("let partial = 1; missing + 1;").is_err());
+        assert_eq!(engine.eval("partial + total;").unwrap(), Value::Integer(101));
+
+        engine.interpreter_mut().reset();
+        assert!(engine.eval("add(1);").is_err());
+        assert!(engine.eval("total;").is_err());
+        assert_eq!(engine.interpreter().globals().count(), 0);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn programs_and_interpreters_cross_threads() {
+        use std::{sync::Arc, thread};
+
+        fn assert_send<T: Send>() {}
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send::<Interpreter>();
+        assert_send::<Engine>();
+        assert_send_sync::<Expression>();
+        assert_send_sync::<Value>();
+
+        let ast: Arc<Expression> = Arc::from(parse_source(
+            r#"
+            func square(n) {
+                return n * n;
+            }
+            let result = square(input);
+            "#,
+        ));
+
+        let workers: Vec<_> = (1..=4)
+            .map(|input| {
+                let ast = ast.clone();
+                thread::spawn(move || {
+                    let mut interpreter = Interpreter::new();
+                    interpreter.set_global("input", Value::Integer(input));
+                    interpreter.run(Some(ast.as_ref())).unwrap();
+                    interpreter.get_global("result").unwrap()
+                })
+            })
+            .collect();
+        let results: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        assert_eq!(
+            results,
+            vec![
+                Value::Integer(1),
+                Value::Integer(4),
+                Value::Integer(9),
+                Value::Integer(16)
+            ]
+        );
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("input", Value::Integer(0));
+        interpreter.run(Some(ast.as_ref())).unwrap();
+        let moved = thread::spawn(move || interpreter.call("square", &[Value::Integer(7)]));
+        assert_eq!(moved.join().unwrap().unwrap(), Value::Integer(49));
+    }
+
+    #[test]
+    fn host_objects_are_opaque_to_scripts() {
+        use parser::{
+            interpreter::object::{HostObject, ObjectType},
+            shared::Shared,
+        };
+
+        struct Sensor {
+            name: String,
+            reading: f64,
+        }
+        struct Door;
+
+        let sensor_type = Shared::new(
+            ObjectType::new("Sensor")
+                .with_typed_method("reading", |sensor: Shared<Sensor>| sensor.reading)
+                .with_method("name", |_, args| {
+                    let sensor = match args[0].as_ref() {
+                        Value::Object(object) => object.downcast_ref::<Sensor>(),
+                        _ => None,
+                    };
+                    let name = sensor.map(|sensor| sensor.name.clone()).unwrap_or_default();
+                    Ok(Value::String(name.into()).into_rc())
+                })
+                .with_formatter(|sensor: &Sensor| format!("Sensor({})", sensor.name)),
+        );
+        let door_type = Shared::new(ObjectType::new("Door"));
+
+        let output = CapturedOutput::default();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(Box::new(output.clone()));
+        let new_sensor = sensor_type.clone();
+        interpreter.register_typed_function("sensor", move |name: String, reading: f64| {
+            HostObject::new(&new_sensor, Sensor { name, reading })
+        });
+        interpreter.register_typed_function("sensor_name", |sensor: Shared<Sensor>| {
+            sensor.name.clone()
+        });
+        interpreter.set_global("door", Value::Object(HostObject::new(&door_type, Door)));
+
+        let ast = parse_source(
+            r#"
+            let a = sensor("kitchen", 21.5);
+            let b = sensor("garage", 12);
+            let same = a;
+            println(a);
+            println("b is " + b + ", door is " + door);
+            let total = reading(a) + reading(b);
+            let names = name(a) + "/" + sensor_name(b);
+            let identical = a == same;
+            let different = a == b;
+            "#,
+        );
+        interpreter.run(Some(ast.as_ref())).unwrap();
+
+        assert_eq!(
+            output.contents(),
+            "Sensor(kitchen)\nb is Sensor(garage), door is <Door>\n"
+        );
+        assert_eq!(interpreter.get_global("total"), Some(Value::Float(33.5)));
+        assert_eq!(
+            interpreter.get_global("names"),
+            Some(Value::String("kitchen/garage".into()))
+        );
+        assert_eq!(interpreter.get_global("identical"), Some(Value::Boolean(true)));
+        assert_eq!(interpreter.get_global("different"), Some(Value::Boolean(false)));
+
+        // The host gets back the very object it created.
+        let Some(Value::Object(a)) = interpreter.get_global("a") else {
+            panic!("expected an object");
+        };
+        assert_eq!(a.type_name(), "Sensor");
+        assert_eq!(a.downcast_ref::<Sensor>().map(|s| s.reading), Some(21.5));
+        assert_eq!(interpreter.get_global("same"), Some(Value::Object(a)));
+
+        for (source, message) in [
+            ("sensor_name(door);", "Function 'sensor_name' expected Sensor for argument 1, got Door"),
+            ("reading(door);", "Method not found: reading"),
+            ("a + 1;", "Unable to convert Sensor object to number"),
+        ] {
+            let ast = parse_source(source);
+            let err = interpreter.run(Some(ast.as_ref())).unwrap_err();
+            assert_eq!(err.message, message);
+        }
+    }
+
+    #[test]
+    fn assertion_builtins_check_values() {
+        let mut engine = Engine::new();
+        engine
+            .eval(
+                r#"
+                assert("holds", 1 < 2);
+                assert_eq(2 + 2, 4);
+                assert_eq(2, 2.0, "numbers compare across types");
+                assert_near(0.1 + 0.2, 0.3);
+                assert_near(3.14, 3.1416, 0.01, "pi");
+                "#,
+            )
+            .unwrap();
+
+        let failure = |source: &str| match Engine::new().eval(source) {
+            Err(parser::Error::Runtime(err)) => {
+                assert_eq!(err.kind, RuntimeErrorKind::AssertionFailed);
+                err.message
+            }
+            other => panic!("expected an assertion failure, got {:?}", other),
+        };
+        assert_eq!(
+            failure(r#"assert("positive", -1 > 0);"#),
+            "Assertion failed: positive"
+        );
+        assert_eq!(
+            failure(r#"assert_eq("1", 1);"#),
+            "Assertion failed: expected 1, got \"1\""
+        );
+        assert_eq!(
+            failure(r#"assert_eq(3, 4, "sum");"#),
+            "Assertion failed: sum: expected 4, got 3"
+        );
+        assert_eq!(
+            failure("assert_near(1.0, 1.1, 0.05);"),
+            "Assertion failed: expected 1.1 within 0.05, got 1"
+        );
+        assert_eq!(
+            failure(r#"func fine() { return 1; } assert_throws("fine");"#),
+            "Assertion failed: expected fine to fail"
+        );
+        assert_eq!(
+            failure(r#"func boom() { return missing; } assert_throws("bomo");"#),
+            "Assertion failed: no function named bomo"
+        );
+        assert_eq!(
+            failure(r#"func boom() { return missing; } assert_throws("boom", 1, 2);"#),
+            "Assertion failed: boom takes 0 arguments, given 2"
+        );
+    }
+
+    #[test]
+    fn assert_throws_recovers_from_the_failed_call() {
+        let mut engine = Engine::new();
+        engine
+            .interpreter_mut()
+            .register_function("stack", |context, _| {
+                let names: Vec<String> = context
+                    .call_stack()
+                    .frames
+                    .iter()
+                    .map(|frame| frame.function.to_string())
+                    .collect();
+                Ok(Value::String(names.join(">").into()).into_rc())
+            });
+        let value = engine
+            .eval(
+                r#"
+                func fail(n) {
+                    let doomed = n;
+                    if (n > 0) {
+                        return fail(n - 1) + 1;
+                    }
+                    return missing;
+                }
+                func check() {
+                    let kept = 7;
+                    let message = assert_throws("fail", 3);
+                    return message + ", " + kept + ", " + stack();
+                }
+                check();
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            value,
+            Value::String("Undefined variable missing, 7, check>stack".into())
+        );
+    }
+
+    #[test]
+    fn coverage_counts_statements_and_branches() {
+        let source = "func sign(n) {\n    if (n < 0) {\n        return -1;\n    }\n    \
+                      return 1;\n}\nsign(2);\nsign(3);\n";
+        let mut engine = Engine::new();
+        engine.interpreter_mut().enable_coverage();
+        engine.eval(source).unwrap();
+
+        let coverage = engine.interpreter().coverage().unwrap();
+        assert_eq!(coverage.line_hits(2), 2);
+        assert_eq!(coverage.line_hits(3), 0);
+        assert_eq!(coverage.branch_hits(2), (0, 2));
+
+        let program = parse_source(source);
+        assert_eq!(
+            coverage.lcov("sign.rmp", &program),
+            "TN:\nSF:sign.rmp\nBRDA:2,0,0,0\nBRDA:2,0,1,2\nBRF:2\nBRH:1\n\
+             DA:1,1\nDA:2,2\nDA:3,0\nDA:5,2\nDA:7,1\nDA:8,1\nLF:6\nLH:5\nend_of_record\n"
+        );
+        assert_eq!(
+            coverage.annotate(source, &program),
+            "      1     1 | func sign(n) {\n      \
+             2     2 |     if (n < 0) {  [then 0, else 2]\n  \
+             #####     3 |         return -1;\n      \
+             -     4 |     }\n      \
+             2     5 |     return 1;\n      \
+             -     6 | }\n      \
+             1     7 | sign(2);\n      \
+             1     8 | sign(3);\n\
+             \nLines: 5 of 6 (83.3%)\nBranches: 1 of 2 (50.0%)\n"
+        );
+    }
+
+    #[test]
+    fn coverage_counts_each_run_of_a_line_once() {
+        let source = "func f(n) { if (n > 1) { return f(n - 1); } }
+                      func g(n) { if (n > 1) { return 1 + g(n - 1); } }
+                      let a = 1; let b = 2;
+                      f(5);
+                      g(4);
+";
+        let mut engine = Engine::new();
+        engine.interpreter_mut().enable_coverage();
+        engine.eval(source).unwrap();
+        engine.eval(source).unwrap();
+
+        let coverage = engine.interpreter().coverage().unwrap();
+        // Twice for the definitions, then once per call, tail call or not.
+        assert_eq!(coverage.line_hits(1), 2 + 2 * 5);
+        assert_eq!(coverage.line_hits(2), 2 + 2 * 4);
+        // Both statements run once per run.
+        assert_eq!(coverage.line_hits(3), 2);
+        assert_eq!(coverage.line_hits(4), 2);
+    }
 }
@@ -0,0 +1,65 @@
+use parser::{interpreter::value::Value, Engine, Error};
+
+#[test]
+fn eval_returns_last_expression_value() {
+    let mut engine = Engine::new();
+
+    assert_eq!(engine.eval("1 + 2;").unwrap(), Value::Integer(3));
+    assert_eq!(
+        engine
+            .eval("let name = \"rmp\"; \"hello \" + name;")
+            .unwrap(),
+        Value::String("hello rmp".into())
+    );
+    assert_eq!(
+        engine
+            .eval("func square(x) { return x * x; } square(4) + 1;")
+            .unwrap(),
+        Value::Integer(17)
+    );
+    assert_eq!(
+        engine.eval("let answer = 42; answer;").unwrap(),
+        Value::Integer(42)
+    );
+    assert_eq!(engine.eval("-2.5;").unwrap(), Value::Float(-2.5));
+}
+
+#[test]
+fn eval_returns_empty_when_last_statement_is_not_an_expression() {
+    let mut engine = Engine::new();
+
+    assert_eq!(engine.eval("1 + 2; let x = 3;").unwrap(), Value::Empty);
+    assert_eq!(engine.eval("if (true) { 5; }").unwrap(), Value::Empty);
+    assert_eq!(engine.eval("").unwrap(), Value::Empty);
+}
+
+#[test]
+fn eval_reports_each_failure_stage() {
+    let mut engine = Engine::new();
+
+    assert!(matches!(
+        engine.eval("let x = 10..1;"),
+        Err(Error::Lexer(_))
+    ));
+    assert!(matches!(engine.eval("let = 1;"), Err(Error::Parser(_))));
+    assert!(matches!(engine.eval("missing;"), Err(Error::Runtime(_))));
+    assert!(matches!(
+        engine.eval_file("does/not/exist.rmp"),
+        Err(Error::Io(_))
+    ));
+
+    // The engine is still usable after a failure.
+    assert_eq!(engine.eval("2 * 21;").unwrap(), Value::Integer(42));
+}
+
+#[test]
+fn eval_file_runs_a_script() {
+    let path = std::env::temp_dir().join(format!("engine_eval_file_{}.rmp", std::process::id()));
+    std::fs::write(&path, "func add(a, b) { return a + b; }\nadd(40, 2);\n").unwrap();
+
+    let mut engine = Engine::new();
+    let value = engine.eval_file(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(value.unwrap(), Value::Integer(42));
+}
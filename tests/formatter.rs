@@ -0,0 +1,109 @@
+use parser::{ast_dump, formatter::format_source, Engine};
+
+/// The tree of `source` without call locations, which move when lines do.
+fn shape(source: &str) -> String {
+    let ast = Engine::compile(source).unwrap();
+    ast_dump::to_text(&ast)
+        .lines()
+        .map(|line| line.split(" (line").next().unwrap())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn assert_formats(source: &str, expected: &str) {
+    let formatted = format_source(source).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(format_source(&formatted).unwrap(), formatted, "not idempotent");
+    assert_eq!(shape(&formatted), shape(source), "meaning changed");
+}
+
+#[test]
+fn layout_is_canonical() {
+    assert_formats(
+        "let x=1;let y  =  f( x,2 );\nfunc add(a,b){return a+b;}\n\
+         if(x>1)println(\"big\");else if (x == 1) {println(\"one\");} else{}",
+        "let x = 1;\n\
+         let y = f(x, 2);\n\
+         func add(a, b) {\n    return a + b;\n}\n\
+         if (x > 1) {\n    println(\"big\");\n} else if (x == 1) {\n    println(\"one\");\n} else {}\n",
+    );
+}
+
+#[test]
+fn parentheses_follow_precedence() {
+    assert_formats(
+        "let a = ((1 + 2)) * 3 - (4 - 5) + (6 * 7);",
+        "let a = (1 + 2) * 3 - (4 - 5) + 6 * 7;\n",
+    );
+    assert_formats("let b = 2 ^ (3 ^ 2) + (2 ^ 3) ^ 2;", "let b = 2 ^ 3 ^ 2 + (2 ^ 3) ^ 2;\n");
+    assert_formats("let c = -(1 + 2) * -x + !f(y);", "let c = -(1 + 2) * -x + !f(y);\n");
+    // Comparisons bind tighter than `+`; the parentheses are kept anyway.
+    assert_formats(
+        "println(\"equal: \" + (1 == 1)); let d = a == b && c;",
+        "println(\"equal: \" + (1 == 1));\nlet d = a == b && c;\n",
+    );
+    assert_formats("let e = 1.50 + 2.;", "let e = 1.5 + 2.0;\n");
+}
+
+#[test]
+fn comments_and_blank_lines_are_kept() {
+    let source = "\
+// header
+
+
+let a = 1;   // trailing
+let b = f(1, // inside a call
+    2);
+func g(x) { // after the brace
+    // leading
+
+    return x;
+
+
+}   // after the function
+if (a) { b(); } // before else
+else { c(); }
+// last
+";
+    assert_formats(
+        source,
+        "\
+// header
+
+let a = 1; // trailing
+// inside a call
+let b = f(1, 2);
+func g(x) {
+    // after the brace
+    // leading
+
+    return x;
+} // after the function
+// before else
+if (a) {
+    b();
+} else {
+    c();
+}
+// last
+",
+    );
+}
+
+#[test]
+fn the_sample_program_formats_idempotently() {
+    let source = include_str!("../program.rmp");
+    let formatted = format_source(source).unwrap();
+    assert_eq!(format_source(&formatted).unwrap(), formatted);
+    assert_eq!(shape(&formatted), shape(source));
+    assert_eq!(
+        source.matches("//").count(),
+        formatted.matches("//").count()
+    );
+}
+
+#[test]
+fn invalid_source_is_an_error() {
+    assert!(format_source("let = 1;").is_err());
+    assert!(format_source("let a = $;").is_err());
+}
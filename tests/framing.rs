@@ -0,0 +1,26 @@
+//! The `Content-Length` framing shared by the language server and the debug
+//! adapter.
+
+use serde_json::Value;
+
+/// Frames `messages` into the input of a server.
+pub fn frame(messages: &[Value]) -> Vec<u8> {
+    let mut input = Vec::new();
+    for message in messages {
+        let body = message.to_string();
+        input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).bytes());
+    }
+    input
+}
+
+/// Splits the output of a server into the messages it sent.
+pub fn unframe(output: &[u8]) -> Vec<Value> {
+    let mut text = std::str::from_utf8(output).unwrap();
+    let mut messages = Vec::new();
+    while let Some((header, rest)) = text.split_once("\r\n\r\n") {
+        let length: usize = header.trim_start_matches("Content-Length: ").parse().unwrap();
+        messages.push(serde_json::from_str(&rest[..length]).unwrap());
+        text = &rest[length..];
+    }
+    messages
+}
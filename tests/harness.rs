@@ -1,56 +1,76 @@
-use std::cell::RefCell;
-
-use parser::{
-    interpreter::{methods::{NativeFnArgs, NativeFnReturn}, runtime_errors::RuntimeError, value::Value},
-    register_method,
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
 };
 
+use parser::interpreter::{runtime_errors::RuntimeError, value::Value, Interpreter};
+
 #[derive(Clone, Debug)]
 pub struct AssertionRecord {
     pub message: String,
     pub passed: bool,
 }
 
-thread_local! {
-    static ASSERT_LOG: RefCell<Vec<AssertionRecord>> = RefCell::new(Vec::new());
-}
-
-pub fn reset_assertions() {
-    ASSERT_LOG.with(|log| log.borrow_mut().clear());
-}
+/// Shared with the registered closure; `Arc<Mutex<_>>` so the harness also
+/// builds with the `sync` feature.
+pub type AssertionLog = Arc<Mutex<Vec<AssertionRecord>>>;
 
-pub fn take_assertions() -> Vec<AssertionRecord> {
-    ASSERT_LOG.with(|log| log.borrow_mut().drain(..).collect())
-}
+/// Registers `assert(message, condition)` on `interpreter`, shadowing the
+/// builtin of the same name, and returns the log every assertion is recorded
+/// in.
+pub fn register_assert(interpreter: &mut Interpreter) -> AssertionLog {
+    let log = AssertionLog::default();
+    let records = log.clone();
 
-fn fn_assert(args: NativeFnArgs) -> Result<NativeFnReturn, RuntimeError> {
-    if args.len() != 2 {
-        return Err(RuntimeError::new(format!(
-            "assert expects 2 arguments, got {}",
-            args.len()
-        )));
-    }
+    interpreter.register_function("assert", move |_, args| {
+        if args.len() != 2 {
+            return Err(RuntimeError::new(format!(
+                "assert expects 2 arguments, got {}",
+                args.len()
+            )));
+        }
 
-    let message_value = args.get(0).unwrap().to_string();
-    let message = match message_value {
-        Value::String(rc) => rc.as_ref().to_owned(),
-        _ => unreachable!(),
-    };
+        let message_value = args.first().unwrap().to_string();
+        let message = match message_value {
+            Value::String(rc) => rc.as_ref().to_owned(),
+            _ => unreachable!(),
+        };
 
-    let passed = args.get(1).unwrap().to_bool();
+        let passed = args.get(1).unwrap().to_bool();
 
-    ASSERT_LOG.with(|log| {
-        log.borrow_mut().push(AssertionRecord {
+        records.lock().unwrap().push(AssertionRecord {
             message: message.clone(),
             passed,
-        })
+        });
+
+        if passed {
+            Ok(Value::Empty.into_rc())
+        } else {
+            Err(RuntimeError::new(message))
+        }
     });
 
-    if passed {
-        Ok(Value::Empty.into_rc())
-    } else {
-        Err(RuntimeError::new(message))
+    log
+}
+
+/// A writer that can be handed to an interpreter while the test keeps a clone
+/// to read back what was written.
+#[derive(Clone, Debug, Default)]
+pub struct CapturedOutput(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedOutput {
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).expect("output should be UTF-8")
     }
 }
 
-register_method!("assert", fn_assert);
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+mod framing;
+
+use framing::{frame, unframe};
+use std::io::Cursor;
+
+use parser::lsp::{
+    document::{Document, Position, Severity},
+    serve,
+};
+use serde_json::json;
+
+fn position(line: usize, character: usize) -> Position {
+    Position { line, character }
+}
+
+const SOURCE: &str = "let total = 0;\nfunc add(a, b) {\n    return a + b;\n}\nlet total = add(total, 2);\nprintln(total);\n";
+
+#[test]
+fn reports_parser_errors_and_analysis_warnings() {
+    let broken = Document::new("let x = ;\n".to_string());
+    assert_eq!(broken.diagnostics().len(), 1);
+    assert_eq!(broken.diagnostics()[0].severity, Severity::Error);
+
+    let document = Document::new("let x = 1;\n  missing(x);\n".to_string());
+    let diagnostics = document.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Warning);
+    assert!(diagnostics[0].message.contains("missing"));
+    assert_eq!(diagnostics[0].range.start, position(1, 2));
+    assert_eq!(diagnostics[0].range.end, position(1, 13));
+}
+
+#[test]
+fn resolves_definitions_and_hovers() {
+    let document = Document::new(SOURCE.to_string());
+
+    let parameter = document.definition(position(2, 11)).unwrap();
+    assert_eq!(parameter.name, "a");
+    assert_eq!(document.definition_range(parameter).start, position(1, 9));
+
+    let function = document.definition(position(4, 13)).unwrap();
+    assert_eq!(function.detail, "func add(a, b)");
+
+    let (_, hover) = document.hover(position(0, 5)).unwrap();
+    assert!(hover.contains("let total = 0"), "{}", hover);
+    let (_, hover) = document.hover(position(5, 2)).unwrap();
+    assert!(hover.contains("println(values...)"), "{}", hover);
+
+    let symbols: Vec<&str> = document.symbols().map(|symbol| symbol.name.as_str()).collect();
+    assert_eq!(symbols, ["total", "add", "total"]);
+
+    let labels: Vec<String> = document.completions().into_iter().map(|c| c.label).collect();
+    for expected in ["total", "add", "println", "sin", "arg", "func"] {
+        assert!(labels.iter().any(|label| label == expected), "{}", expected);
+    }
+    assert_eq!(labels.iter().filter(|label| *label == "total").count(), 1);
+}
+
+#[test]
+fn serves_a_session_over_json_rpc() {
+    let uri = "file:///tmp/main.rmp";
+    let input = frame(&[
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": {"uri": uri, "languageId": "rmp", "version": 1, "text": "missing();\n"}
+        }}),
+        json!({"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+            "textDocument": {"uri": uri, "version": 2},
+            "contentChanges": [{"text": SOURCE}]
+        }}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/definition", "params": {
+            "textDocument": {"uri": uri}, "position": {"line": 4, "character": 13}
+        }}),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "textDocument/documentSymbol", "params": {
+            "textDocument": {"uri": uri}
+        }}),
+        json!({"jsonrpc": "2.0", "id": 4, "method": "textDocument/formatting", "params": {}}),
+        json!({"jsonrpc": "2.0", "id": 5, "method": "shutdown"}),
+        json!({"jsonrpc": "2.0", "method": "exit"}),
+    ]);
+    let mut output = Vec::new();
+
+    let code = serve(Cursor::new(input), &mut output).unwrap();
+    assert_eq!(code, 0);
+
+    let messages = unframe(&output);
+    assert_eq!(messages.len(), 7);
+    assert_eq!(messages[0]["result"]["capabilities"]["hoverProvider"], true);
+
+    assert_eq!(messages[1]["method"], "textDocument/publishDiagnostics");
+    let diagnostics = messages[1]["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["severity"], 2);
+    assert_eq!(messages[2]["params"]["diagnostics"], json!([]));
+
+    assert_eq!(messages[3]["result"]["uri"], uri);
+    assert_eq!(messages[3]["result"]["range"]["start"], json!({"line": 1, "character": 5}));
+
+    let symbols = messages[4]["result"].as_array().unwrap();
+    assert_eq!(symbols.len(), 3);
+    assert_eq!(symbols[1]["name"], "add");
+    assert_eq!(symbols[1]["kind"], 12);
+
+    assert_eq!(messages[5]["error"]["code"], -32601);
+    assert_eq!(messages[6]["id"], 5);
+}
+
+#[test]
+fn exits_with_failure_without_shutdown() {
+    let input = frame(&[json!({"jsonrpc": "2.0", "method": "exit"})]);
+    let mut output = Vec::new();
+    assert_eq!(serve(Cursor::new(input), &mut output).unwrap(), 1);
+    assert!(output.is_empty());
+}
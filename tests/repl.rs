@@ -0,0 +1,85 @@
+use std::fs;
+
+use parser::repl::{Repl, Reply};
+
+fn output(text: &str) -> Reply {
+    Reply::Output(text.to_string())
+}
+
+#[test]
+fn detects_unfinished_input() {
+    assert!(Repl::is_complete("let x = 1;"));
+    assert!(!Repl::is_complete("func f(a) {\n"));
+    assert!(!Repl::is_complete("println(\"a\",\n"));
+    assert!(!Repl::is_complete("let s = \"still {open"));
+    assert!(Repl::is_complete("let s = \"{\"; // {"));
+    assert!(Repl::is_complete("func f(a) {\n  return a;\n}\n"));
+    assert!(Repl::is_complete("}"));
+}
+
+#[test]
+fn shows_expression_results_and_keeps_definitions() {
+    let mut repl = Repl::new();
+
+    assert_eq!(repl.handle("let x = 20;"), Reply::Empty);
+    assert_eq!(
+        repl.handle("func twice(n) {\n  return n * 2;\n}\n"),
+        Reply::Empty
+    );
+    assert_eq!(repl.handle("twice(x) + 2"), output("42"));
+    assert_eq!(repl.handle("\"a\" + x"), output("\"a20\""));
+    assert_eq!(repl.handle("let y = x + 1 // note"), Reply::Empty);
+    assert_eq!(repl.handle("y * 2 // double it"), output("42"));
+    assert!(matches!(
+        repl.handle("missing + 1"),
+        Reply::Error(text) if text.starts_with("Runtime Error: Undefined variable missing")
+    ));
+    assert!(matches!(
+        repl.handle("let = 1;"),
+        Reply::Error(text) if text.starts_with("Parsing error:")
+    ));
+}
+
+#[test]
+fn meta_commands_inspect_and_reset_the_session() {
+    let mut repl = Repl::new();
+    repl.handle("let rate = 0.5;");
+    repl.handle("let name = \"rmp\";");
+    repl.handle("func scale(value, factor) { return value * factor; }");
+
+    assert_eq!(repl.handle(":vars"), output("name = \"rmp\"\nrate = 0.5"));
+    assert_eq!(repl.handle(":funcs"), output("scale(value, factor)"));
+    assert_eq!(
+        repl.handle(":ast 1 + f(x)"),
+        output(
+            "Program\n  Statement\n    BinaryOperation +\n      Literal 1\n      \
+             FunctionCall f (line 1)\n        Identifier x"
+        )
+    );
+
+    assert_eq!(repl.handle(":reset"), output("Session reset"));
+    assert_eq!(repl.handle(":vars"), Reply::Empty);
+    assert_eq!(repl.handle(":funcs"), Reply::Empty);
+
+    assert_eq!(repl.handle(":quit"), Reply::Quit);
+    assert!(matches!(repl.handle(":bogus"), Reply::Error(_)));
+    assert!(matches!(repl.handle(":ast"), Reply::Error(_)));
+}
+
+#[test]
+fn load_runs_a_file_in_the_session() {
+    let path = std::env::temp_dir().join("rmp_repl_load_test.rmp");
+    fs::write(&path, "func greet(name) { return \"hi \" + name; }").unwrap();
+
+    let mut repl = Repl::new();
+    let path = path.to_str().unwrap();
+    assert_eq!(
+        repl.handle(&format!(":load {}", path)),
+        output(&format!("Loaded {}", path))
+    );
+    assert_eq!(repl.handle("greet(\"ada\")"), output("\"hi ada\""));
+    assert!(matches!(
+        repl.handle(":load /no/such/file.rmp"),
+        Reply::Error(_)
+    ));
+}